@@ -0,0 +1,41 @@
+-- Add migration script here
+
+-- Issues can now be scheduled, so `published_at` must become nullable and
+-- we need to keep track of where each issue is in its lifecycle.
+-- We have to create a temp table because SQLite doesn't support
+-- altering constraints on column.
+-- `issue_delivery_queue` references this table: defer the foreign key checks
+-- until the rebuilt table is in place.
+PRAGMA defer_foreign_keys = ON;
+
+CREATE TABLE newsletter_issues_temp (
+    newsletter_issue_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    status TEXT NOT NULL,
+    scheduled_at INTEGER NULL,
+    published_at INTEGER NULL,
+    PRIMARY KEY(newsletter_issue_id)
+);
+
+INSERT INTO newsletter_issues_temp (
+    newsletter_issue_id,
+    title,
+    text_content,
+    html_content,
+    status,
+    published_at
+)
+SELECT
+    newsletter_issue_id,
+    title,
+    text_content,
+    html_content,
+    'published',
+    CAST(published_at AS INTEGER)
+FROM newsletter_issues;
+
+DROP TABLE newsletter_issues;
+
+ALTER Table newsletter_issues_temp RENAME TO newsletter_issues;
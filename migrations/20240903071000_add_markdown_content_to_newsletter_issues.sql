@@ -0,0 +1,3 @@
+-- Add migration script here
+-- The Markdown source of an issue, when it wasn't written as HTML and plain text directly.
+ALTER TABLE newsletter_issues ADD COLUMN markdown_content TEXT NULL;
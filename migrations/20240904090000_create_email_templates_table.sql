@@ -0,0 +1,31 @@
+-- Add migration script here
+CREATE TABLE email_templates (
+    template_id TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    html_layout TEXT NOT NULL,
+    text_layout TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+-- The layout is copied over when an issue is published: editing a template
+-- must not change issues that are already on their way out.
+ALTER TABLE newsletter_issues ADD COLUMN template_id TEXT NULL REFERENCES email_templates(template_id);
+ALTER TABLE newsletter_issues ADD COLUMN html_layout TEXT NULL;
+ALTER TABLE newsletter_issues ADD COLUMN text_layout TEXT NULL;
+
+INSERT INTO email_templates (template_id, name, html_layout, text_layout, updated_at)
+VALUES (
+    '0H5KD2T0CN1QP',
+    'Default',
+    '<div style="font-family: sans-serif; max-width: 600px; margin: 0 auto;">
+{{ content }}
+<hr>
+<p style="font-size: 12px; color: #666666;">You are receiving this email because you subscribed to our newsletter. <a href="{{ unsubscribe_url }}">Unsubscribe</a>.</p>
+</div>',
+    '{{ content }}
+
+--
+You are receiving this email because you subscribed to our newsletter.
+Unsubscribe: {{ unsubscribe_url }}',
+    unixepoch()
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Open tracking is opt-in, issue by issue.
+ALTER TABLE newsletter_issues ADD COLUMN track_opens BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- What happened to an issue once it left the delivery queue, one row per event and recipient.
+CREATE TABLE delivery_events (
+    delivery_event_id TEXT NOT NULL PRIMARY KEY,
+    newsletter_issue_id TEXT NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id TEXT NOT NULL REFERENCES subscriptions (id),
+    event_type TEXT NOT NULL,
+    occurred_at INTEGER NOT NULL
+);
+CREATE INDEX delivery_events_by_issue ON delivery_events (newsletter_issue_id, event_type);
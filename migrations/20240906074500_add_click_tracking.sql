@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Click tracking is opt-in, issue by issue, and subscribers can opt out of any tracking.
+ALTER TABLE newsletter_issues ADD COLUMN track_clicks BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE subscriptions ADD COLUMN tracking_opt_out BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The original destination of a tracked link, for `clicked` events.
+ALTER TABLE delivery_events ADD COLUMN url TEXT NULL;
@@ -0,0 +1,8 @@
+-- Add migration script here
+-- When subscribers left, for the growth of the list over time.
+ALTER TABLE subscriptions ADD COLUMN unsubscribed_at INTEGER NULL;
+
+-- We do not know when earlier unsubscribes happened, they are counted as of now.
+UPDATE subscriptions
+    SET unsubscribed_at = unixepoch()
+    WHERE status = 'unsubscribed';
@@ -0,0 +1,16 @@
+-- Add migration script here
+-- Invited users have an email address, the seeded admin does not.
+ALTER TABLE users ADD COLUMN email TEXT NULL;
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'active';
+CREATE UNIQUE INDEX users_email ON users (email);
+
+-- Only a hash of the token is stored: the link in the email is as good as a password.
+CREATE TABLE invitations (
+    invitation_id TEXT NOT NULL PRIMARY KEY,
+    email TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    invited_by TEXT NOT NULL REFERENCES users (user_id),
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    accepted_at INTEGER NULL
+);
@@ -0,0 +1,8 @@
+-- Add migration script here
+-- New users get the least privileged role...
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'viewer';
+-- ...but those who could already do everything keep doing so.
+UPDATE users SET role = 'owner';
+
+-- Whoever sends an invitation chooses the role of the new user.
+ALTER TABLE invitations ADD COLUMN role TEXT NOT NULL DEFAULT 'viewer';
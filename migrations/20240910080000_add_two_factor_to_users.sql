@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Base32 TOTP secret, only set once the user proved their authenticator works.
+ALTER TABLE users ADD COLUMN totp_secret TEXT NULL;
+-- The last accepted time step: a code cannot be used twice.
+ALTER TABLE users ADD COLUMN totp_last_used_step INTEGER NULL;
+
+-- Recovery codes are hashed like passwords and can be used once each.
+CREATE TABLE recovery_codes (
+    recovery_code_id TEXT NOT NULL PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users (user_id),
+    code_hash TEXT NOT NULL,
+    used_at INTEGER NULL
+);
+CREATE INDEX recovery_codes_user_id ON recovery_codes (user_id);
@@ -0,0 +1,10 @@
+-- Add migration script here
+-- As for invitations, only a hash of the emailed token is stored.
+CREATE TABLE password_reset_tokens (
+    password_reset_token_id TEXT NOT NULL PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users (user_id),
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    used_at INTEGER NULL
+);
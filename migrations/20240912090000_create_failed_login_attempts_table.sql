@@ -0,0 +1,10 @@
+-- Add migration script here
+-- Failed logins are kept in the database, so restarting the app does not reset throttling.
+CREATE TABLE failed_login_attempts (
+    failed_login_attempt_id TEXT NOT NULL PRIMARY KEY,
+    username TEXT NOT NULL,
+    ip_address TEXT NOT NULL,
+    attempted_at INTEGER NOT NULL
+);
+CREATE INDEX failed_login_attempts_username ON failed_login_attempts (username, attempted_at);
+CREATE INDEX failed_login_attempts_ip_address ON failed_login_attempts (ip_address, attempted_at);
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Copied from the session state on each save, so sessions can be listed per user.
+ALTER TABLE sessions ADD COLUMN user_id TEXT NULL;
+-- What the session is known as in the UI: the id is the secret cookie value.
+ALTER TABLE sessions ADD COLUMN handle TEXT NULL;
+ALTER TABLE sessions ADD COLUMN ip_address TEXT NULL;
+ALTER TABLE sessions ADD COLUMN user_agent TEXT NULL;
+ALTER TABLE sessions ADD COLUMN created_at INTEGER NULL;
+ALTER TABLE sessions ADD COLUMN last_seen_at INTEGER NULL;
+CREATE INDEX sessions_user_id ON sessions (user_id);
+CREATE UNIQUE INDEX sessions_handle ON sessions (handle);
+
+-- State values are JSON-encoded strings themselves.
+UPDATE sessions SET user_id = json_extract(json_extract(session, '$.user_id'), '$');
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Expiries used to be stored as RFC 3339 strings, which compare greater than any number:
+-- sessions never expired. Unparseable ones are treated as expired.
+UPDATE sessions SET expires = COALESCE(unixepoch(expires), 0) WHERE typeof(expires) = 'text';
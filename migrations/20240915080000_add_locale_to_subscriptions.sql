@@ -0,0 +1,2 @@
+-- The language confirmation emails, subscription pages and issue headers are written in.
+ALTER TABLE subscriptions ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
@@ -0,0 +1,9 @@
+-- Confirmation emails customised by admins, one per language.
+-- Languages without a row get the message catalogue's email.
+CREATE TABLE confirmation_emails(
+    locale TEXT NOT NULL PRIMARY KEY,
+    subject TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
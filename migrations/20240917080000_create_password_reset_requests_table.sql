@@ -0,0 +1,10 @@
+-- Add migration script here
+-- Requests for reset links are throttled on their own: asking for a link must not count as a failed login.
+CREATE TABLE password_reset_requests (
+    password_reset_request_id TEXT NOT NULL PRIMARY KEY,
+    username TEXT NOT NULL,
+    ip_address TEXT NOT NULL,
+    requested_at INTEGER NOT NULL
+);
+CREATE INDEX password_reset_requests_username ON password_reset_requests (username, requested_at);
+CREATE INDEX password_reset_requests_ip_address ON password_reset_requests (ip_address, requested_at);
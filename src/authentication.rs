@@ -1,4 +1,14 @@
+pub mod csrf;
+pub mod invitation;
 pub mod middleware;
 pub use middleware::UserId;
 mod password;
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub mod password_reset;
+mod role;
+pub mod throttling;
+pub mod two_factor;
+pub use password::{
+    change_password, compute_password_hash, validate_credentials, validate_new_password, AuthError,
+    Credentials, MIN_PASSWORD_LENGTH,
+};
+pub use role::Role;
@@ -0,0 +1,92 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::{Method, StatusCode},
+    middleware::Next,
+    web, FromRequest, HttpResponse,
+};
+use askama::Template;
+
+use crate::{
+    session_state::TypedSession,
+    utils::{e500, html_page_with_status},
+};
+
+/// The form field carrying the session's CSRF token.
+pub const CSRF_FIELD: &str = "csrf_token";
+/// Scripts can send the token in this header instead.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+///
+/// The token every form posting to a protected route renders in its `csrf_token` field.
+pub fn csrf_token(session: &TypedSession) -> Result<String, actix_web::Error> {
+    session.get_or_insert_csrf_token().map_err(e500)
+}
+
+#[derive(serde::Deserialize)]
+struct CsrfForm {
+    csrf_token: Option<String>,
+}
+
+///
+/// Reject state-changing requests that do not send back the token of their session,
+/// so that other sites cannot submit forms on behalf of a logged in user.
+pub async fn verify_csrf_token(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.call(req).await;
+    }
+
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+    let expected = session.get_csrf_token().map_err(e500)?;
+    let submitted = match req.headers().get(CSRF_HEADER) {
+        Some(header) => header.to_str().ok().map(str::to_owned),
+        None => form_token(&mut req).await?,
+    };
+    match (expected, submitted) {
+        (Some(expected), Some(submitted)) if tokens_match(&expected, &submitted) => {
+            next.call(req).await
+        }
+        _ => {
+            let e = anyhow::anyhow!("The request does not carry the CSRF token of its session");
+            Err(InternalError::from_response(e, invalid_token()?).into())
+        }
+    }
+}
+
+///
+/// Read the token from the form, then put the body back for the handler.
+async fn form_token(req: &mut ServiceRequest) -> Result<Option<String>, actix_web::Error> {
+    let body = req.extract::<web::Bytes>().await?;
+    let token = serde_urlencoded::from_bytes::<CsrfForm>(&body)
+        .ok()
+        .and_then(|form| form.csrf_token);
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(body);
+    req.set_payload(payload.into());
+    Ok(token)
+}
+
+/// Compare in constant time, not to leak how much of a guess is right.
+fn tokens_match(expected: &str, submitted: &str) -> bool {
+    expected.len() == submitted.len()
+        && expected
+            .bytes()
+            .zip(submitted.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[derive(Template)]
+#[template(path = "invalid_csrf_token.html")]
+struct InvalidTokenPage;
+
+fn invalid_token() -> Result<HttpResponse, actix_web::Error> {
+    html_page_with_status(StatusCode::FORBIDDEN, &InvalidTokenPage)
+}
@@ -0,0 +1,21 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+/// How long the link in an invitation email can be used, in seconds.
+pub const INVITATION_TTL: i64 = 7 * 24 * 60 * 60;
+
+///
+/// Generate a random 32 characters long case-sensitive invitation token.
+pub fn generate_invitation_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+/// What gets stored instead of the token: a leaked database does not let anyone in.
+pub fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
@@ -1,16 +1,25 @@
 use std::ops::Deref;
 
 use actix_web::{
-    body::MessageBody,
+    body::{EitherBody, MessageBody},
     dev::{ServiceRequest, ServiceResponse},
     error::InternalError,
+    http::{header::REFERER, Method, StatusCode, Uri},
     middleware::Next,
-    FromRequest, HttpMessage,
+    web, FromRequest, HttpMessage, HttpResponse,
 };
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use askama::Template;
+use chrono::Utc;
+use sqlx::SqlitePool;
 
 use crate::{
+    authentication::Role,
+    configuration::SessionSettings,
+    session::SqlxSqliteSessionStore,
     session_state::TypedSession,
-    utils::{e500, see_other},
+    utils::{e500, html_page_with_status, see_other},
 };
 
 #[derive(Clone, Debug)]
@@ -33,21 +42,173 @@ impl Deref for UserId {
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
-) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
+    let now = Utc::now().timestamp();
+    let user_id = session.get_user_id().map_err(e500)?;
+    let role = match &user_id {
         Some(user_id) => {
+            let pool = req
+                .app_data::<web::Data<SqlitePool>>()
+                .expect("The connection pool is registered as application data.");
+            get_role(pool, user_id).await.map_err(e500)?
+        }
+        None => None,
+    };
+    match (user_id, role) {
+        (Some(_), Some(_))
+            if session
+                .has_expired(session_settings(&req), now)
+                .map_err(e500)? =>
+        {
+            // A successful response, so that the session and flash middlewares
+            // persist the purge and the message.
+            tracing::info!("The session has expired");
+            session.log_out();
+            FlashMessage::error("Your session has expired, please log in again.").send();
+            Ok(req.into_response(see_other("/login")).map_into_right_body())
+        }
+        (Some(user_id), Some(role)) => {
+            session.record_activity(now).map_err(e500)?;
+            if let Some(handle) = session.get_session_handle().map_err(e500)? {
+                let session_store = req
+                    .app_data::<web::Data<SqlxSqliteSessionStore>>()
+                    .expect("The session store is registered as application data.");
+                session_store.touch(&handle).await.map_err(e500)?;
+            }
             req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
+            req.extensions_mut().insert(role);
+            Ok(next.call(req).await?.map_into_left_body())
         }
-        None => {
+        _ => {
             let response = see_other("/login");
             let e = anyhow::anyhow!("The user has not logged in");
             Err(InternalError::from_response(e, response).into())
         }
     }
 }
+
+fn session_settings(req: &ServiceRequest) -> &SessionSettings {
+    req.app_data::<web::Data<SessionSettings>>()
+        .expect("The session settings are registered as application data.")
+}
+
+///
+/// Route guard: the password must have been entered recently, or be entered again.
+/// Must be registered within the scope of `reject_anonymous_users`, like the role guards.
+pub async fn require_recent_authentication(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+    let now = Utc::now().timestamp();
+    if !session
+        .needs_reauthentication(session_settings(&req), now)
+        .map_err(e500)?
+    {
+        return next.call(req).await;
+    }
+
+    // Come back to the page after re-authenticating. Forms cannot be replayed,
+    // so a submission returns to the page it was sent from.
+    let next_page = if req.method() == Method::GET {
+        req.uri()
+            .path_and_query()
+            .map(|p| p.as_str().to_owned())
+            .unwrap_or_default()
+    } else {
+        req.headers()
+            .get(REFERER)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|referer| referer.parse::<Uri>().ok())
+            .map(|uri| uri.path().to_owned())
+            .unwrap_or_default()
+    };
+    let response = see_other(&format!(
+        "/admin/reauthenticate?next={}",
+        urlencoding::encode(admin_page_or_dashboard(&next_page))
+    ));
+    let e = anyhow::anyhow!("The user has not authenticated recently");
+    Err(InternalError::from_response(e, response).into())
+}
+
+///
+/// Only redirect to admin pages after re-authenticating, never to another site.
+pub fn admin_page_or_dashboard(page: &str) -> &str {
+    if page.starts_with("/admin/") {
+        page
+    } else {
+        "/admin/dashboard"
+    }
+}
+
+/// The role of an active user. Deactivated users have none, whatever their session says.
+#[tracing::instrument(name = "Get the role of a user", skip(pool))]
+async fn get_role(pool: &SqlitePool, user_id: &str) -> Result<Option<Role>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT role FROM users WHERE user_id = $1 AND status = 'active'"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the role of a user.")?;
+    row.map(|r| Role::parse(&r.role).map_err(anyhow::Error::msg))
+        .transpose()
+}
+
+/// Route guard: owners and editors only.
+pub async fn require_editor(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require(req, next, Role::can_publish).await
+}
+
+/// Route guard: anyone allowed to see analytics.
+pub async fn require_analyst(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require(req, next, Role::can_see_analytics).await
+}
+
+/// Route guard: owners only.
+pub async fn require_owner(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require(req, next, Role::can_manage_users).await
+}
+
+///
+/// Let the request through if the role loaded by `reject_anonymous_users` allows it.
+/// Guards must therefore be registered within its scope.
+async fn require(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+    allowed: fn(&Role) -> bool,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let role = req.extensions().get::<Role>().copied();
+    match role {
+        Some(role) if allowed(&role) => next.call(req).await,
+        _ => {
+            let e = anyhow::anyhow!("The user's role does not allow this: {:?}", role);
+            Err(InternalError::from_response(e, forbidden()?).into())
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "forbidden.html")]
+struct ForbiddenPage;
+
+fn forbidden() -> Result<HttpResponse, actix_web::Error> {
+    html_page_with_status(StatusCode::FORBIDDEN, &ForbiddenPage)
+}
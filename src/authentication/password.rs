@@ -7,6 +7,19 @@ use sqlx::SqlitePool;
 
 use crate::telemetry::spawn_block_with_tracing;
 
+/// Users choose their own password, it has to be a reasonable one.
+pub const MIN_PASSWORD_LENGTH: usize = 12;
+
+/// Check a password a user chose, returning why it is rejected.
+pub fn validate_new_password(password: &Secret<String>) -> Result<(), String> {
+    if password.expose_secret().chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "The password must be at least {MIN_PASSWORD_LENGTH} characters long."
+        ));
+    }
+    Ok(())
+}
+
 pub struct Credentials {
     pub username: String,
     pub password: Secret<String>,
@@ -52,7 +65,7 @@ async fn get_stored_credentials(
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND status = 'active'
         "#,
         username,
     )
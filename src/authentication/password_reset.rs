@@ -0,0 +1,200 @@
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+use tsid::create_tsid;
+
+use crate::{
+    authentication::{compute_password_hash, invitation::hash_token},
+    telemetry::spawn_block_with_tracing,
+};
+
+/// How long the link in a password reset email can be used, in seconds.
+pub const PASSWORD_RESET_TTL: i64 = 60 * 60;
+/// Only the reset requests of the last hour count.
+const RESET_REQUEST_WINDOW: i64 = 60 * 60;
+/// Enough to ask again for a lost email, too few to flood a mailbox.
+const RESET_REQUESTS_PER_USERNAME: i64 = 3;
+/// Colleagues may share an address.
+const RESET_REQUESTS_PER_IP_ADDRESS: i64 = 10;
+
+///
+/// Record a request for a reset link for `username` from `ip_address`,
+/// or return `false` if too many were made recently and this one must be ignored.
+/// Failed logins are not involved: asking for links must not lock anybody out.
+#[tracing::instrument(name = "Throttle password reset requests", skip(pool))]
+pub async fn allow_reset_request(
+    pool: &SqlitePool,
+    username: &str,
+    ip_address: &str,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Sqlite connection from the pool.")?;
+    // Nothing older than the window matters anymore.
+    let since = Utc::now().timestamp() - RESET_REQUEST_WINDOW;
+    sqlx::query!(
+        r#"DELETE FROM password_reset_requests WHERE requested_at <= $1"#,
+        since
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete old password reset requests.")?;
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(username = $1), 0) AS "by_username!: i64",
+            COALESCE(SUM(ip_address = $2), 0) AS "by_ip_address!: i64"
+        FROM password_reset_requests
+        "#,
+        username,
+        ip_address
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to count password reset requests.")?;
+    if counts.by_username >= RESET_REQUESTS_PER_USERNAME
+        || counts.by_ip_address >= RESET_REQUESTS_PER_IP_ADDRESS
+    {
+        return Ok(false);
+    }
+
+    let password_reset_request_id = create_tsid().to_string();
+    let now = Utc::now().timestamp();
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_requests (password_reset_request_id, username, ip_address, requested_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        password_reset_request_id,
+        username,
+        ip_address,
+        now
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record a password reset request.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to record a password reset request.")?;
+    Ok(true)
+}
+
+#[tracing::instrument(name = "Store a password reset token", skip(pool, reset_token))]
+pub async fn store_reset_token(
+    pool: &SqlitePool,
+    user_id: &str,
+    reset_token: &str,
+) -> Result<(), anyhow::Error> {
+    let password_reset_token_id = create_tsid().to_string();
+    let token_hash = hash_token(reset_token);
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (
+            password_reset_token_id, user_id, token_hash, created_at, expires_at
+        )
+        VALUES ($1, $2, $3, unixepoch(), unixepoch() + $4)
+        "#,
+        password_reset_token_id,
+        user_id,
+        token_hash,
+        PASSWORD_RESET_TTL
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a password reset token.")?;
+    Ok(())
+}
+
+///
+/// Whether `reset_token` can still be used to reset the password of an active user.
+#[tracing::instrument(name = "Check a password reset token", skip_all)]
+pub async fn reset_token_is_valid(
+    pool: &SqlitePool,
+    reset_token: &str,
+) -> Result<bool, anyhow::Error> {
+    let token_hash = hash_token(reset_token);
+    let row = sqlx::query!(
+        r#"
+        SELECT t.user_id
+        FROM password_reset_tokens t
+        JOIN users u ON u.user_id = t.user_id
+        WHERE t.token_hash = $1
+            AND t.used_at IS NULL
+            AND t.expires_at > unixepoch()
+            AND u.status = 'active'
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve a password reset token.")?;
+    Ok(row.is_some())
+}
+
+///
+/// Use `reset_token` to set a new password, returning whose password it was.
+/// Returns `None` if the token cannot be used, e.g. because it already was.
+#[tracing::instrument(name = "Reset a password", skip_all)]
+pub async fn reset_password(
+    pool: &SqlitePool,
+    reset_token: &str,
+    password: Secret<String>,
+) -> Result<Option<String>, anyhow::Error> {
+    let password_hash = spawn_block_with_tracing(move || compute_password_hash(password))
+        .await?
+        .context("Failed to hash password")?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Sqlite connection from the pool.")?;
+    let token_hash = hash_token(reset_token);
+    let Some(row) = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = unixepoch()
+        WHERE token_hash = $1
+            AND used_at IS NULL
+            AND expires_at > unixepoch()
+            AND user_id IN (SELECT user_id FROM users WHERE status = 'active')
+        RETURNING user_id
+        "#,
+        token_hash
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to use a password reset token.")?
+    else {
+        return Ok(None);
+    };
+
+    let password_hash = password_hash.expose_secret();
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash,
+        row.user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to change user's password in the database.")?;
+    // Other links sent before this one are no good anymore either.
+    sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = unixepoch()
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        row.user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to invalidate other password reset tokens.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reset a password.")?;
+    Ok(Some(row.user_id))
+}
@@ -0,0 +1,90 @@
+/// What a user is allowed to do in the admin panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Everything, including managing users.
+    Owner,
+    /// Write, publish and manage issues and templates.
+    Editor,
+    /// Read-only access, plus analytics.
+    Analyst,
+    /// Read-only access.
+    Viewer,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Owner, Role::Editor, Role::Analyst, Role::Viewer];
+
+    pub fn parse(s: &str) -> Result<Role, String> {
+        match s {
+            "owner" => Ok(Role::Owner),
+            "editor" => Ok(Role::Editor),
+            "analyst" => Ok(Role::Analyst),
+            "viewer" => Ok(Role::Viewer),
+            other => Err(format!("{other} is not a known role.")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Editor => "editor",
+            Role::Analyst => "analyst",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn can_publish(&self) -> bool {
+        matches!(self, Role::Owner | Role::Editor)
+    }
+
+    pub fn can_see_analytics(&self) -> bool {
+        matches!(self, Role::Owner | Role::Editor | Role::Analyst)
+    }
+
+    pub fn can_manage_users(&self) -> bool {
+        matches!(self, Role::Owner)
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok_eq};
+
+    use crate::authentication::Role;
+
+    #[test]
+    fn roles_round_trip() {
+        for role in Role::ALL {
+            assert_ok_eq!(Role::parse(role.as_str()), role);
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        assert_err!(Role::parse("admin"));
+        assert_err!(Role::parse("Owner"));
+    }
+
+    #[test]
+    fn only_owners_manage_users() {
+        let managers: Vec<_> = Role::ALL
+            .into_iter()
+            .filter(Role::can_manage_users)
+            .collect();
+        assert_eq!(managers, [Role::Owner]);
+    }
+
+    #[test]
+    fn viewers_can_only_look() {
+        assert!(!Role::Viewer.can_publish());
+        assert!(!Role::Viewer.can_see_analytics());
+        assert!(Role::Analyst.can_see_analytics());
+        assert!(!Role::Analyst.can_publish());
+    }
+}
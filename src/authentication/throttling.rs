@@ -0,0 +1,183 @@
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use tsid::create_tsid;
+
+/// Only failures from the last hour count.
+const FAILURE_WINDOW: i64 = 60 * 60;
+/// How long a locked username or address has to wait after its last failure.
+const LOCKOUT_DURATION: i64 = 15 * 60;
+/// The longest progressive delay before the lockout kicks in.
+const MAX_DELAY: i64 = 60;
+
+struct Policy {
+    /// Failures tolerated before each attempt has to wait.
+    free_attempts: i64,
+    /// Failures after which attempts are refused for `LOCKOUT_DURATION`.
+    lockout_after: i64,
+}
+
+/// A single account only needs a few tries.
+const USERNAME_POLICY: Policy = Policy {
+    free_attempts: 3,
+    lockout_after: 10,
+};
+/// Colleagues may share an address, but one address trying many usernames is suspicious.
+const IP_ADDRESS_POLICY: Policy = Policy {
+    free_attempts: 10,
+    lockout_after: 50,
+};
+
+///
+/// How many seconds after the last failure the next attempt is allowed.
+/// The delay doubles with each failure past the free ones.
+fn required_delay(failures: i64, policy: &Policy) -> i64 {
+    if failures >= policy.lockout_after {
+        LOCKOUT_DURATION
+    } else if failures >= policy.free_attempts {
+        (1 << (failures - policy.free_attempts).min(6)).min(MAX_DELAY)
+    } else {
+        0
+    }
+}
+
+fn seconds_to_wait(failures: i64, last_failure_at: Option<i64>, policy: &Policy, now: i64) -> i64 {
+    match last_failure_at {
+        Some(last_failure_at) => (last_failure_at + required_delay(failures, policy) - now).max(0),
+        None => 0,
+    }
+}
+
+///
+/// Return how many seconds to wait before `username` can be tried from `ip_address`,
+/// or `None` if the attempt can go ahead.
+/// Throttling only depends on what was submitted, so it does not tell whether a username exists.
+#[tracing::instrument(name = "Check login throttling", skip(pool))]
+pub async fn login_retry_after(
+    pool: &SqlitePool,
+    username: &str,
+    ip_address: &str,
+) -> Result<Option<i64>, anyhow::Error> {
+    let now = Utc::now().timestamp();
+    let since = now - FAILURE_WINDOW;
+    let by_username = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "failures!: i64", MAX(attempted_at) AS "last_failure_at: i64"
+        FROM failed_login_attempts
+        WHERE username = $1 AND attempted_at > $2
+        "#,
+        username,
+        since
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count failed logins for a username.")?;
+    let by_ip_address = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "failures!: i64", MAX(attempted_at) AS "last_failure_at: i64"
+        FROM failed_login_attempts
+        WHERE ip_address = $1 AND attempted_at > $2
+        "#,
+        ip_address,
+        since
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count failed logins for an IP address.")?;
+
+    let wait = seconds_to_wait(
+        by_username.failures,
+        by_username.last_failure_at,
+        &USERNAME_POLICY,
+        now,
+    )
+    .max(seconds_to_wait(
+        by_ip_address.failures,
+        by_ip_address.last_failure_at,
+        &IP_ADDRESS_POLICY,
+        now,
+    ));
+    Ok((wait > 0).then_some(wait))
+}
+
+#[tracing::instrument(name = "Record a failed login", skip(pool))]
+pub async fn record_failed_login(
+    pool: &SqlitePool,
+    username: &str,
+    ip_address: &str,
+) -> Result<(), anyhow::Error> {
+    let failed_login_attempt_id = create_tsid().to_string();
+    let now = Utc::now().timestamp();
+    sqlx::query!(
+        r#"
+        INSERT INTO failed_login_attempts (failed_login_attempt_id, username, ip_address, attempted_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        failed_login_attempt_id,
+        username,
+        ip_address,
+        now
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a failed login.")?;
+    // Nothing older than the window matters anymore.
+    let expired = now - FAILURE_WINDOW;
+    sqlx::query!(
+        r#"DELETE FROM failed_login_attempts WHERE attempted_at < $1"#,
+        expired
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete old failed logins.")?;
+    Ok(())
+}
+
+///
+/// Forget the failures of a username once its password was entered correctly.
+#[tracing::instrument(name = "Clear failed logins", skip(pool))]
+pub async fn clear_failed_logins(pool: &SqlitePool, username: &str) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM failed_login_attempts WHERE username = $1"#,
+        username
+    )
+    .execute(pool)
+    .await
+    .context("Failed to clear failed logins.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_failures_are_free() {
+        for failures in 0..USERNAME_POLICY.free_attempts {
+            assert_eq!(required_delay(failures, &USERNAME_POLICY), 0);
+        }
+    }
+
+    #[test]
+    fn the_delay_doubles_up_to_a_maximum() {
+        let delays: Vec<_> = (3..10)
+            .map(|failures| required_delay(failures, &USERNAME_POLICY))
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60]);
+    }
+
+    #[test]
+    fn too_many_failures_lock_out() {
+        assert_eq!(required_delay(10, &USERNAME_POLICY), LOCKOUT_DURATION);
+        assert_eq!(required_delay(50, &IP_ADDRESS_POLICY), LOCKOUT_DURATION);
+        assert!(required_delay(49, &IP_ADDRESS_POLICY) <= MAX_DELAY);
+    }
+
+    #[test]
+    fn the_wait_is_counted_from_the_last_failure() {
+        let now = 1_725_000_000;
+        assert_eq!(seconds_to_wait(5, Some(now - 1), &USERNAME_POLICY, now), 3);
+        assert_eq!(seconds_to_wait(5, Some(now - 10), &USERNAME_POLICY, now), 0);
+        assert_eq!(seconds_to_wait(0, None, &USERNAME_POLICY, now), 0);
+    }
+}
@@ -0,0 +1,360 @@
+use anyhow::Context;
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use qrcode::{render::svg, QrCode};
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+use totp_rs::{Algorithm, Secret as TotpSecret, TOTP};
+use tsid::create_tsid;
+
+use crate::{authentication::compute_password_hash, telemetry::spawn_block_with_tracing};
+
+/// The name authenticator apps show next to the account.
+const TOTP_ISSUER: &str = "zero2prod";
+/// RFC 6238 defaults, which is what every authenticator app expects.
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+/// How many steps away from ours a code may be, to forgive clock drift.
+const TOTP_SKEW: u64 = 1;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// No 0/o or 1/l, recovery codes are typed from paper.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+///
+/// Generate a random 160 bits TOTP secret, base32 encoded.
+pub fn generate_totp_secret() -> String {
+    TotpSecret::generate_secret().to_encoded().to_string()
+}
+
+fn build_totp(secret: &str, account_name: &str) -> Result<TOTP, anyhow::Error> {
+    let secret = TotpSecret::Encoded(secret.to_string())
+        .to_bytes()
+        .context("Failed to decode the TOTP secret.")?;
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .context("Failed to build the TOTP.")
+}
+
+///
+/// The `otpauth://` URI authenticator apps enrol from.
+pub fn provisioning_uri(secret: &str, username: &str) -> Result<String, anyhow::Error> {
+    // Colons separate the issuer from the account name in the URI.
+    Ok(build_totp(secret, &username.replace(':', ""))?.get_url())
+}
+
+///
+/// Render the provisioning URI as an inline SVG QR code.
+pub fn qr_code_svg(provisioning_uri: &str) -> Result<String, anyhow::Error> {
+    let svg = QrCode::new(provisioning_uri.as_bytes())
+        .context("Failed to encode the provisioning URI as a QR code.")?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    // The XML declaration has no business inside an HTML page.
+    Ok(svg
+        .trim_start_matches(r#"<?xml version="1.0" standalone="yes"?>"#)
+        .to_string())
+}
+
+///
+/// Return the time step `code` is valid for around `now` (unix seconds), if any.
+pub fn matching_step(secret: &str, code: &str, now: u64) -> Result<Option<u64>, anyhow::Error> {
+    let totp = build_totp(secret, "")?;
+    let current_step = now / TOTP_STEP;
+    let step = (current_step.saturating_sub(TOTP_SKEW)..=current_step + TOTP_SKEW)
+        .find(|step| totp.check(code.trim(), step * TOTP_STEP));
+    Ok(step)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("System time is before the unix epoch.")
+        .as_secs()
+}
+
+///
+/// Return a TOTP time step usable from now, or `None` if `code` is not valid.
+pub fn current_matching_step(secret: &str, code: &str) -> Result<Option<u64>, anyhow::Error> {
+    matching_step(secret, code, unix_now())
+}
+
+///
+/// Generate a fresh set of recovery codes, e.g. `k3x9p-2mq7v`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = thread_rng();
+    let mut chunk = || -> String {
+        (0..5)
+            .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+            .collect()
+    };
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| format!("{}-{}", chunk(), chunk()))
+        .collect()
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_lowercase()
+}
+
+#[tracing::instrument(
+    name = "Check whether two-factor authentication is enabled",
+    skip(pool)
+)]
+pub async fn two_factor_enabled(user_id: &str, pool: &SqlitePool) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT totp_secret
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the TOTP secret.")?;
+    Ok(row.and_then(|r| r.totp_secret).is_some())
+}
+
+///
+/// Store a confirmed TOTP secret and replace the user's recovery codes.
+/// Returns the recovery codes in clear: this is the only time they are available.
+#[tracing::instrument(name = "Enable two-factor authentication", skip(secret, pool))]
+pub async fn enable_two_factor(
+    user_id: &str,
+    secret: &str,
+    confirmed_step: u64,
+    pool: &SqlitePool,
+) -> Result<Vec<String>, anyhow::Error> {
+    let recovery_codes = generate_recovery_codes();
+    let codes = recovery_codes.clone();
+    let hashes = spawn_block_with_tracing(move || {
+        codes
+            .into_iter()
+            .map(|code| compute_password_hash(Secret::new(code)))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .context("Failed to spawn blocking task.")??;
+
+    let confirmed_step = confirmed_step as i64;
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = $1, totp_last_used_step = $2
+        WHERE user_id = $3
+        "#,
+        secret,
+        confirmed_step,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the TOTP secret.")?;
+    sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete previous recovery codes.")?;
+    for hash in hashes {
+        let recovery_code_id = create_tsid().to_string();
+        let hash = hash.expose_secret();
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (recovery_code_id, user_id, code_hash)
+            VALUES ($1, $2, $3)
+            "#,
+            recovery_code_id,
+            user_id,
+            hash
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to store a recovery code.")?;
+    }
+    transaction.commit().await?;
+    Ok(recovery_codes)
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(pool))]
+pub async fn disable_two_factor(user_id: &str, pool: &SqlitePool) -> Result<(), anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = NULL, totp_last_used_step = NULL
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to remove the TOTP secret.")?;
+    sqlx::query!("DELETE FROM recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete recovery codes.")?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+///
+/// Check a code from the user's authenticator app, or else one of their unused recovery codes.
+/// Either can only be used once.
+#[tracing::instrument(name = "Verify second factor", skip(code, pool))]
+pub async fn verify_second_factor(
+    user_id: &str,
+    code: Secret<String>,
+    pool: &SqlitePool,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT totp_secret, totp_last_used_step
+        FROM users
+        WHERE user_id = $1 AND status = 'active'
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the TOTP secret.")?;
+    let Some(secret) = row.as_ref().and_then(|r| r.totp_secret.as_deref()) else {
+        return Ok(false);
+    };
+
+    if let Some(step) = current_matching_step(secret, code.expose_secret())? {
+        let step = step as i64;
+        // Only moving the last used step forward accepts the code, so it cannot be replayed.
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_last_used_step = $1
+            WHERE user_id = $2
+                AND (totp_last_used_step IS NULL OR totp_last_used_step < $1)
+            "#,
+            step,
+            user_id
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record the TOTP step.")?;
+        return Ok(result.rows_affected() == 1);
+    }
+
+    use_recovery_code(user_id, code, pool).await
+}
+
+#[tracing::instrument(name = "Use recovery code", skip(code, pool))]
+async fn use_recovery_code(
+    user_id: &str,
+    code: Secret<String>,
+    pool: &SqlitePool,
+) -> Result<bool, anyhow::Error> {
+    let candidates = sqlx::query!(
+        r#"
+        SELECT recovery_code_id, code_hash
+        FROM recovery_codes
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve recovery codes.")?
+    .into_iter()
+    .map(|r| (r.recovery_code_id, r.code_hash))
+    .collect::<Vec<_>>();
+
+    let code = normalize_recovery_code(code.expose_secret());
+    let matching_id = spawn_block_with_tracing(move || {
+        candidates
+            .into_iter()
+            .find_map(|(recovery_code_id, code_hash)| {
+                let hash = PasswordHash::new(&code_hash).ok()?;
+                Argon2::default()
+                    .verify_password(code.as_bytes(), &hash)
+                    .ok()
+                    .map(|_| recovery_code_id)
+            })
+    })
+    .await
+    .context("Failed to spawn blocking task.")?;
+
+    let Some(recovery_code_id) = matching_id else {
+        return Ok(false);
+    };
+    let result = sqlx::query!(
+        r#"
+        UPDATE recovery_codes
+        SET used_at = unixepoch()
+        WHERE recovery_code_id = $1 AND used_at IS NULL
+        "#,
+        recovery_code_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark the recovery code as used.")?;
+    Ok(result.rows_affected() == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_generated_secret_can_be_used() {
+        let secret = generate_totp_secret();
+        let uri = provisioning_uri(&secret, "ursula").unwrap();
+        assert!(uri.starts_with("otpauth://totp/zero2prod:ursula?"));
+        assert!(uri.contains(&format!("secret={secret}")));
+    }
+
+    #[test]
+    fn codes_from_neighbouring_steps_are_accepted() {
+        let secret = generate_totp_secret();
+        let totp = build_totp(&secret, "").unwrap();
+        let now = 1_725_000_015;
+        let step = now / TOTP_STEP;
+
+        for s in [step - 1, step, step + 1] {
+            let code = totp.generate(s * TOTP_STEP);
+            assert_eq!(matching_step(&secret, &code, now).unwrap(), Some(s));
+        }
+    }
+
+    #[test]
+    fn codes_from_distant_steps_are_rejected() {
+        let secret = generate_totp_secret();
+        let totp = build_totp(&secret, "").unwrap();
+        let now = 1_725_000_015;
+
+        let code = totp.generate(now - 2 * TOTP_STEP);
+        assert_eq!(matching_step(&secret, &code, now).unwrap(), None);
+        assert_eq!(matching_step(&secret, "not-a-code", now).unwrap(), None);
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_unambiguous() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            assert_eq!(code.len(), 11);
+            assert!(!code.contains(['0', 'o', '1', 'l']));
+        }
+        let unique: std::collections::HashSet<_> = codes.iter().collect();
+        assert_eq!(unique.len(), RECOVERY_CODE_COUNT);
+    }
+
+    #[test]
+    fn recovery_codes_are_normalized() {
+        assert_eq!(normalize_recovery_code("  K3X9P-2MQ7V\n"), "k3x9p-2mq7v");
+    }
+}
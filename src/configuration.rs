@@ -1,3 +1,6 @@
+use std::num::{NonZeroU32, NonZeroU64};
+
+use actix_web::cookie::SameSite;
 use config::ConfigError;
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
@@ -11,6 +14,7 @@ pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub session_cleanup: SessionCleanupSettings,
 }
 
 #[derive(Deserialize, Clone)]
@@ -26,6 +30,65 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// The header a trusted reverse proxy puts the client's address in, e.g. `Fly-Client-IP`.
+    /// Without one, the peer address of the connection is the client's.
+    pub client_ip_header: Option<String>,
+    pub session: SessionSettings,
+    pub security_headers: SecurityHeadersSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SecurityHeadersSettings {
+    /// For the admin and login pages. Previews of issues load remote images and inline styles.
+    pub admin_content_security_policy: String,
+    /// For the archive, feeds, tracking links and the other public pages.
+    pub public_content_security_policy: String,
+    /// Only sent when `base_url` is https. 0 disables HSTS.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub hsts_max_age_seconds: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SessionSettings {
+    pub cookie_name: String,
+    /// Only send the cookie over HTTPS. Off locally, where there is no TLS.
+    pub cookie_secure: bool,
+    pub cookie_same_site: CookieSameSite,
+    pub cookie_domain: Option<String>,
+    /// Log out users who have not done anything for this long.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idle_timeout_seconds: i64,
+    /// Log out users this long after they logged in, however active they are.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub absolute_lifetime_seconds: i64,
+    /// How recently the password must have been entered for sensitive actions.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub reauthentication_window_seconds: i64,
+    /// Sessions kept in memory in front of the database. 0 disables the cache.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cache_capacity: usize,
+    /// Session expiries are only written back when they move by at least this much.
+    /// Only with the cache: without it, every expiry is written.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub expiry_write_threshold_seconds: i64,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<CookieSameSite> for SameSite {
+    fn from(same_site: CookieSameSite) -> Self {
+        match same_site {
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::Lax => SameSite::Lax,
+            CookieSameSite::None => SameSite::None,
+        }
+    }
 }
 
 #[derive(Deserialize, Clone)]
@@ -36,6 +99,22 @@ pub struct EmailClientSettings {
     pub timeout_milliseconds: u64,
 }
 
+/// Both are non-zero: a zero interval cannot be ticked and a zero batch never ends the cleanup.
+#[derive(Deserialize, Clone)]
+pub struct SessionCleanupSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub interval_seconds: NonZeroU64,
+    /// Expired sessions deleted per statement, to keep write locks short.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub batch_size: NonZeroU32,
+}
+
+impl SessionCleanupSettings {
+    pub fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.interval_seconds.get())
+    }
+}
+
 impl EmailClientSettings {
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use sqlx::SqlitePool;
+
+use crate::{
+    i18n::{Locale, Message},
+    templating::{
+        Escaping, MergeTemplate, TemplateError, CONFIRMATION_LINK_VARIABLE, CONFIRMATION_VARIABLES,
+    },
+};
+
+///
+/// The email asking a new subscriber to confirm their subscription, in one language.
+/// Admins can customise it: the message catalogue's is sent until they do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmationEmail {
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+}
+
+impl ConfirmationEmail {
+    pub fn default_for(locale: Locale) -> ConfirmationEmail {
+        Self {
+            subject: locale.message(Message::ConfirmationEmailSubject).into(),
+            html_content: locale.message(Message::ConfirmationEmailHtml).into(),
+            text_content: locale.message(Message::ConfirmationEmailText).into(),
+        }
+    }
+
+    /// Both versions must link to the confirmation page, or nobody could ever confirm.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.subject.trim().is_empty() {
+            return Err("The confirmation email must have a subject.".into());
+        }
+        parse(&self.html_content).map_err(|e| format!("The HTML version is invalid: {e}"))?;
+        parse(&self.text_content).map_err(|e| format!("The plain text version is invalid: {e}"))?;
+        Ok(())
+    }
+
+    /// Fill in the confirmation link, returning the HTML and plain text bodies.
+    pub fn render(&self, confirmation_link: &str) -> Result<(String, String), TemplateError> {
+        let values = HashMap::from([(CONFIRMATION_LINK_VARIABLE, confirmation_link.to_string())]);
+        Ok((
+            parse(&self.html_content)?.render(&values, Escaping::Html),
+            parse(&self.text_content)?.render(&values, Escaping::PlainText),
+        ))
+    }
+}
+
+fn parse(content: &str) -> Result<MergeTemplate, TemplateError> {
+    let template = MergeTemplate::parse(content, &CONFIRMATION_VARIABLES)?;
+    template.require(CONFIRMATION_LINK_VARIABLE)?;
+    Ok(template)
+}
+
+pub fn confirmation_link(base_url: &str, subscription_token: &str) -> String {
+    format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token
+    )
+}
+
+///
+/// The email new subscribers get in `locale`.
+/// A customised email that does not validate, e.g. because it was edited in the database,
+/// is ignored in favour of the catalogue's: the confirmation link is never left out.
+#[tracing::instrument(name = "Get the confirmation email", skip(pool))]
+pub async fn get_confirmation_email(
+    pool: &SqlitePool,
+    locale: Locale,
+) -> Result<ConfirmationEmail, anyhow::Error> {
+    match get_customised_confirmation_email(pool, locale).await? {
+        Some(email) => match email.validate() {
+            Ok(()) => Ok(email),
+            Err(e) => {
+                tracing::warn!(
+                    error.message = %e,
+                    "The customised confirmation email is invalid: the default one is sent"
+                );
+                Ok(ConfirmationEmail::default_for(locale))
+            }
+        },
+        None => Ok(ConfirmationEmail::default_for(locale)),
+    }
+}
+
+#[tracing::instrument(name = "Get the customised confirmation email", skip(pool))]
+pub async fn get_customised_confirmation_email(
+    pool: &SqlitePool,
+    locale: Locale,
+) -> Result<Option<ConfirmationEmail>, anyhow::Error> {
+    let locale = locale.code();
+    let email = sqlx::query_as!(
+        ConfirmationEmail,
+        r#"
+        SELECT subject, html_content, text_content
+        FROM confirmation_emails
+        WHERE locale = $1
+        "#,
+        locale
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the confirmation email.")?;
+    Ok(email)
+}
+
+#[tracing::instrument(name = "Save the confirmation email", skip(pool, email))]
+pub async fn save_confirmation_email(
+    pool: &SqlitePool,
+    locale: Locale,
+    email: &ConfirmationEmail,
+) -> Result<(), anyhow::Error> {
+    let locale = locale.code();
+    sqlx::query!(
+        r#"
+        INSERT INTO confirmation_emails (locale, subject, html_content, text_content, updated_at)
+        VALUES ($1, $2, $3, $4, unixepoch())
+        ON CONFLICT (locale) DO UPDATE SET
+            subject = excluded.subject,
+            html_content = excluded.html_content,
+            text_content = excluded.text_content,
+            updated_at = excluded.updated_at
+        "#,
+        locale,
+        email.subject,
+        email.html_content,
+        email.text_content
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the confirmation email.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_ok;
+
+    use crate::{confirmation_email::ConfirmationEmail, i18n::Locale};
+
+    fn email(html_content: &str, text_content: &str) -> ConfirmationEmail {
+        ConfirmationEmail {
+            subject: "Welcome".into(),
+            html_content: html_content.into(),
+            text_content: text_content.into(),
+        }
+    }
+
+    #[test]
+    fn the_default_emails_are_valid() {
+        for locale in Locale::ALL {
+            assert_ok!(ConfirmationEmail::default_for(locale).validate());
+        }
+    }
+
+    #[test]
+    fn the_confirmation_link_cannot_be_left_out() {
+        assert_eq!(
+            email("<p>Welcome!</p>", "Confirm: {{ confirmation_link }}").validate(),
+            Err(
+                "The HTML version is invalid: The template must contain `{{ confirmation_link }}`."
+                    .into()
+            )
+        );
+        assert_eq!(
+            email(r#"<a href="{{confirmation_link}}">Confirm</a>"#, "Welcome!").validate(),
+            Err("The plain text version is invalid: The template must contain `{{ confirmation_link }}`.".into())
+        );
+    }
+
+    #[test]
+    fn only_the_confirmation_link_can_be_merged() {
+        assert_eq!(
+            email(
+                "{{ confirmation_link }} {{ name }}",
+                "{{ confirmation_link }}"
+            )
+            .validate(),
+            Err("The HTML version is invalid: `{{ name }}` is not a known merge variable.".into())
+        );
+    }
+
+    #[test]
+    fn the_subject_cannot_be_empty() {
+        let email = ConfirmationEmail {
+            subject: " ".into(),
+            ..ConfirmationEmail::default_for(Locale::English)
+        };
+        assert_eq!(
+            email.validate(),
+            Err("The confirmation email must have a subject.".into())
+        );
+    }
+
+    #[test]
+    fn the_link_is_escaped_in_html_only() {
+        let (html, text) = email(
+            r#"<a href="{{ confirmation_link }}">Confirm</a>"#,
+            "Visit {{ confirmation_link }}",
+        )
+        .render("https://example.com/?a=1&b=2")
+        .unwrap();
+        assert_eq!(
+            html,
+            r#"<a href="https&#x3A;&#x2F;&#x2F;example&#x2E;com&#x2F;&#x3F;a&#x3D;1&amp;b&#x3D;2">Confirm</a>"#
+        );
+        assert_eq!(text, "Visit https://example.com/?a=1&b=2");
+    }
+}
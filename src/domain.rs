@@ -1,3 +1,5 @@
+pub mod issue_content;
 pub mod new_subscriber;
+pub mod scheduled_at;
 pub mod subscriber_email;
 pub mod subscriber_name;
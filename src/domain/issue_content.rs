@@ -0,0 +1,104 @@
+use crate::markdown;
+
+/// The body of a newsletter issue, in both the formats we send out.
+///
+/// Editors either write Markdown, which we render to HTML and plain text, or
+/// hand-write both versions themselves.
+#[derive(Debug)]
+pub struct IssueContent {
+    pub markdown_content: Option<String>,
+    pub html_content: String,
+    pub text_content: String,
+}
+
+impl IssueContent {
+    pub fn parse(
+        markdown_content: String,
+        html_content: String,
+        text_content: String,
+    ) -> Result<IssueContent, String> {
+        let is_blank = |s: &str| s.trim().is_empty();
+        match (
+            is_blank(&markdown_content),
+            is_blank(&html_content),
+            is_blank(&text_content),
+        ) {
+            (false, true, true) => Ok(Self::from_markdown(markdown_content)),
+            (false, _, _) => {
+                Err("Use either Markdown or the HTML and plain text fields, not both.".into())
+            }
+            (true, false, false) => Ok(Self {
+                markdown_content: None,
+                html_content,
+                text_content,
+            }),
+            (true, _, _) => Err(
+                "The issue content must be provided either as Markdown or as both HTML and plain text."
+                    .into(),
+            ),
+        }
+    }
+
+    fn from_markdown(markdown_content: String) -> IssueContent {
+        Self {
+            html_content: markdown::render_html(&markdown_content),
+            text_content: markdown::render_text(&markdown_content),
+            markdown_content: Some(markdown_content),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use crate::domain::issue_content::IssueContent;
+
+    #[test]
+    fn markdown_is_rendered_to_both_formats() {
+        let content = IssueContent::parse("# Hello".into(), "".into(), "".into()).unwrap();
+        assert_eq!(content.markdown_content.as_deref(), Some("# Hello"));
+        assert_eq!(content.html_content.trim(), "<h1>Hello</h1>");
+        assert_eq!(content.text_content, "Hello\n=====");
+    }
+
+    #[test]
+    fn html_and_plain_text_are_kept_verbatim() {
+        let content = IssueContent::parse("".into(), "<p>Hi</p>".into(), "Hi".into()).unwrap();
+        assert_eq!(content.markdown_content, None);
+        assert_eq!(content.html_content, "<p>Hi</p>");
+        assert_eq!(content.text_content, "Hi");
+    }
+
+    #[test]
+    fn markdown_and_html_cannot_be_mixed() {
+        assert_err!(IssueContent::parse(
+            "# Hello".into(),
+            "<p>Hi</p>".into(),
+            "".into()
+        ));
+    }
+
+    #[test]
+    fn html_requires_a_plain_text_alternative() {
+        assert_err!(IssueContent::parse(
+            "".into(),
+            "<p>Hi</p>".into(),
+            "  ".into()
+        ));
+    }
+
+    #[test]
+    fn empty_content_is_rejected() {
+        assert_err!(IssueContent::parse("".into(), "".into(), "".into()));
+    }
+
+    #[test]
+    fn whitespace_around_markdown_is_fine() {
+        assert_ok!(IssueContent::parse(
+            "\n# Hello\n".into(),
+            " ".into(),
+            "".into()
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// A point in the future at which a newsletter issue should be published.
+///
+/// Admins submit it through an `<input type="datetime-local">`, which carries
+/// no timezone: it is always interpreted as UTC.
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduledAt(DateTime<Utc>);
+
+impl ScheduledAt {
+    const FORMATS: [&'static str; 2] = ["%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"];
+
+    pub fn parse(s: String) -> Result<ScheduledAt, String> {
+        let scheduled_at = Self::FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(s.trim(), format).ok())
+            .map(|naive| naive.and_utc())
+            .ok_or_else(|| format!("{} is not a valid publication time.", s))?;
+
+        if scheduled_at <= Utc::now() {
+            return Err(format!("{} is not in the future.", s));
+        }
+        Ok(Self(scheduled_at))
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.0.timestamp()
+    }
+}
+
+impl std::fmt::Display for ScheduledAt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.format("%Y-%m-%d %H:%M UTC").fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use claims::{assert_err, assert_ok};
+
+    use crate::domain::scheduled_at::ScheduledAt;
+
+    #[test]
+    fn a_datetime_local_value_in_the_future_is_accepted() {
+        let value = (Utc::now() + Duration::days(3))
+            .format("%Y-%m-%dT%H:%M")
+            .to_string();
+        assert_ok!(ScheduledAt::parse(value));
+    }
+
+    #[test]
+    fn seconds_are_accepted() {
+        let value = (Utc::now() + Duration::days(3))
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string();
+        assert_ok!(ScheduledAt::parse(value));
+    }
+
+    #[test]
+    fn a_time_in_the_past_is_rejected() {
+        let value = (Utc::now() - Duration::minutes(5))
+            .format("%Y-%m-%dT%H:%M")
+            .to_string();
+        assert_err!(ScheduledAt::parse(value));
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert_err!(ScheduledAt::parse("".to_string()));
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert_err!(ScheduledAt::parse("next monday".to_string()));
+    }
+}
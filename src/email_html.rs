@@ -0,0 +1,151 @@
+use std::{borrow::Cow, collections::HashSet};
+
+use ammonia::{Builder, Url, UrlRelative, UrlRelativeEvaluate};
+use css_inline::CSSInliner;
+
+/// Gmail clips messages with more HTML than this behind a "View entire message" link.
+pub const GMAIL_CLIPPING_THRESHOLD: usize = 102 * 1024;
+
+/// Presentational attributes that email clients still rely on, on top of `ammonia`'s defaults.
+const EMAIL_ATTRIBUTES: [&str; 10] = [
+    "style",
+    "class",
+    "align",
+    "valign",
+    "width",
+    "height",
+    "bgcolor",
+    "border",
+    "cellpadding",
+    "cellspacing",
+];
+
+/// Layouts wrap issues in landmarks `ammonia` does not allow by default.
+const EMAIL_TAGS: [&str; 1] = ["main"];
+
+pub struct PreparedHtml {
+    pub html: String,
+    pub warnings: Vec<String>,
+}
+
+///
+/// Get the HTML of an issue ready to be sent.
+/// Styles from `<style>` blocks are inlined, since Gmail and Outlook ignore most of them,
+/// scripts and event handlers are stripped and relative URLs are resolved against `base_url`.
+/// Merge variables such as `{{ unsubscribe_url }}` are left for the templating step.
+pub fn prepare(html: &str, base_url: &str) -> PreparedHtml {
+    let mut warnings = Vec::new();
+    let inliner = CSSInliner::options().load_remote_stylesheets(false).build();
+    let inlined = match inliner.inline(html) {
+        Ok(inlined) => Cow::Owned(inlined),
+        Err(e) => {
+            warnings.push(format!("The styles could not be inlined: {e}"));
+            Cow::Borrowed(html)
+        }
+    };
+
+    let html = sanitise(&inlined, base_url);
+
+    PreparedHtml { html, warnings }
+}
+
+/// Strip scripts, styles and event handlers, and resolve relative URLs against `base_url`.
+pub fn sanitise(html: &str, base_url: &str) -> String {
+    let url_relative = match Url::parse(base_url) {
+        Ok(base_url) => UrlRelative::Custom(Box::new(ResolveAgainst(base_url))),
+        Err(_) => UrlRelative::PassThrough,
+    };
+    Builder::default()
+        .add_tags(EMAIL_TAGS)
+        .add_generic_attributes(EMAIL_ATTRIBUTES)
+        .clean_content_tags(HashSet::from(["script", "style", "title"]))
+        .url_relative(url_relative)
+        .clean(html)
+        .to_string()
+}
+
+/// Warn when an email is large enough to be clipped by Gmail.
+/// Check the rendered email rather than the content alone: the layout counts too.
+pub fn size_warning(html: &str) -> Option<String> {
+    (html.len() > GMAIL_CLIPPING_THRESHOLD).then(|| {
+        format!(
+            "The HTML version is {}KB: Gmail clips messages larger than {}KB.",
+            html.len().div_ceil(1024),
+            GMAIL_CLIPPING_THRESHOLD / 1024
+        )
+    })
+}
+
+struct ResolveAgainst(Url);
+
+impl<'a> UrlRelativeEvaluate<'a> for ResolveAgainst {
+    fn evaluate<'url>(&self, url: &'url str) -> Option<Cow<'url, str>> {
+        // Merge variables and anchors are not relative URLs, they must survive as they are.
+        if url.trim_start().starts_with("{{") || url.starts_with('#') {
+            return Some(Cow::Borrowed(url));
+        }
+        self.0.join(url).ok().map(|url| Cow::Owned(url.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::email_html::{prepare, size_warning, GMAIL_CLIPPING_THRESHOLD};
+
+    const BASE_URL: &str = "https://newsletter.example.com";
+
+    #[test]
+    fn scripts_and_event_handlers_are_stripped() {
+        let prepared = prepare(
+            r#"<p onclick="steal()">Hi</p><script>alert("x")</script>"#,
+            BASE_URL,
+        );
+        assert_eq!(prepared.html, "<p>Hi</p>");
+        assert!(prepared.warnings.is_empty());
+    }
+
+    #[test]
+    fn styles_are_inlined() {
+        let prepared = prepare(
+            r#"<style>p { color: red; } .big { font-size: 20px; }</style><p class="big">Hi</p>"#,
+            BASE_URL,
+        );
+        assert_eq!(
+            prepared.html,
+            r#"<p class="big" style="color: red;font-size: 20px;">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn relative_urls_are_resolved_against_the_base_url() {
+        let prepared = prepare(
+            r#"<a href="/issues">Archive</a><img src="logo.png" alt="Logo"><a href="https://example.com/">Out</a>"#,
+            BASE_URL,
+        );
+        assert_eq!(
+            prepared.html,
+            r#"<a href="https://newsletter.example.com/issues" rel="noopener noreferrer">Archive</a><img src="https://newsletter.example.com/logo.png" alt="Logo"><a href="https://example.com/" rel="noopener noreferrer">Out</a>"#
+        );
+    }
+
+    #[test]
+    fn merge_variables_are_left_alone() {
+        let prepared = prepare(
+            r#"<p>Hi {{ name }}</p><a href="{{ unsubscribe_url }}">Unsubscribe</a>"#,
+            BASE_URL,
+        );
+        assert_eq!(
+            prepared.html,
+            r#"<p>Hi {{ name }}</p><a href="{{ unsubscribe_url }}" rel="noopener noreferrer">Unsubscribe</a>"#
+        );
+    }
+
+    #[test]
+    fn oversized_emails_are_flagged() {
+        assert!(size_warning("<p>Hi</p>").is_none());
+        let paragraph = "<p>Lorem ipsum dolor sit amet.</p>";
+        let html = paragraph.repeat(GMAIL_CLIPPING_THRESHOLD / paragraph.len() + 1);
+        let warning = size_warning(&html).unwrap();
+        assert!(warning.contains("Gmail clips messages larger than 102KB"));
+    }
+}
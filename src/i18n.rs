@@ -0,0 +1,253 @@
+//! The languages subscribers read their confirmation emails, subscription pages and issue headers in.
+//! English is the fallback whenever a subscriber's language is unknown or not supported.
+
+/// A language of the message catalogue, stored as its code on `subscriptions.locale`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum Locale {
+    #[default]
+    English,
+    French,
+    German,
+}
+
+/// The text the catalogue has a translation of.
+/// Confirmation emails contain `{{ confirmation_link }}` and view in browser links `{{ issue_url }}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    ConfirmationEmailSubject,
+    ConfirmationEmailHtml,
+    ConfirmationEmailText,
+    SubscriptionConfirmedTitle,
+    SubscriptionConfirmed,
+    Unsubscribe,
+    ConfirmUnsubscribe,
+    UnsubscribedTitle,
+    Unsubscribed,
+    DisableTracking,
+    ConfirmDisableTracking,
+    TrackingDisabledTitle,
+    TrackingDisabled,
+    ViewInBrowserHtml,
+    ViewInBrowserText,
+    Home,
+    PastIssues,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::English, Locale::French, Locale::German];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::French => "fr",
+            Locale::German => "de",
+        }
+    }
+
+    /// The name of the language, in that language.
+    pub fn name(self) -> &'static str {
+        match self {
+            Locale::English => "English",
+            Locale::French => "Français",
+            Locale::German => "Deutsch",
+        }
+    }
+
+    ///
+    /// Parse a language tag such as `fr` or `fr-CA`: regional variants get their language.
+    pub fn parse(tag: &str) -> Option<Locale> {
+        let language = tag.trim().split(['-', '_']).next()?;
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.code().eq_ignore_ascii_case(language))
+    }
+
+    ///
+    /// The supported language an `Accept-Language` header ranks highest, English if there is none.
+    /// Languages of equal weight are ranked in the order they are listed.
+    pub fn negotiate(accept_language: &str) -> Locale {
+        let mut best: Option<(Locale, f32)> = None;
+        for range in accept_language.split(',') {
+            let mut parts = range.split(';');
+            let Some(locale) = parts.next().and_then(Locale::parse) else {
+                continue;
+            };
+            let weight = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())
+                .unwrap_or(0.0);
+            if weight > 0.0 && best.is_none_or(|(_, best_weight)| weight > best_weight) {
+                best = Some((locale, weight));
+            }
+        }
+        best.map(|(locale, _)| locale).unwrap_or_default()
+    }
+
+    pub fn message(self, message: Message) -> &'static str {
+        match self {
+            Locale::English => english(message),
+            Locale::French => french(message),
+            Locale::German => german(message),
+        }
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = String;
+
+    fn try_from(tag: String) -> Result<Self, Self::Error> {
+        Locale::parse(&tag).ok_or_else(|| format!("{tag} is not a supported language."))
+    }
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::ConfirmationEmailSubject => "Welcome",
+        Message::ConfirmationEmailHtml => {
+            r#"Welcome to our newsletter!<br/>Click <a href="{{ confirmation_link }}">here</a> to confirm your subscription."#
+        }
+        Message::ConfirmationEmailText => {
+            "Welcome to our newsletter!\nVisit {{ confirmation_link }} to confirm your subscription"
+        }
+        Message::SubscriptionConfirmedTitle => "Subscription confirmed",
+        Message::SubscriptionConfirmed => {
+            "Thank you for confirming your subscription: the next issue will land in your inbox."
+        }
+        Message::Unsubscribe => "Unsubscribe",
+        Message::ConfirmUnsubscribe => "Do you want to stop receiving our issues?",
+        Message::UnsubscribedTitle => "Unsubscribed",
+        Message::Unsubscribed => "You have been unsubscribed and will not receive any more issues.",
+        Message::DisableTracking => "Disable tracking",
+        Message::ConfirmDisableTracking => {
+            "Do you want us to stop tracking whether you open our issues or follow their links?"
+        }
+        Message::TrackingDisabledTitle => "Tracking disabled",
+        Message::TrackingDisabled => {
+            "We will no longer track whether you open our issues or follow their links."
+        }
+        Message::ViewInBrowserHtml => {
+            r#"<a href="{{ issue_url }}">View this email in your browser</a>"#
+        }
+        Message::ViewInBrowserText => "View this email in your browser: {{ issue_url }}",
+        Message::Home => "Home",
+        Message::PastIssues => "Past issues",
+    }
+}
+
+fn french(message: Message) -> &'static str {
+    match message {
+        Message::ConfirmationEmailSubject => "Bienvenue",
+        Message::ConfirmationEmailHtml => {
+            r#"Bienvenue dans notre newsletter !<br/>Cliquez <a href="{{ confirmation_link }}">ici</a> pour confirmer votre abonnement."#
+        }
+        Message::ConfirmationEmailText => {
+            "Bienvenue dans notre newsletter !\nRendez-vous sur {{ confirmation_link }} pour confirmer votre abonnement."
+        }
+        Message::SubscriptionConfirmedTitle => "Abonnement confirmé",
+        Message::SubscriptionConfirmed => {
+            "Merci d'avoir confirmé votre abonnement : le prochain numéro arrivera dans votre boîte de réception."
+        }
+        Message::Unsubscribe => "Se désabonner",
+        Message::ConfirmUnsubscribe => "Voulez-vous ne plus recevoir nos numéros ?",
+        Message::UnsubscribedTitle => "Désabonnement",
+        Message::Unsubscribed => {
+            "Vous avez été désabonné et ne recevrez plus aucun numéro."
+        }
+        Message::DisableTracking => "Désactiver le suivi",
+        Message::ConfirmDisableTracking => {
+            "Voulez-vous que nous cessions de suivre l'ouverture de nos numéros et les clics sur leurs liens ?"
+        }
+        Message::TrackingDisabledTitle => "Suivi désactivé",
+        Message::TrackingDisabled => {
+            "Nous ne suivrons plus l'ouverture de nos numéros ni les clics sur leurs liens."
+        }
+        Message::ViewInBrowserHtml => {
+            r#"<a href="{{ issue_url }}">Afficher cet e-mail dans votre navigateur</a>"#
+        }
+        Message::ViewInBrowserText => {
+            "Afficher cet e-mail dans votre navigateur : {{ issue_url }}"
+        }
+        Message::Home => "Accueil",
+        Message::PastIssues => "Numéros précédents",
+    }
+}
+
+fn german(message: Message) -> &'static str {
+    match message {
+        Message::ConfirmationEmailSubject => "Willkommen",
+        Message::ConfirmationEmailHtml => {
+            r#"Willkommen bei unserem Newsletter!<br/>Klicken Sie <a href="{{ confirmation_link }}">hier</a>, um Ihr Abonnement zu bestätigen."#
+        }
+        Message::ConfirmationEmailText => {
+            "Willkommen bei unserem Newsletter!\nBesuchen Sie {{ confirmation_link }}, um Ihr Abonnement zu bestätigen."
+        }
+        Message::SubscriptionConfirmedTitle => "Abonnement bestätigt",
+        Message::SubscriptionConfirmed => {
+            "Vielen Dank für die Bestätigung Ihres Abonnements: Die nächste Ausgabe landet in Ihrem Posteingang."
+        }
+        Message::Unsubscribe => "Abmelden",
+        Message::ConfirmUnsubscribe => "Möchten Sie unsere Ausgaben nicht mehr erhalten?",
+        Message::UnsubscribedTitle => "Abgemeldet",
+        Message::Unsubscribed => "Sie wurden abgemeldet und erhalten keine weiteren Ausgaben.",
+        Message::DisableTracking => "Tracking deaktivieren",
+        Message::ConfirmDisableTracking => {
+            "Möchten Sie, dass wir nicht mehr verfolgen, ob Sie unsere Ausgaben öffnen oder ihren Links folgen?"
+        }
+        Message::TrackingDisabledTitle => "Tracking deaktiviert",
+        Message::TrackingDisabled => {
+            "Wir verfolgen nicht mehr, ob Sie unsere Ausgaben öffnen oder ihren Links folgen."
+        }
+        Message::ViewInBrowserHtml => {
+            r#"<a href="{{ issue_url }}">Diese E-Mail im Browser anzeigen</a>"#
+        }
+        Message::ViewInBrowserText => "Diese E-Mail im Browser anzeigen: {{ issue_url }}",
+        Message::Home => "Startseite",
+        Message::PastIssues => "Frühere Ausgaben",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_none, assert_ok};
+
+    use crate::{
+        i18n::{Locale, Message},
+        templating::{MergeTemplate, ISSUE_VARIABLES},
+    };
+
+    #[test]
+    fn language_tags_are_parsed_without_their_region() {
+        assert_eq!(Locale::parse("fr"), Some(Locale::French));
+        assert_eq!(Locale::parse("fr-CA"), Some(Locale::French));
+        assert_eq!(Locale::parse(" DE_at"), Some(Locale::German));
+        assert_none!(Locale::parse("es"));
+        assert_none!(Locale::parse(""));
+    }
+
+    #[test]
+    fn the_highest_ranked_supported_language_is_negotiated() {
+        assert_eq!(Locale::negotiate("de-DE,de;q=0.9,en;q=0.8"), Locale::German);
+        assert_eq!(Locale::negotiate("es, fr;q=0.5, en;q=0.7"), Locale::English);
+        assert_eq!(Locale::negotiate("es, fr;q=0.5"), Locale::French);
+        assert_eq!(Locale::negotiate("fr, de"), Locale::French);
+    }
+
+    #[test]
+    fn english_is_the_fallback() {
+        assert_eq!(Locale::negotiate(""), Locale::English);
+        assert_eq!(Locale::negotiate("es, *;q=0.1"), Locale::English);
+        assert_eq!(Locale::negotiate("fr;q=0"), Locale::English);
+        assert_eq!(Locale::negotiate("fr;q=high"), Locale::English);
+    }
+
+    #[test]
+    fn every_view_in_browser_link_points_to_the_issue() {
+        for locale in Locale::ALL {
+            for message in [Message::ViewInBrowserHtml, Message::ViewInBrowserText] {
+                let template = MergeTemplate::parse(locale.message(message), &ISSUE_VARIABLES);
+                assert_ok!(template.unwrap().require("issue_url"));
+            }
+        }
+    }
+}
@@ -1,11 +1,18 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use anyhow::Context;
-use sqlx::{Sqlite, SqlitePool, Transaction};
+use secrecy::Secret;
+use sqlx::{Sqlite, SqliteExecutor, SqlitePool, Transaction};
 use tracing::{field::display, Span};
+use tsid::create_tsid;
 
 use crate::{
-    configuration::Settings, domain::subscriber_email::SubscriberEmail, email_client::EmailClient,
+    configuration::Settings,
+    domain::subscriber_email::SubscriberEmail,
+    email_client::EmailClient,
+    i18n::{Locale, Message},
+    templating::{Escaping, MergeTemplate, TemplateError, CONTENT_VARIABLE, ISSUE_VARIABLES},
+    tracking::{track_clicks, TrackedEvent},
     utils::get_connection_pool,
 };
 
@@ -25,13 +32,15 @@ pub enum ExecutionOutcome {
 pub async fn try_execute_task(
     pool: &SqlitePool,
     email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
     let task = dequeue_task(pool).await?;
     if task.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
 
-    let (transaction, issue_id, email) = task.unwrap();
+    let (mut transaction, issue_id, email) = task.unwrap();
 
     Span::current()
         .record("newsletter_issue_id", display(&issue_id))
@@ -40,21 +49,40 @@ pub async fn try_execute_task(
     match SubscriberEmail::parse(email.clone()) {
         Ok(email) => {
             let issue = get_issue(pool, &issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
-                )
-                .await
-            {
-                // unlock_job(&mut transaction, &issue_id, &email).await?;
-                tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. Skipping"
-                )
+            match get_recipient(pool, email).await? {
+                Some(recipient) => {
+                    let outcome = deliver_issue(
+                        email_client,
+                        base_url,
+                        &issue,
+                        &recipient,
+                        Some(hmac_secret),
+                    )
+                    .await;
+                    let event_type = match outcome {
+                        Ok(()) => "sent",
+                        Err(e) => {
+                            // unlock_job(&mut transaction, &issue_id, &email).await?;
+                            tracing::error!(
+                                error.cause_chain = ?e,
+                                error.message = %e,
+                                "Failed to deliver issue to a confirmed subscriber. Skipping"
+                            );
+                            "failed"
+                        }
+                    };
+                    record_delivery_event(
+                        &mut *transaction,
+                        &issue_id,
+                        &recipient.subscriber_id,
+                        event_type,
+                        None,
+                    )
+                    .await?;
+                }
+                None => {
+                    tracing::error!("Skipping a subscriber without a subscription token")
+                }
             }
         }
         Err(e) => {
@@ -72,6 +100,58 @@ pub async fn try_execute_task(
 
 type SqliteTransaction = Transaction<'static, Sqlite>;
 
+/// Store what happened to an issue for one recipient, e.g. `sent`, `failed` or `opened`.
+/// Clicks come with the URL of the link that was followed.
+#[tracing::instrument(skip_all)]
+pub async fn record_delivery_event(
+    executor: impl SqliteExecutor<'_>,
+    newsletter_issue_id: &str,
+    subscriber_id: &str,
+    event_type: &str,
+    url: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let delivery_event_id = create_tsid().to_string();
+    sqlx::query!(
+        r#"
+        INSERT INTO delivery_events (
+            delivery_event_id, newsletter_issue_id, subscriber_id, event_type, occurred_at, url
+        )
+        VALUES ($1, $2, $3, $4, unixepoch(), $5)
+        "#,
+        delivery_event_id,
+        newsletter_issue_id,
+        subscriber_id,
+        event_type,
+        url
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Fan out a published issue: one delivery task per confirmed subscriber.
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut SqliteTransaction,
+    newsletters_issue_id: &str,
+) -> Result<(), sqlx::Error> {
+    let _ = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email
+        )
+        SELECT $1, email
+        FROM subscriptions
+        WHERE status = 'confirmed'
+        "#,
+        newsletters_issue_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     pool: &SqlitePool,
@@ -160,10 +240,180 @@ async fn _unlock_job(
     Ok(())
 }
 
-struct NewsletterIssue {
-    title: String,
-    text_content: String,
-    html_content: String,
+/// The id of an issue being previewed or test sent, which is not in the archive.
+pub const DRAFT_ISSUE_ID: &str = "draft";
+
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: String,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub text_layout: Option<String>,
+    pub html_layout: Option<String>,
+    pub track_opens: bool,
+    pub track_clicks: bool,
+}
+
+impl NewsletterIssue {
+    ///
+    /// The HTML and plain text templates of the email, with the content embedded in its layout.
+    /// Publishing an issue goes through this too, so errors surface before anything is sent.
+    pub fn templates(
+        &self,
+        locale: Locale,
+    ) -> Result<(MergeTemplate, MergeTemplate), TemplateError> {
+        let html = compose(self.html_layout.as_deref(), &self.html_content)?;
+        let text = compose(self.text_layout.as_deref(), &self.text_content)?;
+        Ok((
+            with_view_in_browser_link(
+                VIEW_IN_BROWSER_HTML,
+                locale.message(Message::ViewInBrowserHtml),
+                &html,
+            ),
+            with_view_in_browser_link(
+                VIEW_IN_BROWSER_TEXT,
+                locale.message(Message::ViewInBrowserText),
+                &text,
+            ),
+        ))
+    }
+
+    /// The page of the issue in the public archive, or the archive itself for a draft.
+    fn issue_url(&self, base_url: &str) -> String {
+        if self.newsletter_issue_id == DRAFT_ISSUE_ID {
+            format!("{}/issues", base_url)
+        } else {
+            format!("{}/issues/{}", base_url, self.newsletter_issue_id)
+        }
+    }
+
+    /// Fill in the merge variables for one recipient, returning the HTML and plain text bodies.
+    pub fn render(
+        &self,
+        base_url: &str,
+        name: &str,
+        subscription_token: &str,
+        locale: Locale,
+    ) -> Result<(String, String), TemplateError> {
+        let (html, text) = self.templates(locale)?;
+        let values = HashMap::from([
+            ("name", name.to_string()),
+            (
+                "unsubscribe_url",
+                format!(
+                    "{}/subscriptions/unsubscribe?subscription_token={}&newsletter_issue_id={}",
+                    base_url, subscription_token, self.newsletter_issue_id
+                ),
+            ),
+            ("issue_url", self.issue_url(base_url)),
+            (
+                "tracking_opt_out_url",
+                format!(
+                    "{}/subscriptions/tracking-opt-out?subscription_token={}",
+                    base_url, subscription_token
+                ),
+            ),
+        ]);
+        Ok((
+            html.render(&values, Escaping::Html),
+            text.render(&values, Escaping::PlainText),
+        ))
+    }
+}
+
+/// Every email links to its page in the public archive, whatever its layout,
+/// in the language of the recipient.
+const VIEW_IN_BROWSER_HTML: &str = r#"<p style="font-size: 12px;">{{ view_in_browser }}</p>
+{{ content }}"#;
+const VIEW_IN_BROWSER_TEXT: &str = "{{ view_in_browser }}
+
+{{ content }}";
+const VIEW_IN_BROWSER_VARIABLE: &str = "view_in_browser";
+
+fn with_view_in_browser_link(header: &str, link: &str, email: &MergeTemplate) -> MergeTemplate {
+    let link = MergeTemplate::parse(link, &ISSUE_VARIABLES)
+        .expect("The catalogue's view in browser links are valid templates.");
+    MergeTemplate::parse(header, &[VIEW_IN_BROWSER_VARIABLE, CONTENT_VARIABLE])
+        .expect("The view in browser header is a valid layout.")
+        .embed(VIEW_IN_BROWSER_VARIABLE, &link)
+        .embed(CONTENT_VARIABLE, email)
+}
+
+fn compose(layout: Option<&str>, content: &str) -> Result<MergeTemplate, TemplateError> {
+    let content = MergeTemplate::parse(content, &ISSUE_VARIABLES)?;
+    match layout {
+        None => Ok(content),
+        Some(layout) => Ok(MergeTemplate::parse_layout(layout)?.embed(CONTENT_VARIABLE, &content)),
+    }
+}
+
+pub struct Recipient {
+    pub subscriber_id: String,
+    pub email: SubscriberEmail,
+    pub name: String,
+    pub subscription_token: String,
+    pub tracking_opt_out: bool,
+    pub locale: Locale,
+}
+
+/// Render an issue for a single recipient and hand it over to the email API.
+///
+/// This is the only way issues leave the system: test sends from the admin
+/// panel go through here as well, so editors see exactly what subscribers get.
+/// They are not tracked though, which is why the secret used to sign tracking
+/// links is optional. Neither are subscribers who opted out of tracking.
+#[tracing::instrument(skip_all)]
+pub async fn deliver_issue(
+    email_client: &EmailClient,
+    base_url: &str,
+    issue: &NewsletterIssue,
+    recipient: &Recipient,
+    tracking_secret: Option<&Secret<String>>,
+) -> Result<(), anyhow::Error> {
+    let (mut html, text) = issue
+        .render(
+            base_url,
+            &recipient.name,
+            &recipient.subscription_token,
+            recipient.locale,
+        )
+        .context("The issue has invalid merge variables.")?;
+    let tracking_secret = tracking_secret.filter(|_| !recipient.tracking_opt_out);
+    if let Some(hmac_secret) = tracking_secret.filter(|_| issue.track_clicks) {
+        // Subscription management links must keep working as they are.
+        let subscriptions_url = format!("{}/subscriptions/", base_url);
+        html = track_clicks(
+            &html,
+            base_url,
+            &issue.newsletter_issue_id,
+            &recipient.subscriber_id,
+            &[&subscriptions_url],
+            hmac_secret,
+        )
+        .context("Failed to rewrite the links of the issue.")?;
+    }
+    if let Some(hmac_secret) = tracking_secret.filter(|_| issue.track_opens) {
+        let open = TrackedEvent::Open {
+            newsletter_issue_id: issue.newsletter_issue_id.clone(),
+            subscriber_id: recipient.subscriber_id.clone(),
+        };
+        let pixel_url = format!("{}/t/{}", base_url, open.token(hmac_secret));
+        html = with_tracking_pixel(&html, &pixel_url);
+    }
+    email_client
+        .send_email(&recipient.email, &issue.title, &html, &text)
+        .await?;
+    Ok(())
+}
+
+/// Add an invisible image at the end of the body: email clients loading it tell us the email was opened.
+fn with_tracking_pixel(html: &str, pixel_url: &str) -> String {
+    let pixel =
+        format!(r#"<img src="{pixel_url}" width="1" height="1" alt="" style="border: 0;">"#);
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{pixel}{}", &html[..end], &html[end..]),
+        None => format!("{html}{pixel}"),
+    }
 }
 
 #[tracing::instrument(skip_all)]
@@ -171,8 +421,11 @@ async fn get_issue(pool: &SqlitePool, issue_id: &String) -> Result<NewsletterIss
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT title, text_content, html_content 
-        FROM newsletter_issues 
+        SELECT
+            newsletter_issue_id, title, text_content, html_content, text_layout, html_layout,
+            track_opens AS "track_opens: bool",
+            track_clicks AS "track_clicks: bool"
+        FROM newsletter_issues
         WHERE
             newsletter_issue_id = $1
         "#,
@@ -184,9 +437,48 @@ async fn get_issue(pool: &SqlitePool, issue_id: &String) -> Result<NewsletterIss
     Ok(issue)
 }
 
-async fn worker_loop(pool: SqlitePool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+#[tracing::instrument(skip_all)]
+async fn get_recipient(
+    pool: &SqlitePool,
+    email: SubscriberEmail,
+) -> Result<Option<Recipient>, anyhow::Error> {
+    let subscriber_email = email.as_ref();
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            subscriptions.id,
+            subscriptions.name,
+            subscriptions.tracking_opt_out AS "tracking_opt_out: bool",
+            subscriptions.locale,
+            subscription_tokens.subscription_token
+        FROM subscriptions
+        JOIN subscription_tokens ON subscription_tokens.subscriber_id = subscriptions.id
+        WHERE subscriptions.email = $1
+        LIMIT 1
+        "#,
+        subscriber_email
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| Recipient {
+        subscriber_id: r.id,
+        email,
+        name: r.name,
+        subscription_token: r.subscription_token,
+        tracking_opt_out: r.tracking_opt_out,
+        locale: Locale::parse(&r.locale).unwrap_or_default(),
+    }))
+}
+
+async fn worker_loop(
+    pool: SqlitePool,
+    email_client: EmailClient,
+    base_url: String,
+    hmac_secret: Secret<String>,
+) -> Result<(), anyhow::Error> {
     loop {
-        match try_execute_task(&pool, &email_client).await {
+        match try_execute_task(&pool, &email_client, &base_url, &hmac_secret).await {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 actix_web::rt::time::sleep(Duration::from_secs(10)).await;
             }
@@ -201,5 +493,11 @@ async fn worker_loop(pool: SqlitePool, email_client: EmailClient) -> Result<(),
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database, None).await;
     let email_client = configuration.email_client.client();
-    worker_loop(connection_pool, email_client).await
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+    )
+    .await
 }
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use sqlx::SqlitePool;
+use tracing::{field::display, Span};
+
+use crate::{
+    configuration::Settings, issue_delivery_worker::enqueue_delivery_tasks,
+    utils::get_connection_pool,
+};
+
+pub enum SchedulingOutcome {
+    IssuePublished,
+    NothingDue,
+}
+
+/// Publish the oldest scheduled issue that is due, if any.
+///
+/// Flipping the status and fanning out the delivery tasks happen in the same
+/// transaction, and the status flip only succeeds while the issue is still
+/// `scheduled`: an issue is enqueued exactly once, even if the process restarts
+/// half-way or another scheduler is running against the same database.
+#[tracing::instrument(
+    skip_all,
+    fields(newsletter_issue_id=tracing::field::Empty),
+    err
+)]
+pub async fn try_publish_due_issue(pool: &SqlitePool) -> Result<SchedulingOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let due_issue = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE status = 'scheduled' AND scheduled_at <= unixepoch()
+        ORDER BY scheduled_at
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    let Some(issue) = due_issue else {
+        return Ok(SchedulingOutcome::NothingDue);
+    };
+    Span::current().record("newsletter_issue_id", display(&issue.newsletter_issue_id));
+
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'published', published_at = unixepoch()
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        issue.newsletter_issue_id
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+
+    if n_updated_rows > 0 {
+        enqueue_delivery_tasks(&mut transaction, &issue.newsletter_issue_id).await?;
+    }
+    transaction.commit().await?;
+    Ok(SchedulingOutcome::IssuePublished)
+}
+
+async fn scheduler_loop(pool: SqlitePool) -> Result<(), anyhow::Error> {
+    loop {
+        match try_publish_due_issue(&pool).await {
+            Ok(SchedulingOutcome::NothingDue) => {
+                actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(SchedulingOutcome::IssuePublished) => {}
+        }
+    }
+}
+
+pub async fn run_scheduler_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database, None).await;
+    scheduler_loop(connection_pool).await
+}
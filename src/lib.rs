@@ -1,12 +1,21 @@
 pub mod authentication;
 pub mod configuration;
+pub mod confirmation_email;
 pub mod domain;
 pub mod email_client;
+pub mod email_html;
+pub mod i18n;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod issue_scheduler;
+pub mod markdown;
 pub mod routes;
+pub mod security_headers;
 pub mod session;
+pub mod session_cleanup;
 pub mod session_state;
 pub mod startup;
 pub mod telemetry;
+pub mod templating;
+pub mod tracking;
 pub mod utils;
@@ -1,8 +1,11 @@
 use std::fmt::{Debug, Display};
 
+use tokio::sync::oneshot;
 use tokio::task::JoinError;
 use zero2prod::configuration::get_configuration;
 use zero2prod::issue_delivery_worker::run_worker_until_stopped;
+use zero2prod::issue_scheduler::run_scheduler_until_stopped;
+use zero2prod::session_cleanup::run_session_cleanup_until_stopped;
 use zero2prod::startup::Application;
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
@@ -15,11 +18,21 @@ async fn main() -> Result<(), std::io::Error> {
     let application = Application::build(configuration.clone(), None).await?;
 
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+    let scheduler_task = tokio::spawn(run_scheduler_until_stopped(configuration.clone()));
+    let (stop_session_cleanup, session_cleanup_stopped) = oneshot::channel::<()>();
+    let session_cleanup_task =
+        tokio::spawn(run_session_cleanup_until_stopped(configuration, async {
+            let _ = session_cleanup_stopped.await;
+        }));
     tokio::select! {
         outcome = application_task => report_exit("API", outcome),
-        outcome = worker_task => report_exit("Background worker", outcome)
+        outcome = worker_task => report_exit("Background worker", outcome),
+        outcome = scheduler_task => report_exit("Issue scheduler", outcome)
     }
+    // Let a cleanup in progress finish instead of cutting it off.
+    let _ = stop_session_cleanup.send(());
+    report_exit("Session cleanup", session_cleanup_task.await);
     Ok(())
 }
 
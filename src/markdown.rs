@@ -0,0 +1,255 @@
+use pulldown_cmark::{html, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH
+}
+
+///
+/// Render Markdown to HTML.
+/// Markdown lets authors embed raw HTML: the output is sanitised before it's returned.
+pub fn render_html(markdown: &str) -> String {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options()));
+    ammonia::clean(&unsafe_html)
+}
+
+///
+/// Render Markdown to a plain text alternative meant to be read as-is in an email client.
+/// Formatting markers are dropped, links are spelled out and raw HTML is skipped.
+pub fn render_text(markdown: &str) -> String {
+    let mut writer = PlainTextWriter {
+        at_line_start: true,
+        ..Default::default()
+    };
+    for event in Parser::new_ext(markdown, options()) {
+        writer.handle(event);
+    }
+    writer.finish()
+}
+
+#[derive(Default)]
+struct PlainTextWriter {
+    output: String,
+    at_line_start: bool,
+    quote_depth: usize,
+    in_code_block: bool,
+    /// One entry per nested list, holding the next number of ordered lists.
+    lists: Vec<Option<u64>>,
+    /// Destinations of the links and images we are in, with where their text starts.
+    links: Vec<(String, usize)>,
+    heading_start: usize,
+}
+
+impl PlainTextWriter {
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) | Event::Code(text) => self.push(&text),
+            Event::SoftBreak | Event::HardBreak => self.push("\n"),
+            Event::Rule => {
+                self.end_block();
+                self.push("----");
+                self.end_block();
+            }
+            Event::TaskListMarker(done) => self.push(if done { "[x] " } else { "[ ] " }),
+            Event::Html(_)
+            | Event::InlineHtml(_)
+            | Event::FootnoteReference(_)
+            | Event::InlineMath(_)
+            | Event::DisplayMath(_) => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph | Tag::Table(_) => self.start_block(),
+            Tag::Heading { .. } => {
+                self.start_block();
+                self.heading_start = self.output.len();
+            }
+            Tag::BlockQuote(_) => {
+                self.start_block();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.start_block();
+                self.in_code_block = true;
+            }
+            Tag::List(first_number) => {
+                if self.lists.is_empty() {
+                    self.start_block();
+                }
+                self.lists.push(first_number);
+            }
+            Tag::Item => {
+                self.start_line();
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.push(&format!("{}{}", "  ".repeat(depth), marker));
+            }
+            Tag::TableRow | Tag::TableHead => self.start_line(),
+            Tag::TableCell if !self.at_line_start => self.push(" | "),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.links.push((dest_url.to_string(), self.output.len()));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                if self.lists.is_empty() {
+                    self.end_block();
+                } else {
+                    self.start_line();
+                }
+            }
+            TagEnd::Heading(level) => {
+                let underline = match level {
+                    HeadingLevel::H1 => Some('='),
+                    HeadingLevel::H2 => Some('-'),
+                    _ => None,
+                };
+                if let Some(underline) = underline {
+                    let width = self.output[self.heading_start..].chars().count();
+                    self.push("\n");
+                    self.push(&underline.to_string().repeat(width));
+                }
+                self.end_block();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.end_block();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            TagEnd::CodeBlock => {
+                self.in_code_block = false;
+                self.end_block();
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.end_block();
+                }
+            }
+            TagEnd::Table => self.end_block(),
+            TagEnd::Link | TagEnd::Image => {
+                if let Some((dest_url, text_start)) = self.links.pop() {
+                    if self.output[text_start..] != dest_url && !dest_url.is_empty() {
+                        self.push(&format!(" ({})", dest_url));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.at_line_start && c != '\n' {
+                self.output.push_str(&"> ".repeat(self.quote_depth));
+                if self.in_code_block {
+                    self.output.push_str("    ");
+                }
+            }
+            self.output.push(c);
+            self.at_line_start = c == '\n';
+        }
+    }
+
+    fn start_line(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.push("\n");
+        }
+    }
+
+    fn start_block(&mut self) {
+        self.start_line();
+    }
+
+    fn end_block(&mut self) {
+        self.start_line();
+        if !self.output.is_empty() && !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+            self.at_line_start = true;
+        }
+    }
+
+    fn finish(self) -> String {
+        self.output.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markdown::{render_html, render_text};
+
+    #[test]
+    fn markdown_is_rendered_to_html() {
+        let html = render_html("# Hello\n\nSome *emphasis* and a [link](https://example.com).");
+        assert!(html.contains("<h1>Hello</h1>"));
+        assert!(html.contains("<em>emphasis</em>"));
+        assert!(
+            html.contains(r#"<a href="https://example.com" rel="noopener noreferrer">link</a>"#)
+        );
+    }
+
+    #[test]
+    fn raw_html_is_sanitised() {
+        let html = render_html(
+            "Hi!\n\n<script>alert('boom')</script>\n\n<a href=\"#\" onclick=\"boom()\">x</a>",
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("onclick"));
+    }
+
+    #[test]
+    fn headings_are_underlined_in_plain_text() {
+        let text = render_text("# Title\n\n## Section\n\nBody");
+        assert_eq!(text, "Title\n=====\n\nSection\n-------\n\nBody");
+    }
+
+    #[test]
+    fn links_are_spelled_out_in_plain_text() {
+        let text =
+            render_text("Read [the docs](https://example.com/docs) or <https://example.com>.");
+        assert_eq!(
+            text,
+            "Read the docs (https://example.com/docs) or https://example.com."
+        );
+    }
+
+    #[test]
+    fn lists_are_rendered_in_plain_text() {
+        let text = render_text("Intro\n\n- one\n- two\n  1. nested\n  2. again\n\nOutro");
+        assert_eq!(
+            text,
+            "Intro\n\n- one\n- two\n  1. nested\n  2. again\n\nOutro"
+        );
+    }
+
+    #[test]
+    fn quotes_and_code_are_rendered_in_plain_text() {
+        let text = render_text("> quoted\n> text\n\n```\nlet x = 1;\n```");
+        assert_eq!(text, "> quoted\n> text\n\n    let x = 1;");
+    }
+
+    #[test]
+    fn tables_are_rendered_in_plain_text() {
+        let text = render_text("| Day | Topic |\n|-----|-------|\n| Mon | Rust |");
+        assert_eq!(text, "Day | Topic\nMon | Rust");
+    }
+
+    #[test]
+    fn raw_html_is_dropped_from_plain_text() {
+        let text = render_text("Hello <b>there</b>\n\n<div>block</div>");
+        assert_eq!(text, "Hello there");
+    }
+}
@@ -1,6 +1,10 @@
+pub mod feeds;
 pub mod health_check;
 pub mod newsletters;
 pub mod site;
 pub mod subscriptions;
 pub mod subscriptions_confirm;
+pub mod subscriptions_tracking_opt_out;
+pub mod subscriptions_unsubscribe;
+pub mod track;
 pub use site::*;
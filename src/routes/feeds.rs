@@ -0,0 +1,240 @@
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    http::header::{ETag, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch, LastModified},
+    web, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat};
+use htmlescape::encode_minimal;
+use sqlx::SqlitePool;
+
+use crate::{routes::site::issues::render_for_the_web, startup::ApplicationBaseUrl, utils::e500};
+
+const FEED_TITLE: &str = "Our newsletter";
+/// Feed readers only need the latest issues, the archive has the rest.
+const FEED_LENGTH: i64 = 20;
+
+struct FeedEntry {
+    newsletter_issue_id: String,
+    title: String,
+    html_content: String,
+    published_at: Option<i64>,
+}
+
+impl FeedEntry {
+    /// Stable across renames and moves of the site, unlike the issue's URL.
+    fn guid(&self) -> String {
+        format!("urn:newsletter-issue:{}", self.newsletter_issue_id)
+    }
+
+    fn published_at(&self) -> DateTime<chrono::Utc> {
+        self.published_at
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .unwrap_or_default()
+    }
+}
+
+#[tracing::instrument(name = "Serve the RSS feed", skip_all)]
+pub async fn rss_feed(
+    request: HttpRequest,
+    pool: web::Data<SqlitePool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let entries = get_feed_entries(&pool).await.map_err(e500)?;
+    let validators = Validators::new(&entries);
+    if validators.not_modified(&request) {
+        return Ok(validators.apply(HttpResponse::NotModified()).finish());
+    }
+
+    let base_url = &base_url.0;
+    let mut items = String::new();
+    for entry in &entries {
+        let link = format!("{}/issues/{}", base_url, entry.newsletter_issue_id);
+        let content = render_for_the_web(base_url, &entry.newsletter_issue_id, &entry.html_content);
+        write!(
+            items,
+            r#"
+    <item>
+      <title>{}</title>
+      <link>{}</link>
+      <guid isPermaLink="false">{}</guid>
+      <pubDate>{}</pubDate>
+      <description>{}</description>
+    </item>"#,
+            encode_minimal(&entry.title),
+            encode_minimal(&link),
+            entry.guid(),
+            entry.published_at().to_rfc2822(),
+            encode_minimal(&content)
+        )
+        .unwrap();
+    }
+    let last_build_date = entries
+        .first()
+        .map(|e| {
+            format!(
+                "\n    <lastBuildDate>{}</lastBuildDate>",
+                e.published_at().to_rfc2822()
+            )
+        })
+        .unwrap_or_default();
+
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+  <channel>
+    <title>{}</title>
+    <link>{}/issues</link>
+    <description>Past issues of our newsletter</description>
+    <atom:link href="{}/feed.xml" rel="self" type="application/rss+xml"/>{last_build_date}{items}
+  </channel>
+</rss>
+"#,
+        FEED_TITLE,
+        encode_minimal(base_url),
+        encode_minimal(base_url)
+    );
+    Ok(validators
+        .apply(HttpResponse::Ok())
+        .content_type("application/rss+xml; charset=utf-8")
+        .body(body))
+}
+
+#[tracing::instrument(name = "Serve the Atom feed", skip_all)]
+pub async fn atom_feed(
+    request: HttpRequest,
+    pool: web::Data<SqlitePool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let entries = get_feed_entries(&pool).await.map_err(e500)?;
+    let validators = Validators::new(&entries);
+    if validators.not_modified(&request) {
+        return Ok(validators.apply(HttpResponse::NotModified()).finish());
+    }
+
+    let base_url = &base_url.0;
+    let mut atom_entries = String::new();
+    for entry in &entries {
+        let link = format!("{}/issues/{}", base_url, entry.newsletter_issue_id);
+        let content = render_for_the_web(base_url, &entry.newsletter_issue_id, &entry.html_content);
+        let published_at = entry
+            .published_at()
+            .to_rfc3339_opts(SecondsFormat::Secs, true);
+        write!(
+            atom_entries,
+            r#"
+  <entry>
+    <title>{}</title>
+    <link href="{}"/>
+    <id>{}</id>
+    <published>{published_at}</published>
+    <updated>{published_at}</updated>
+    <content type="html">{}</content>
+  </entry>"#,
+            encode_minimal(&entry.title),
+            encode_minimal(&link),
+            entry.guid(),
+            encode_minimal(&content)
+        )
+        .unwrap();
+    }
+    // Atom requires `updated` even when nothing has been published yet.
+    let updated = entries
+        .first()
+        .map(FeedEntry::published_at)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true);
+
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>{}</title>
+  <link href="{}/issues"/>
+  <link href="{}/atom.xml" rel="self"/>
+  <id>urn:newsletter-feed</id>
+  <updated>{updated}</updated>
+  <author><name>{}</name></author>{atom_entries}
+</feed>
+"#,
+        FEED_TITLE,
+        encode_minimal(base_url),
+        encode_minimal(base_url),
+        FEED_TITLE
+    );
+    Ok(validators
+        .apply(HttpResponse::Ok())
+        .content_type("application/atom+xml; charset=utf-8")
+        .body(body))
+}
+
+/// Conditional GET support: feed readers poll often and the feeds rarely change.
+struct Validators {
+    etag: EntityTag,
+    last_modified: Option<HttpDate>,
+}
+
+impl Validators {
+    /// Published issues are never edited, so the newest one identifies the feed's content.
+    fn new(entries: &[FeedEntry]) -> Self {
+        let newest = entries.first();
+        let etag = EntityTag::new_strong(
+            newest
+                .map(|e| e.newsletter_issue_id.clone())
+                .unwrap_or_else(|| "empty".into()),
+        );
+        let last_modified = newest.and_then(|e| e.published_at).map(|t| {
+            HttpDate::from(UNIX_EPOCH + Duration::from_secs(t.try_into().unwrap_or_default()))
+        });
+        Self {
+            etag,
+            last_modified,
+        }
+    }
+
+    fn not_modified(&self, request: &HttpRequest) -> bool {
+        // `If-None-Match` takes precedence over `If-Modified-Since` when both are sent.
+        if let Some(if_none_match) = request.get_header::<IfNoneMatch>() {
+            return match if_none_match {
+                IfNoneMatch::Any => true,
+                IfNoneMatch::Items(etags) => etags.iter().any(|e| e.weak_eq(&self.etag)),
+            };
+        }
+        match (request.get_header::<IfModifiedSince>(), self.last_modified) {
+            (Some(IfModifiedSince(since)), Some(last_modified)) => {
+                SystemTime::from(last_modified) <= SystemTime::from(since)
+            }
+            _ => false,
+        }
+    }
+
+    fn apply(&self, mut builder: HttpResponseBuilder) -> HttpResponseBuilder {
+        builder.insert_header(ETag(self.etag.clone()));
+        if let Some(last_modified) = self.last_modified {
+            builder.insert_header(LastModified(last_modified));
+        }
+        builder
+    }
+}
+
+#[tracing::instrument(name = "Get feed entries", skip(pool))]
+async fn get_feed_entries(pool: &SqlitePool) -> Result<Vec<FeedEntry>, anyhow::Error> {
+    let entries = sqlx::query_as!(
+        FeedEntry,
+        r#"
+        SELECT newsletter_issue_id, title, html_content, published_at
+        FROM newsletter_issues
+        WHERE status = 'published'
+        ORDER BY published_at DESC, newsletter_issue_id DESC
+        LIMIT $1
+        "#,
+        FEED_LENGTH
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the issues of the feed.")?;
+    Ok(entries)
+}
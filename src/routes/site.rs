@@ -1,3 +1,7 @@
 pub mod admin;
+pub mod filters;
 pub mod home;
+pub mod invitation;
+pub mod issues;
 pub mod login;
+pub mod password_reset;
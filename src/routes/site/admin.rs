@@ -1,4 +1,12 @@
+pub mod analytics;
+pub mod confirmation_email;
 pub mod dashboard;
+pub mod issues;
 pub mod logout;
 pub mod newsletter;
 pub mod password;
+pub mod reauthenticate;
+pub mod sessions;
+pub mod templates;
+pub mod two_factor;
+pub mod users;
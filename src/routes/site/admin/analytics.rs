@@ -0,0 +1,3 @@
+pub mod export;
+pub mod get;
+pub mod report;
@@ -0,0 +1,104 @@
+use actix_web::{
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web, HttpResponse,
+};
+use anyhow::Context;
+use sqlx::SqlitePool;
+
+use crate::{authentication::UserId, utils::e500};
+
+use super::report::get_report;
+
+#[tracing::instrument {
+    name = "Export analytics as JSON"
+    skip(pool)
+    fields(user_id=%&*user_id)
+}]
+pub async fn analytics_json(
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let report = get_report(&pool).await.map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .insert_header(attachment("analytics.json"))
+        .json(report))
+}
+
+#[tracing::instrument {
+    name = "Export issue analytics as CSV"
+    skip(pool)
+    fields(user_id=%&*user_id)
+}]
+pub async fn issues_csv(
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let report = get_report(&pool).await.map_err(e500)?;
+    let body = to_csv(&report.issues).map_err(e500)?;
+    Ok(csv_response("issues.csv", body))
+}
+
+#[tracing::instrument {
+    name = "Export monthly analytics as CSV"
+    skip(pool)
+    fields(user_id=%&*user_id)
+}]
+pub async fn months_csv(
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let report = get_report(&pool).await.map_err(e500)?;
+    let body = to_csv(&report.months).map_err(e500)?;
+    Ok(csv_response("months.csv", body))
+}
+
+/// Spreadsheets evaluate cells starting with one of these as formulas.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// One line per row, with a header line named after the fields.
+fn to_csv<T: serde::Serialize>(rows: &[T]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in rows {
+        writer
+            .serialize(row)
+            .context("Failed to write a CSV row.")?;
+    }
+    let csv = writer.into_inner().context("Failed to write CSV.")?;
+
+    // Titles are typed by editors: spreadsheets opening the export must not run them.
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(csv.as_slice());
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for record in reader.records() {
+        let record = record.context("Failed to read a CSV row back.")?;
+        writer
+            .write_record(record.iter().map(neutralise_formula))
+            .context("Failed to write a CSV row.")?;
+    }
+    writer.into_inner().context("Failed to write CSV.")
+}
+
+/// Prefix cells that would be evaluated as formulas with `'`, so they are shown as text.
+fn neutralise_formula(cell: &str) -> String {
+    if cell.starts_with(FORMULA_PREFIXES) {
+        format!("'{cell}")
+    } else {
+        cell.to_string()
+    }
+}
+
+fn csv_response(filename: &str, body: Vec<u8>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(attachment(filename))
+        .content_type("text/csv; charset=utf-8")
+        .body(body)
+}
+
+/// Browsers download exports rather than display them.
+fn attachment(filename: &str) -> ContentDisposition {
+    ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(filename.into())],
+    }
+}
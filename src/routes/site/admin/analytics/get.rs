@@ -0,0 +1,30 @@
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::UserId,
+    routes::site::filters,
+    utils::{e500, html_page},
+};
+
+use super::report::{get_report, AnalyticsReport};
+
+#[derive(Template)]
+#[template(path = "admin/analytics.html")]
+struct AnalyticsPage {
+    report: AnalyticsReport,
+}
+
+#[tracing::instrument {
+    name = "Show analytics"
+    skip(pool)
+    fields(user_id=%&*user_id)
+}]
+pub async fn analytics(
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let report = get_report(&pool).await.map_err(e500)?;
+    html_page(&AnalyticsPage { report })
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use sqlx::SqlitePool;
+
+/// Everything the analytics pages show, as served in JSON.
+#[derive(serde::Serialize)]
+pub struct AnalyticsReport {
+    pub subscribers: SubscriberSummary,
+    pub issues: Vec<IssueAnalytics>,
+    pub months: Vec<MonthlyAnalytics>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberSummary {
+    pub confirmed: i64,
+    pub pending_confirmation: i64,
+    pub unsubscribed: i64,
+    /// The share of signups that were confirmed, between 0 and 1.
+    pub confirmation_rate: Option<f64>,
+}
+
+/// What happened to a published issue. Deliveries are the emails the email API accepted,
+/// failed sends the ones it refused: bounces reported later by the email provider are not known.
+#[derive(serde::Serialize)]
+pub struct IssueAnalytics {
+    pub newsletter_issue_id: String,
+    pub title: String,
+    pub published_at: Option<i64>,
+    pub sends: i64,
+    pub deliveries: i64,
+    pub failed_sends: i64,
+    pub opens: i64,
+    pub unique_opens: i64,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+    pub unsubscribes: i64,
+}
+
+impl IssueAnalytics {
+    /// The share of delivered emails that were opened, by distinct subscribers.
+    pub fn open_rate(&self) -> Option<f64> {
+        rate(self.unique_opens, self.deliveries)
+    }
+}
+
+/// The activity of one month (`YYYY-MM`, UTC).
+#[derive(serde::Serialize, Default)]
+pub struct MonthlyAnalytics {
+    pub month: String,
+    pub signups: i64,
+    /// Signups of the month that were confirmed, whenever that happened.
+    pub confirmed_signups: i64,
+    pub confirmation_rate: Option<f64>,
+    pub unsubscribes: i64,
+    /// Subscribers at the end of the month.
+    pub subscribers: i64,
+    pub sends: i64,
+    pub deliveries: i64,
+    pub failed_sends: i64,
+    pub opens: i64,
+    pub clicks: i64,
+}
+
+/// `part` out of `total`, between 0 and 1, if there is anything to divide.
+pub fn rate(part: i64, total: i64) -> Option<f64> {
+    (total > 0).then(|| part as f64 / total as f64)
+}
+
+#[tracing::instrument(name = "Build the analytics report", skip(pool))]
+pub async fn get_report(pool: &SqlitePool) -> Result<AnalyticsReport, anyhow::Error> {
+    Ok(AnalyticsReport {
+        subscribers: get_subscriber_summary(pool).await?,
+        issues: get_issue_analytics(pool).await?,
+        months: get_monthly_analytics(pool).await?,
+    })
+}
+
+async fn get_subscriber_summary(pool: &SqlitePool) -> Result<SubscriberSummary, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(status = 'confirmed'), 0) AS "confirmed!: i64",
+            COALESCE(SUM(status = 'pending_confirmation'), 0) AS "pending_confirmation!: i64",
+            COALESCE(SUM(status = 'unsubscribed'), 0) AS "unsubscribed!: i64"
+        FROM subscriptions
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count subscribers.")?;
+    // Unsubscribing takes a link from an issue, which only confirmed subscribers get.
+    let confirmed_signups = row.confirmed + row.unsubscribed;
+    Ok(SubscriberSummary {
+        confirmed: row.confirmed,
+        pending_confirmation: row.pending_confirmation,
+        unsubscribed: row.unsubscribed,
+        confirmation_rate: rate(
+            confirmed_signups,
+            confirmed_signups + row.pending_confirmation,
+        ),
+    })
+}
+
+async fn get_issue_analytics(pool: &SqlitePool) -> Result<Vec<IssueAnalytics>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueAnalytics,
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            i.published_at,
+            COALESCE(SUM(e.event_type IN ('sent', 'failed')), 0) AS "sends!: i64",
+            COALESCE(SUM(e.event_type = 'sent'), 0) AS "deliveries!: i64",
+            COALESCE(SUM(e.event_type = 'failed'), 0) AS "failed_sends!: i64",
+            COALESCE(SUM(e.event_type = 'opened'), 0) AS "opens!: i64",
+            COUNT(DISTINCT CASE WHEN e.event_type = 'opened' THEN e.subscriber_id END)
+                AS "unique_opens!: i64",
+            COALESCE(SUM(e.event_type = 'clicked'), 0) AS "clicks!: i64",
+            COUNT(DISTINCT CASE WHEN e.event_type = 'clicked' THEN e.subscriber_id END)
+                AS "unique_clicks!: i64",
+            COALESCE(SUM(e.event_type = 'unsubscribed'), 0) AS "unsubscribes!: i64"
+        FROM newsletter_issues i
+        LEFT JOIN delivery_events e ON e.newsletter_issue_id = i.newsletter_issue_id
+        WHERE i.status = 'published'
+        GROUP BY i.newsletter_issue_id
+        ORDER BY i.published_at DESC, i.newsletter_issue_id DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to aggregate the events of published issues.")?;
+    Ok(issues)
+}
+
+async fn get_monthly_analytics(pool: &SqlitePool) -> Result<Vec<MonthlyAnalytics>, anyhow::Error> {
+    let mut months: BTreeMap<String, MonthlyAnalytics> = BTreeMap::new();
+
+    // `subscribed_at` is stored as text, starting with the date.
+    let signups = sqlx::query!(
+        r#"
+        SELECT
+            substr(subscribed_at, 1, 7) AS "month!: String",
+            COUNT(*) AS "signups!: i64",
+            COALESCE(SUM(status != 'pending_confirmation'), 0) AS "confirmed_signups!: i64"
+        FROM subscriptions
+        GROUP BY 1
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count signups by month.")?;
+    for row in signups {
+        let month = months.entry(row.month).or_default();
+        month.signups = row.signups;
+        month.confirmed_signups = row.confirmed_signups;
+    }
+
+    let unsubscribes = sqlx::query!(
+        r#"
+        SELECT
+            strftime('%Y-%m', unsubscribed_at, 'unixepoch') AS "month!: String",
+            COUNT(*) AS "unsubscribes!: i64"
+        FROM subscriptions
+        WHERE unsubscribed_at IS NOT NULL
+        GROUP BY 1
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count unsubscribes by month.")?;
+    for row in unsubscribes {
+        months.entry(row.month).or_default().unsubscribes = row.unsubscribes;
+    }
+
+    let events = sqlx::query!(
+        r#"
+        SELECT
+            strftime('%Y-%m', occurred_at, 'unixepoch') AS "month!: String",
+            COALESCE(SUM(event_type IN ('sent', 'failed')), 0) AS "sends!: i64",
+            COALESCE(SUM(event_type = 'sent'), 0) AS "deliveries!: i64",
+            COALESCE(SUM(event_type = 'failed'), 0) AS "failed_sends!: i64",
+            COALESCE(SUM(event_type = 'opened'), 0) AS "opens!: i64",
+            COALESCE(SUM(event_type = 'clicked'), 0) AS "clicks!: i64"
+        FROM delivery_events
+        GROUP BY 1
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count delivery events by month.")?;
+    for row in events {
+        let month = months.entry(row.month).or_default();
+        month.sends = row.sends;
+        month.deliveries = row.deliveries;
+        month.failed_sends = row.failed_sends;
+        month.opens = row.opens;
+        month.clicks = row.clicks;
+    }
+
+    // Confirmations are not timestamped: subscribers count from the month they signed up.
+    let mut subscribers = 0;
+    Ok(months
+        .into_iter()
+        .map(|(key, mut month)| {
+            subscribers += month.confirmed_signups - month.unsubscribes;
+            month.month = key;
+            month.subscribers = subscribers;
+            month.confirmation_rate = rate(month.confirmed_signups, month.signups);
+            month
+        })
+        .collect())
+}
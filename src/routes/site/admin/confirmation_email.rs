@@ -0,0 +1,4 @@
+pub mod get;
+pub mod post;
+pub mod preview;
+pub mod send_test;
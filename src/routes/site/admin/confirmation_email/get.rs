@@ -0,0 +1,103 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::csrf::csrf_token,
+    confirmation_email::{confirmation_link, get_customised_confirmation_email, ConfirmationEmail},
+    i18n::Locale,
+    routes::admin::newsletter::preview::PREVIEW_SUBSCRIPTION_TOKEN,
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page},
+};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    locale: Option<Locale>,
+}
+
+/// The content of the confirmation email form, used to fill it back in after a preview or a test send.
+#[derive(serde::Deserialize)]
+pub struct ConfirmationEmailDraft {
+    pub locale: Locale,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    #[serde(default)]
+    pub test_recipients: String,
+}
+
+impl ConfirmationEmailDraft {
+    pub fn email(&self) -> ConfirmationEmail {
+        ConfirmationEmail {
+            subject: self.subject.clone(),
+            html_content: self.html_content.clone(),
+            text_content: self.text_content.clone(),
+        }
+    }
+
+    ///
+    /// Render the draft, returning the HTML and plain text bodies, or why it cannot be saved.
+    /// The confirmation link matches no subscriber, so following it confirms nobody.
+    pub fn render(&self, base_url: &str) -> Result<(String, String), String> {
+        let email = self.email();
+        email.validate()?;
+        email
+            .render(&confirmation_link(base_url, PREVIEW_SUBSCRIPTION_TOKEN))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// The confirmation email rendered the way a new subscriber would receive it.
+pub struct ConfirmationEmailPreview {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/confirmation_email.html")]
+struct ConfirmationEmailPage<'a> {
+    messages: Vec<String>,
+    preview: Option<ConfirmationEmailPreview>,
+    csrf_token: String,
+    locales: [Locale; 3],
+    draft: &'a ConfirmationEmailDraft,
+}
+
+pub async fn confirmation_email_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let locale = parameters.locale.unwrap_or_default();
+    let email = get_customised_confirmation_email(&pool, locale)
+        .await
+        .map_err(e500)?
+        .unwrap_or_else(|| ConfirmationEmail::default_for(locale));
+    let draft = ConfirmationEmailDraft {
+        locale,
+        subject: email.subject,
+        html_content: email.html_content,
+        text_content: email.text_content,
+        test_recipients: String::new(),
+    };
+    confirmation_email_page(flash_contents(&flash_messages), None, &session, &draft)
+}
+
+pub fn confirmation_email_page(
+    messages: Vec<String>,
+    preview: Option<ConfirmationEmailPreview>,
+    session: &TypedSession,
+    draft: &ConfirmationEmailDraft,
+) -> Result<HttpResponse, actix_web::Error> {
+    html_page(&ConfirmationEmailPage {
+        messages,
+        preview,
+        csrf_token: csrf_token(session)?,
+        locales: Locale::ALL,
+        draft,
+    })
+}
@@ -0,0 +1,38 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::UserId,
+    confirmation_email::save_confirmation_email,
+    session_state::TypedSession,
+    utils::{e500, see_other},
+};
+
+use super::get::{confirmation_email_page, ConfirmationEmailDraft};
+
+#[tracing::instrument {
+    name = "Customise the confirmation email"
+    skip(form, pool, session)
+    fields(user_id=%&*user_id)
+}]
+pub async fn update_confirmation_email(
+    form: web::Form<ConfirmationEmailDraft>,
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = form.email();
+    if let Err(e) = email.validate() {
+        return confirmation_email_page(vec![e], None, &session, &form);
+    }
+
+    save_confirmation_email(&pool, form.locale, &email)
+        .await
+        .map_err(e500)?;
+    FlashMessage::error("The confirmation email has been saved.").send();
+    Ok(see_other(&format!(
+        "/admin/confirmation-email?locale={}",
+        form.locale.code()
+    )))
+}
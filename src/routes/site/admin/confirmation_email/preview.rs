@@ -0,0 +1,29 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{authentication::UserId, session_state::TypedSession, startup::ApplicationBaseUrl};
+
+use super::get::{confirmation_email_page, ConfirmationEmailDraft, ConfirmationEmailPreview};
+
+#[tracing::instrument {
+    name = "Preview the confirmation email"
+    skip(form, base_url, session)
+    fields(user_id=%&*user_id)
+}]
+pub async fn preview_confirmation_email(
+    form: web::Form<ConfirmationEmailDraft>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    match form.render(&base_url.0) {
+        Err(e) => confirmation_email_page(vec![e], None, &session, &form),
+        Ok((html, text)) => {
+            let preview = ConfirmationEmailPreview {
+                subject: form.subject.clone(),
+                html,
+                text,
+            };
+            confirmation_email_page(Vec::new(), Some(preview), &session, &form)
+        }
+    }
+}
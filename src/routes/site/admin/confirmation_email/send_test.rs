@@ -0,0 +1,53 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    authentication::UserId,
+    email_client::EmailClient,
+    routes::admin::newsletter::send_test::{join, parse_recipients},
+    session_state::TypedSession,
+    startup::ApplicationBaseUrl,
+};
+
+use super::get::{confirmation_email_page, ConfirmationEmailDraft};
+
+#[tracing::instrument {
+    name = "Send a test confirmation email"
+    skip(form, email_client, base_url, session)
+    fields(user_id=%&*user_id)
+}]
+pub async fn send_test_confirmation_email(
+    form: web::Form<ConfirmationEmailDraft>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let message = match (
+        form.render(&base_url.0),
+        parse_recipients(&form.test_recipients),
+    ) {
+        (Err(e), _) | (_, Err(e)) => e,
+        (Ok((html, text)), Ok(recipients)) => {
+            let mut failed = Vec::new();
+            for recipient in &recipients {
+                if let Err(e) = email_client
+                    .send_email(recipient, &form.subject, &html, &text)
+                    .await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to deliver a test confirmation email"
+                    );
+                    failed.push(recipient.as_ref());
+                }
+            }
+            if failed.is_empty() {
+                format!("A test email has been sent to {}.", join(&recipients))
+            } else {
+                format!("Failed to send a test email to {}.", failed.join(", "))
+            }
+        }
+    };
+    confirmation_email_page(vec![message], None, &session, &form)
+}
@@ -1,40 +1,37 @@
-use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web::{web, HttpResponse};
 use anyhow::Context;
+use askama::Template;
 use sqlx::SqlitePool;
 
-use crate::{authentication::UserId, utils::e500};
+use crate::{
+    authentication::{csrf::csrf_token, Role, UserId},
+    session_state::TypedSession,
+    utils::{e500, html_page},
+};
+
+#[derive(Template)]
+#[template(path = "admin/dashboard.html")]
+struct DashboardPage {
+    username: String,
+    role: Role,
+    csrf_token: String,
+}
 
 pub async fn admin_dashboard(
     pool: web::Data<SqlitePool>,
     user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+    session: TypedSession,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = csrf_token(&session)?;
     let user_id = user_id.into_inner();
     let username = get_username(&user_id, &pool).await.map_err(e500)?;
 
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-    <title>Admin dashboard</title>
-</head>
-<body>
-    <p>Welcome {username}!</p>
-    <p>Available actions:</p>
-    <ol>
-        <li><a href="/admin/password">Change password</a></li>
-        <li>
-            <form name="logoutForm" action="/admin/logout" method="post">
-                <input type="submit" value="Logout">
-            </form>
-        </li>
-        <li><a href="/admin/newsletters">Send a Newsletter</a></li>
-    </ol>
-</body>
-</html>"#
-        )))
+    html_page(&DashboardPage {
+        username,
+        role: role.into_inner(),
+        csrf_token,
+    })
 }
 
 #[tracing::instrument {
@@ -0,0 +1,68 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use chrono::DateTime;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::csrf::csrf_token,
+    routes::site::filters,
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page},
+};
+
+struct IssueSummary {
+    newsletter_issue_id: String,
+    title: String,
+    status: String,
+    scheduled_at: Option<i64>,
+    published_at: Option<i64>,
+}
+
+impl IssueSummary {
+    /// The current schedule, in the format of a `datetime-local` input.
+    fn schedule_input_value(&self) -> String {
+        self.scheduled_at
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .map(|t| t.format("%Y-%m-%dT%H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/issues.html")]
+struct IssuesPage {
+    messages: Vec<String>,
+    csrf_token: String,
+    issues: Vec<IssueSummary>,
+}
+
+pub async fn list_issues(
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issues = get_issues(&pool).await.map_err(e500)?;
+    html_page(&IssuesPage {
+        messages: flash_contents(&flash_messages),
+        csrf_token: csrf_token(&session)?,
+        issues,
+    })
+}
+
+#[tracing::instrument(name = "Get newsletter issues", skip(pool))]
+async fn get_issues(pool: &SqlitePool) -> Result<Vec<IssueSummary>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT newsletter_issue_id, title, status, scheduled_at, published_at
+        FROM newsletter_issues
+        ORDER BY COALESCE(published_at, scheduled_at) DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve newsletter issues.")?;
+    Ok(issues)
+}
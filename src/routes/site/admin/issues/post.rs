@@ -0,0 +1,95 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::UserId,
+    domain::scheduled_at::ScheduledAt,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    scheduled_at: String,
+}
+
+#[tracing::instrument {
+    name = "Cancel a scheduled newsletter issue"
+    skip(pool)
+    fields(user_id=%&*user_id)
+}]
+pub async fn cancel_issue(
+    issue_id: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'cancelled'
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        issue_id
+    )
+    .execute(&**pool)
+    .await
+    .context("Failed to cancel the newsletter issue.")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_updated_rows > 0 {
+        FlashMessage::error("The newsletter issue has been cancelled.").send();
+    } else {
+        FlashMessage::error("Only scheduled issues can be cancelled.").send();
+    }
+    Ok(see_other("/admin/issues"))
+}
+
+#[tracing::instrument {
+    name = "Reschedule a newsletter issue"
+    skip(form, pool)
+    fields(user_id=%&*user_id)
+}]
+pub async fn reschedule_issue(
+    issue_id: web::Path<String>,
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let scheduled_at = match ScheduledAt::parse(form.0.scheduled_at) {
+        Ok(scheduled_at) => scheduled_at,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/issues"));
+        }
+    };
+
+    let timestamp = scheduled_at.timestamp();
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'scheduled', scheduled_at = $2
+        WHERE newsletter_issue_id = $1 AND status IN ('scheduled', 'cancelled')
+        "#,
+        issue_id,
+        timestamp
+    )
+    .execute(&**pool)
+    .await
+    .context("Failed to reschedule the newsletter issue.")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_updated_rows > 0 {
+        FlashMessage::error(format!(
+            "The newsletter issue has been scheduled for {scheduled_at}."
+        ))
+        .send();
+    } else {
+        FlashMessage::error("Published issues cannot be rescheduled.").send();
+    }
+    Ok(see_other("/admin/issues"))
+}
@@ -0,0 +1,155 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    routes::site::filters,
+    utils::{e500, html_page},
+};
+
+struct IssueStatus {
+    title: String,
+    status: String,
+    scheduled_at: Option<i64>,
+    published_at: Option<i64>,
+    track_opens: bool,
+    queued: i64,
+    sent: i64,
+    failed: i64,
+    opens: i64,
+    unique_opens: i64,
+    track_clicks: bool,
+    clicks: i64,
+    unique_clicks: i64,
+}
+
+/// How often one of the links of an issue was followed.
+struct LinkClicks {
+    url: String,
+    clicks: i64,
+    unique_clicks: i64,
+}
+
+impl IssueStatus {
+    /// The share of recipients who opened the issue, out of those we sent it to.
+    fn open_rate(&self) -> String {
+        self.rate(self.track_opens, self.unique_opens)
+    }
+
+    /// The share of recipients who followed at least one link.
+    fn click_rate(&self) -> String {
+        self.rate(self.track_clicks, self.unique_clicks)
+    }
+
+    fn rate(&self, tracked: bool, recipients: i64) -> String {
+        if !tracked {
+            return "Not tracked".into();
+        }
+        if self.sent == 0 {
+            return "-".into();
+        }
+        format!("{:.1}%", 100.0 * recipients as f64 / self.sent as f64)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/issue_status.html")]
+struct IssueStatusPage {
+    issue: IssueStatus,
+    links: Vec<LinkClicks>,
+}
+
+pub async fn issue_status(
+    issue_id: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_issue_status(&pool, &issue_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let links = if issue.track_clicks {
+        get_link_clicks(&pool, &issue_id).await.map_err(e500)?
+    } else {
+        Vec::new()
+    };
+    html_page(&IssueStatusPage { issue, links })
+}
+
+#[tracing::instrument(name = "Get the status of an issue", skip(pool))]
+async fn get_issue_status(
+    pool: &SqlitePool,
+    issue_id: &str,
+) -> Result<Option<IssueStatus>, anyhow::Error> {
+    let status = sqlx::query_as!(
+        IssueStatus,
+        r#"
+        SELECT
+            title,
+            status,
+            scheduled_at,
+            published_at,
+            track_opens AS "track_opens: bool",
+            (
+                SELECT COUNT(*) FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "queued!: i64",
+            (
+                SELECT COUNT(*) FROM delivery_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.event_type = 'sent'
+            ) AS "sent!: i64",
+            (
+                SELECT COUNT(*) FROM delivery_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.event_type = 'failed'
+            ) AS "failed!: i64",
+            (
+                SELECT COUNT(*) FROM delivery_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.event_type = 'opened'
+            ) AS "opens!: i64",
+            (
+                SELECT COUNT(DISTINCT subscriber_id) FROM delivery_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.event_type = 'opened'
+            ) AS "unique_opens!: i64",
+            track_clicks AS "track_clicks: bool",
+            (
+                SELECT COUNT(*) FROM delivery_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.event_type = 'clicked'
+            ) AS "clicks!: i64",
+            (
+                SELECT COUNT(DISTINCT subscriber_id) FROM delivery_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.event_type = 'clicked'
+            ) AS "unique_clicks!: i64"
+        FROM newsletter_issues i
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the status of a newsletter issue.")?;
+    Ok(status)
+}
+
+#[tracing::instrument(name = "Get the clicks on the links of an issue", skip(pool))]
+async fn get_link_clicks(
+    pool: &SqlitePool,
+    issue_id: &str,
+) -> Result<Vec<LinkClicks>, anyhow::Error> {
+    let links = sqlx::query_as!(
+        LinkClicks,
+        r#"
+        SELECT
+            url AS "url!",
+            COUNT(*) AS "clicks!: i64",
+            COUNT(DISTINCT subscriber_id) AS "unique_clicks!: i64"
+        FROM delivery_events
+        WHERE newsletter_issue_id = $1 AND event_type = 'clicked' AND url IS NOT NULL
+        GROUP BY url
+        ORDER BY 2 DESC, url
+        "#,
+        issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the clicks on the links of a newsletter issue.")?;
+    Ok(links)
+}
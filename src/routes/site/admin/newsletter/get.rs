@@ -1,41 +1,81 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
-use std::fmt::Write;
+use askama::Template;
+use sqlx::SqlitePool;
 
-pub async fn get(flash_messages: IncomingFlashMessages) -> Result<HttpResponse, actix_web::Error> {
-    let mut msg_html = String::new();
+use crate::{
+    authentication::csrf::csrf_token,
+    routes::admin::templates::get::{get_templates, EmailTemplate},
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page},
+};
 
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-    let idempotency_key = uuid::Uuid::new_v4().to_string();
-    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
-        format!(r#"
-<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-    <title>Send Newsletters</title>
-</head>
-<body>
-    {msg_html}
-    <form action="/admin/newsletters" method="post">
-        <label>Title:<br>
-            <input type="text" placeholder="Enter the issue field" name="title">
-        </label>
-        <br>
-        <label>
-            <textarea placeholder="Enter the content in plain text" name="text_content" rows="20" cols="50"></textarea>
-        </label>
-        <label>
-            <textarea placeholder="Enter the content in HTML format" name="html_content" rows="20" cols="50"></textarea>
-        </label>
-        <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
-        <button type="submit">Publish</button>
-    </form>
-    <p><a href="/admin/dashboard">&lt;- Back</a></p>
-</body>
-</html>
-        "#
-    )))
+/// The content of the newsletter form, used to fill it back in after a test send.
+#[derive(Default)]
+pub struct NewsletterDraft {
+    pub title: String,
+    pub markdown_content: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub template_id: String,
+    pub test_recipients: String,
+}
+
+/// An issue rendered the way a subscriber would receive it.
+pub struct IssuePreview {
+    pub title: String,
+    pub html: String,
+    pub text: String,
+    /// Raised while preparing the HTML for email clients.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/newsletter.html")]
+struct NewsletterPage<'a> {
+    messages: Vec<String>,
+    preview: Option<IssuePreview>,
+    csrf_token: String,
+    draft: &'a NewsletterDraft,
+    templates: &'a [EmailTemplate],
+    idempotency_key: String,
+}
+
+pub async fn get(
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let templates = get_templates(&pool).await.map_err(e500)?;
+    let draft = NewsletterDraft {
+        template_id: templates
+            .first()
+            .map(|t| t.template_id.clone())
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    newsletter_form(
+        flash_contents(&flash_messages),
+        None,
+        &session,
+        &draft,
+        &templates,
+    )
+}
+
+pub fn newsletter_form(
+    messages: Vec<String>,
+    preview: Option<IssuePreview>,
+    session: &TypedSession,
+    draft: &NewsletterDraft,
+    templates: &[EmailTemplate],
+) -> Result<HttpResponse, actix_web::Error> {
+    html_page(&NewsletterPage {
+        messages,
+        preview,
+        csrf_token: csrf_token(session)?,
+        draft,
+        templates,
+        idempotency_key: uuid::Uuid::new_v4().to_string(),
+    })
 }
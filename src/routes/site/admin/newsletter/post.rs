@@ -6,37 +6,96 @@ use tsid::create_tsid;
 
 use crate::{
     authentication::UserId,
+    domain::{issue_content::IssueContent, scheduled_at::ScheduledAt},
+    email_html,
+    i18n::Locale,
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    issue_delivery_worker::{enqueue_delivery_tasks, NewsletterIssue},
+    routes::admin::templates::get::{get_template, EmailTemplate},
+    startup::ApplicationBaseUrl,
     utils::{e400, e500, see_other},
 };
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     title: String,
+    #[serde(default)]
+    markdown_content: String,
+    #[serde(default)]
     text_content: String,
+    #[serde(default)]
     html_content: String,
+    #[serde(default)]
+    template_id: String,
+    /// Checkboxes are only sent when they are ticked.
+    track_opens: Option<String>,
+    track_clicks: Option<String>,
     idempotency_key: String,
+    scheduled_at: Option<String>,
 }
 
 #[tracing::instrument {
     name = "Publish a newsletter issue"
-    skip(form, pool)
+    skip(form, pool, base_url)
     fields(user_id=%&*user_id)
 }]
 pub async fn publish_newsletter(
     form: web::Form<FormData>,
     pool: web::Data<SqlitePool>,
+    base_url: web::Data<ApplicationBaseUrl>,
     user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id: UserId = user_id.into_inner();
 
     let FormData {
         title,
+        markdown_content,
         text_content,
         html_content,
+        template_id,
+        track_opens,
+        track_clicks,
         idempotency_key,
+        scheduled_at,
     } = form.0;
+    let track_opens = track_opens.is_some();
+    let track_clicks = track_clicks.is_some();
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let mut content = match IssueContent::parse(markdown_content, html_content, text_content) {
+        Ok(content) => content,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/newsletters"));
+        }
+    };
+    let template = match template_id.as_str() {
+        "" => None,
+        template_id => match get_template(&pool, template_id).await.map_err(e500)? {
+            Some(template) => Some(template),
+            None => {
+                FlashMessage::error("The selected layout does not exist.").send();
+                return Ok(see_other("/admin/newsletters"));
+            }
+        },
+    };
+    // Subscribers get what the preview shows: sanitised, with styles inlined and absolute URLs.
+    let prepared = email_html::prepare(&content.html_content, &base_url.0);
+    content.html_content = prepared.html;
+    // Catch typos in merge variables now rather than once emails are going out.
+    if let Err(e) = issue_templates_are_valid(&content, template.as_ref()) {
+        FlashMessage::error(e).send();
+        return Ok(see_other("/admin/newsletters"));
+    }
+    let scheduled_at = match scheduled_at.filter(|s| !s.trim().is_empty()) {
+        None => None,
+        Some(s) => match ScheduledAt::parse(s) {
+            Ok(scheduled_at) => Some(scheduled_at),
+            Err(e) => {
+                FlashMessage::error(e).send();
+                return Ok(see_other("/admin/newsletters"));
+            }
+        },
+    };
 
     // Return early if we have a saved response in the database
     let mut transaction = match try_processing(&pool, &idempotency_key, &user_id)
@@ -45,82 +104,134 @@ pub async fn publish_newsletter(
     {
         NextAction::StartProcessing(t) => t,
         NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
+            success_message(scheduled_at).send();
+            send_warnings(prepared.warnings);
             return Ok(saved_response);
         }
     };
 
     tracing::Span::current().record("user_id", tracing::field::display(&user_id));
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details")
-        .map_err(e500)?;
-    enqueue_delivery_tasks(&mut transaction, issue_id)
-        .await
-        .context("Failed to enqueue delivery tasks")
-        .map_err(e500)?;
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &title,
+        &content,
+        template.as_ref(),
+        track_opens,
+        track_clicks,
+        scheduled_at,
+    )
+    .await
+    .context("Failed to store newsletter issue details")
+    .map_err(e500)?;
+    // Scheduled issues are fanned out by the scheduler once they are due.
+    if scheduled_at.is_none() {
+        enqueue_delivery_tasks(&mut transaction, &issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks")
+            .map_err(e500)?;
+    }
 
     let response = see_other("/admin/newsletters");
     let response = save_response(transaction, &idempotency_key, &user_id, response)
         .await
         .map_err(e500)?;
-    success_message().send();
+    success_message(scheduled_at).send();
+    send_warnings(prepared.warnings);
     Ok(response)
 }
 
-fn success_message() -> FlashMessage {
-    FlashMessage::error("The newsletter issue has been accepted - emails will go out shortly.")
+/// Tell the editor about what preparing the HTML for email clients ran into, as the preview does.
+pub fn send_warnings(warnings: Vec<String>) {
+    for warning in warnings {
+        FlashMessage::error(warning).send();
+    }
+}
+
+fn success_message(scheduled_at: Option<ScheduledAt>) -> FlashMessage {
+    match scheduled_at {
+        None => FlashMessage::error(
+            "The newsletter issue has been accepted - emails will go out shortly.",
+        ),
+        Some(scheduled_at) => FlashMessage::error(format!(
+            "The newsletter issue has been scheduled for {scheduled_at}."
+        )),
+    }
+}
+
+fn issue_templates_are_valid(
+    content: &IssueContent,
+    template: Option<&EmailTemplate>,
+) -> Result<(), String> {
+    let issue = NewsletterIssue {
+        newsletter_issue_id: String::new(),
+        title: String::new(),
+        text_content: content.text_content.clone(),
+        html_content: content.html_content.clone(),
+        text_layout: template.map(|t| t.text_layout.clone()),
+        html_layout: template.map(|t| t.html_layout.clone()),
+        track_opens: false,
+        track_clicks: false,
+    };
+    issue
+        .templates(Locale::default())
+        .map(|_| ())
+        .map_err(|e| format!("The issue cannot be published: {e}"))
 }
 
 #[tracing::instrument(skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'static, Sqlite>,
     title: &str,
-    text_content: &str,
-    html_content: &str,
+    content: &IssueContent,
+    template: Option<&EmailTemplate>,
+    track_opens: bool,
+    track_clicks: bool,
+    scheduled_at: Option<ScheduledAt>,
 ) -> Result<String, sqlx::Error> {
     let newsletter_issue_id = create_tsid().to_string();
+    let scheduled_at = scheduled_at.map(|t| t.timestamp());
+    let template_id = template.map(|t| &t.template_id);
+    let html_layout = template.map(|t| &t.html_layout);
+    let text_layout = template.map(|t| &t.text_layout);
     let _ = sqlx::query!(
         r#"
         INSERT INTO newsletter_issues (
             newsletter_issue_id,
             title,
+            markdown_content,
             text_content,
             html_content,
+            template_id,
+            html_layout,
+            text_layout,
+            track_opens,
+            track_clicks,
+            status,
+            scheduled_at,
             published_at
         )
-        VALUES ($1, $2, $3, $4, unixepoch())
+        VALUES (
+            $1, $2, $3, $4, $5, $7, $8, $9, $10, $11,
+            CASE WHEN $6 IS NULL THEN 'published' ELSE 'scheduled' END,
+            $6,
+            CASE WHEN $6 IS NULL THEN unixepoch() ELSE NULL END
+        )
         "#,
         newsletter_issue_id,
         title,
-        text_content,
-        html_content
+        content.markdown_content,
+        content.text_content,
+        content.html_content,
+        scheduled_at,
+        template_id,
+        html_layout,
+        text_layout,
+        track_opens,
+        track_clicks
     )
     .execute(&mut **transaction)
     .await?;
 
     Ok(newsletter_issue_id)
 }
-
-#[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
-    transaction: &mut Transaction<'static, Sqlite>,
-    newsletters_issue_id: String,
-) -> Result<(), sqlx::Error> {
-    let _ = sqlx::query!(
-        r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        )
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
-        "#,
-        newsletters_issue_id
-    )
-    .execute(&mut **transaction)
-    .await?;
-    Ok(())
-}
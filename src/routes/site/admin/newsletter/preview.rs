@@ -0,0 +1,133 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::UserId,
+    domain::issue_content::IssueContent,
+    email_html,
+    i18n::Locale,
+    issue_delivery_worker::{NewsletterIssue, DRAFT_ISSUE_ID},
+    routes::admin::templates::get::{get_template, get_templates},
+    session_state::TypedSession,
+    startup::ApplicationBaseUrl,
+    utils::e500,
+};
+
+use super::get::{newsletter_form, IssuePreview, NewsletterDraft};
+
+/// Merge values used when an issue is rendered for the editor rather than a subscriber.
+/// The unsubscribe link they produce does not match any subscriber.
+pub const PREVIEW_NAME: &str = "Test Subscriber";
+pub const PREVIEW_SUBSCRIPTION_TOKEN: &str = "preview";
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    title: String,
+    #[serde(default)]
+    markdown_content: String,
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    html_content: String,
+    #[serde(default)]
+    template_id: String,
+    #[serde(default)]
+    test_recipients: String,
+}
+
+#[tracing::instrument {
+    name = "Preview a newsletter issue"
+    skip(form, pool, base_url, session)
+    fields(user_id=%&*user_id)
+}]
+pub async fn preview_newsletter(
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        title,
+        markdown_content,
+        text_content,
+        html_content,
+        template_id,
+        test_recipients,
+    } = form.0;
+
+    let content = IssueContent::parse(
+        markdown_content.clone(),
+        html_content.clone(),
+        text_content.clone(),
+    );
+    let (messages, preview) = match content {
+        Err(e) => (vec![e], None),
+        Ok(content) => {
+            let (issue, mut warnings) =
+                draft_issue(&pool, &base_url.0, title.clone(), content, &template_id)
+                    .await
+                    .map_err(e500)?;
+            match issue.render(
+                &base_url.0,
+                PREVIEW_NAME,
+                PREVIEW_SUBSCRIPTION_TOKEN,
+                Locale::default(),
+            ) {
+                Err(e) => (vec![format!("The issue cannot be published: {e}")], None),
+                Ok((html, text)) => {
+                    warnings.extend(email_html::size_warning(&html));
+                    let preview = IssuePreview {
+                        title: issue.title,
+                        html,
+                        text,
+                        warnings,
+                    };
+                    (Vec::new(), Some(preview))
+                }
+            }
+        }
+    };
+
+    let draft = NewsletterDraft {
+        title,
+        markdown_content,
+        text_content,
+        html_content,
+        template_id,
+        test_recipients,
+    };
+    let templates = get_templates(&pool).await.map_err(e500)?;
+    newsletter_form(messages, preview, &session, &draft, &templates)
+}
+
+///
+/// Build the issue the form describes, the way it would be published, without storing it.
+/// Returns the warnings raised while preparing its HTML for email clients.
+pub async fn draft_issue(
+    pool: &SqlitePool,
+    base_url: &str,
+    title: String,
+    content: IssueContent,
+    template_id: &str,
+) -> Result<(NewsletterIssue, Vec<String>), anyhow::Error> {
+    let template = match template_id {
+        "" => None,
+        template_id => get_template(pool, template_id)
+            .await
+            .context("Failed to retrieve the selected layout.")?,
+    };
+    let prepared = email_html::prepare(&content.html_content, base_url);
+    let issue = NewsletterIssue {
+        newsletter_issue_id: DRAFT_ISSUE_ID.into(),
+        title,
+        text_content: content.text_content,
+        html_content: prepared.html,
+        text_layout: template.as_ref().map(|t| t.text_layout.clone()),
+        html_layout: template.as_ref().map(|t| t.html_layout.clone()),
+        track_opens: false,
+        track_clicks: false,
+    };
+    Ok((issue, prepared.warnings))
+}
@@ -0,0 +1,142 @@
+use actix_web::{web, HttpResponse};
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::UserId,
+    domain::{issue_content::IssueContent, subscriber_email::SubscriberEmail},
+    email_client::EmailClient,
+    i18n::Locale,
+    issue_delivery_worker::{deliver_issue, Recipient},
+    routes::admin::templates::get::get_templates,
+    session_state::TypedSession,
+    startup::ApplicationBaseUrl,
+    utils::e500,
+};
+
+use super::{
+    get::{newsletter_form, NewsletterDraft},
+    preview::{draft_issue, PREVIEW_NAME, PREVIEW_SUBSCRIPTION_TOKEN},
+};
+
+/// A test send is not an excuse to mail a whole audience from the admin panel.
+const MAX_TEST_RECIPIENTS: usize = 10;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    title: String,
+    #[serde(default)]
+    markdown_content: String,
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    html_content: String,
+    #[serde(default)]
+    template_id: String,
+    test_recipients: String,
+}
+
+#[tracing::instrument {
+    name = "Send a test newsletter issue"
+    skip(form, pool, email_client, base_url, session)
+    fields(user_id=%&*user_id)
+}]
+pub async fn send_test_email(
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        title,
+        markdown_content,
+        text_content,
+        html_content,
+        template_id,
+        test_recipients,
+    } = form.0;
+
+    let content = IssueContent::parse(
+        markdown_content.clone(),
+        html_content.clone(),
+        text_content.clone(),
+    );
+    let message = match (content, parse_recipients(&test_recipients)) {
+        (Err(e), _) | (_, Err(e)) => e,
+        (Ok(content), Ok(recipients)) => {
+            // Nothing is written to `newsletter_issues` or `issue_delivery_queue`:
+            // the draft only lives in the form until it gets published.
+            let (issue, _) = draft_issue(&pool, &base_url.0, title.clone(), content, &template_id)
+                .await
+                .map_err(e500)?;
+            let mut failed = Vec::new();
+            for email in &recipients {
+                // Merge variables are filled in with placeholders, so the links
+                // in a test email do not unsubscribe anybody.
+                let recipient = Recipient {
+                    // Test sends are never tracked: no events are attributed to anybody.
+                    subscriber_id: String::new(),
+                    email: email.clone(),
+                    name: PREVIEW_NAME.into(),
+                    subscription_token: PREVIEW_SUBSCRIPTION_TOKEN.into(),
+                    tracking_opt_out: true,
+                    locale: Locale::default(),
+                };
+                if let Err(e) =
+                    deliver_issue(&email_client, &base_url.0, &issue, &recipient, None).await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to deliver a test issue"
+                    );
+                    failed.push(email.as_ref());
+                }
+            }
+            if failed.is_empty() {
+                format!("A test email has been sent to {}.", join(&recipients))
+            } else {
+                format!("Failed to send a test email to {}.", failed.join(", "))
+            }
+        }
+    };
+
+    let draft = NewsletterDraft {
+        title,
+        markdown_content,
+        text_content,
+        html_content,
+        template_id,
+        test_recipients,
+    };
+    let templates = get_templates(&pool).await.map_err(e500)?;
+    newsletter_form(vec![message], None, &session, &draft, &templates)
+}
+
+pub fn parse_recipients(test_recipients: &str) -> Result<Vec<SubscriberEmail>, String> {
+    let recipients = test_recipients
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| SubscriberEmail::parse(s.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if recipients.is_empty() {
+        return Err("Enter at least one address to send a test to.".into());
+    }
+    if recipients.len() > MAX_TEST_RECIPIENTS {
+        return Err(format!(
+            "A test can be sent to at most {MAX_TEST_RECIPIENTS} addresses."
+        ));
+    }
+    Ok(recipients)
+}
+
+pub fn join(recipients: &[SubscriberEmail]) -> String {
+    recipients
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,42 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::{
+        csrf::csrf_token, middleware::admin_page_or_dashboard, two_factor::two_factor_enabled,
+        UserId,
+    },
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page},
+};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    next: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/reauthenticate.html")]
+struct ReauthenticatePage<'a> {
+    messages: Vec<String>,
+    csrf_token: String,
+    next: &'a str,
+    two_factor_enabled: bool,
+}
+
+pub async fn reauthenticate_form(
+    query: web::Query<Parameters>,
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    html_page(&ReauthenticatePage {
+        messages: flash_contents(&flash_messages),
+        csrf_token: csrf_token(&session)?,
+        next: admin_page_or_dashboard(query.next.as_deref().unwrap_or_default()),
+        two_factor_enabled: two_factor_enabled(&user_id, &pool).await.map_err(e500)?,
+    })
+}
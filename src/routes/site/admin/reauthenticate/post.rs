@@ -0,0 +1,87 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use chrono::Utc;
+use secrecy::Secret;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::{
+        middleware::admin_page_or_dashboard,
+        throttling::{clear_failed_logins, login_retry_after, record_failed_login},
+        two_factor::{two_factor_enabled, verify_second_factor},
+        validate_credentials, AuthError, Credentials, UserId,
+    },
+    routes::admin::dashboard::get_username,
+    session_state::TypedSession,
+    utils::{e500, peer_ip_address, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    password: Secret<String>,
+    code: Option<Secret<String>>,
+    next: String,
+}
+
+#[tracing::instrument {
+    name = "Re-authenticate a user"
+    skip(form, pool, session, request)
+    fields(user_id=%&*user_id)
+}]
+pub async fn reauthenticate(
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    session: TypedSession,
+    request: HttpRequest,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.into_inner();
+    let next = admin_page_or_dashboard(&form.next);
+    let retry = format!("/admin/reauthenticate?next={}", urlencoding::encode(next));
+
+    let username = get_username(&user_id, &pool).await.map_err(e500)?;
+    let ip_address = peer_ip_address(&request);
+    // Same throttling as the login page: this form checks the same password.
+    if let Some(retry_after) = login_retry_after(&pool, &username, &ip_address)
+        .await
+        .map_err(e500)?
+    {
+        tracing::warn!(retry_after, "Re-authentication attempt throttled.");
+        FlashMessage::error("Too many failed attempts, please try again later.").send();
+        return Ok(see_other(&retry));
+    }
+
+    let credentials = Credentials {
+        username: username.clone(),
+        password: form.password,
+    };
+    if let Err(e) = validate_credentials(credentials, &pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                record_failed_login(&pool, &username, &ip_address)
+                    .await
+                    .map_err(e500)?;
+                FlashMessage::error("The password is incorrect.").send();
+                Ok(see_other(&retry))
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+
+    if two_factor_enabled(&user_id, &pool).await.map_err(e500)? {
+        let code = form.code.unwrap_or_else(|| Secret::new(String::new()));
+        if !verify_second_factor(&user_id, code, &pool)
+            .await
+            .map_err(e500)?
+        {
+            FlashMessage::error("The code is invalid.").send();
+            return Ok(see_other(&retry));
+        }
+    }
+
+    clear_failed_logins(&pool, &username).await.map_err(e500)?;
+    session
+        .insert_authenticated_at(Utc::now().timestamp())
+        .map_err(e500)?;
+    Ok(see_other(next))
+}
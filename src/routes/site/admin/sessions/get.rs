@@ -0,0 +1,47 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+use crate::{
+    authentication::{csrf::csrf_token, Role, UserId},
+    routes::site::filters,
+    session::{SessionInfo, SqlxSqliteSessionStore},
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page},
+};
+
+#[derive(Template)]
+#[template(path = "admin/sessions.html")]
+struct SessionsPage {
+    messages: Vec<String>,
+    csrf_token: String,
+    sessions: Vec<SessionInfo>,
+    current_handle: Option<String>,
+    can_revoke_everyone: bool,
+}
+
+impl SessionsPage {
+    fn is_current(&self, handle: &str) -> bool {
+        self.current_handle.as_deref() == Some(handle)
+    }
+}
+
+pub async fn list_sessions(
+    session_store: web::Data<SqlxSqliteSessionStore>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let sessions = session_store
+        .list_user_sessions(&user_id)
+        .await
+        .map_err(e500)?;
+    html_page(&SessionsPage {
+        messages: flash_contents(&flash_messages),
+        csrf_token: csrf_token(&session)?,
+        sessions,
+        current_handle: session.get_session_handle().map_err(e500)?,
+        can_revoke_everyone: role.can_manage_users(),
+    })
+}
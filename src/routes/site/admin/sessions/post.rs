@@ -0,0 +1,69 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+
+use crate::{
+    authentication::UserId,
+    session::SqlxSqliteSessionStore,
+    utils::{e500, see_other},
+};
+
+#[tracing::instrument {
+    name = "Revoke a session"
+    skip(session_store)
+    fields(user_id=%&*user_id)
+}]
+pub async fn revoke_session(
+    handle: web::Path<String>,
+    session_store: web::Data<SqlxSqliteSessionStore>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Only the user's own sessions match, whatever the handle.
+    if !session_store
+        .delete_user_session(&user_id, &handle)
+        .await
+        .context("Failed to revoke a session.")
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    FlashMessage::error("The session has been revoked.").send();
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument {
+    name = "Revoke all sessions of a user"
+    skip(session_store)
+    fields(user_id=%&*user_id)
+}]
+pub async fn revoke_all_sessions(
+    session_store: web::Data<SqlxSqliteSessionStore>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    session_store
+        .delete_user_sessions(&user_id)
+        .await
+        .context("Failed to revoke the sessions of a user.")
+        .map_err(e500)?;
+    FlashMessage::error("You have been logged out of all sessions.").send();
+    Ok(see_other("/login"))
+}
+
+#[tracing::instrument {
+    name = "Revoke the sessions of every user"
+    skip(session_store)
+    fields(user_id=%&*user_id)
+}]
+pub async fn revoke_everyone(
+    session_store: web::Data<SqlxSqliteSessionStore>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    session_store
+        .delete_all_sessions()
+        .await
+        .context("Failed to revoke all sessions.")
+        .map_err(e500)?;
+    tracing::warn!("Every user has been logged out.");
+    FlashMessage::error("Everyone has been logged out.").send();
+    Ok(see_other("/login"))
+}
@@ -0,0 +1,122 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::csrf::csrf_token,
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page},
+};
+
+#[derive(Default)]
+pub struct EmailTemplate {
+    pub template_id: String,
+    pub name: String,
+    pub html_layout: String,
+    pub text_layout: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/email_templates.html")]
+struct EmailTemplatesPage {
+    messages: Vec<String>,
+    templates: Vec<EmailTemplate>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/email_template.html")]
+struct EmailTemplatePage<'a> {
+    messages: Vec<String>,
+    csrf_token: String,
+    action: &'a str,
+    template: &'a EmailTemplate,
+}
+
+pub async fn list_templates(
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let templates = get_templates(&pool).await.map_err(e500)?;
+    html_page(&EmailTemplatesPage {
+        messages: flash_contents(&flash_messages),
+        templates,
+    })
+}
+
+pub async fn new_template_form(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+    template_form(
+        Vec::new(),
+        &session,
+        "/admin/templates",
+        &EmailTemplate::default(),
+    )
+}
+
+pub async fn edit_template_form(
+    template_id: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    match get_template(&pool, &template_id).await.map_err(e500)? {
+        Some(template) => template_form(
+            flash_contents(&flash_messages),
+            &session,
+            &format!("/admin/templates/{}", template.template_id),
+            &template,
+        ),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+pub fn template_form(
+    messages: Vec<String>,
+    session: &TypedSession,
+    action: &str,
+    template: &EmailTemplate,
+) -> Result<HttpResponse, actix_web::Error> {
+    html_page(&EmailTemplatePage {
+        messages,
+        csrf_token: csrf_token(session)?,
+        action,
+        template,
+    })
+}
+
+#[tracing::instrument(name = "Get email templates", skip(pool))]
+pub async fn get_templates(pool: &SqlitePool) -> Result<Vec<EmailTemplate>, anyhow::Error> {
+    let templates = sqlx::query_as!(
+        EmailTemplate,
+        r#"
+        SELECT template_id, name, html_layout, text_layout
+        FROM email_templates
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve email templates.")?;
+    Ok(templates)
+}
+
+#[tracing::instrument(name = "Get email template", skip(pool))]
+pub async fn get_template(
+    pool: &SqlitePool,
+    template_id: &str,
+) -> Result<Option<EmailTemplate>, anyhow::Error> {
+    let template = sqlx::query_as!(
+        EmailTemplate,
+        r#"
+        SELECT template_id, name, html_layout, text_layout
+        FROM email_templates
+        WHERE template_id = $1
+        "#,
+        template_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve an email template.")?;
+    Ok(template)
+}
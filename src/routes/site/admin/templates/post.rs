@@ -0,0 +1,164 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::SqlitePool;
+use tsid::create_tsid;
+
+use crate::{
+    authentication::UserId,
+    email_html::{self, PreparedHtml},
+    routes::admin::newsletter::post::send_warnings,
+    session_state::TypedSession,
+    startup::ApplicationBaseUrl,
+    templating::MergeTemplate,
+    utils::{e500, see_other},
+};
+
+use super::get::{template_form, EmailTemplate};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    html_layout: String,
+    text_layout: String,
+}
+
+impl FormData {
+    ///
+    /// Returns the HTML layout to store: it wraps the content of issues,
+    /// so it is prepared for email clients the same way.
+    fn validate(&self, base_url: &str) -> Result<PreparedHtml, String> {
+        if self.name.trim().is_empty() {
+            return Err("The template must have a name.".into());
+        }
+        MergeTemplate::parse_layout(&self.html_layout)
+            .map_err(|e| format!("The HTML layout is invalid: {e}"))?;
+        MergeTemplate::parse_layout(&self.text_layout)
+            .map_err(|e| format!("The plain text layout is invalid: {e}"))?;
+        let html_layout = email_html::prepare(&self.html_layout, base_url);
+        MergeTemplate::parse_layout(&html_layout.html)
+            .map_err(|e| format!("The HTML layout is invalid once sanitised: {e}"))?;
+        Ok(html_layout)
+    }
+
+    /// Render the form again, with what the admin typed and why it was rejected.
+    fn rejected(
+        self,
+        session: &TypedSession,
+        action: &str,
+        template_id: String,
+        message: &str,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        let template = EmailTemplate {
+            template_id,
+            name: self.name,
+            html_layout: self.html_layout,
+            text_layout: self.text_layout,
+        };
+        template_form(vec![message.to_owned()], session, action, &template)
+    }
+}
+
+#[tracing::instrument {
+    name = "Create an email template"
+    skip(form, pool, base_url, session)
+    fields(user_id=%&*user_id)
+}]
+pub async fn create_template(
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let action = "/admin/templates";
+    let html_layout = match form.validate(&base_url.0) {
+        Ok(html_layout) => html_layout,
+        Err(e) => return form.0.rejected(&session, action, String::new(), &e),
+    };
+
+    let template_id = create_tsid().to_string();
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO email_templates (template_id, name, html_layout, text_layout, updated_at)
+        VALUES ($1, $2, $3, $4, unixepoch())
+        "#,
+        template_id,
+        form.name,
+        html_layout.html,
+        form.text_layout
+    )
+    .execute(&**pool)
+    .await;
+
+    match result {
+        Ok(_) => {
+            FlashMessage::error("The template has been created.").send();
+            send_warnings(html_layout.warnings);
+            Ok(see_other("/admin/templates"))
+        }
+        Err(e) if is_unique_violation(&e) => {
+            let message = format!("A template named {} already exists.", form.name);
+            form.0.rejected(&session, action, String::new(), &message)
+        }
+        Err(e) => Err(e500(
+            anyhow::Error::from(e).context("Failed to store the email template."),
+        )),
+    }
+}
+
+#[tracing::instrument {
+    name = "Update an email template"
+    skip(form, pool, base_url, session)
+    fields(user_id=%&*user_id)
+}]
+pub async fn update_template(
+    template_id: web::Path<String>,
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let template_id = template_id.into_inner();
+    let action = format!("/admin/templates/{template_id}");
+    let html_layout = match form.validate(&base_url.0) {
+        Ok(html_layout) => html_layout,
+        Err(e) => return form.0.rejected(&session, &action, template_id, &e),
+    };
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE email_templates
+        SET name = $2, html_layout = $3, text_layout = $4, updated_at = unixepoch()
+        WHERE template_id = $1
+        "#,
+        template_id,
+        form.name,
+        html_layout.html,
+        form.text_layout
+    )
+    .execute(&**pool)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 0 => Ok(HttpResponse::NotFound().finish()),
+        Ok(_) => {
+            FlashMessage::error("The template has been saved.").send();
+            send_warnings(html_layout.warnings);
+            Ok(see_other(&action))
+        }
+        Err(e) if is_unique_violation(&e) => {
+            let message = format!("A template named {} already exists.", form.name);
+            form.0.rejected(&session, &action, template_id, &message)
+        }
+        Err(e) => Err(e500(
+            anyhow::Error::from(e).context("Failed to update the email template."),
+        )),
+    }
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .map(|e| e.is_unique_violation())
+        .unwrap_or(false)
+}
@@ -0,0 +1,97 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::{
+        csrf::csrf_token,
+        two_factor::{generate_totp_secret, provisioning_uri, qr_code_svg, two_factor_enabled},
+        UserId,
+    },
+    routes::admin::dashboard::get_username,
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page},
+};
+
+/// What a user needs to set up their authenticator app.
+struct Enrolment {
+    secret: String,
+    uri: String,
+    qr_code: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/two_factor.html")]
+struct TwoFactorPage {
+    messages: Vec<String>,
+    csrf_token: String,
+    /// Only set while two-factor authentication is disabled.
+    enrolment: Option<Enrolment>,
+    unused_recovery_codes: i64,
+}
+
+pub async fn two_factor_settings(
+    pool: web::Data<SqlitePool>,
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    let (enrolment, unused_recovery_codes) =
+        if two_factor_enabled(&user_id, &pool).await.map_err(e500)? {
+            let remaining = count_unused_recovery_codes(&user_id, &pool)
+                .await
+                .map_err(e500)?;
+            (None, remaining)
+        } else {
+            // Keep the same secret across reloads, so a scanned QR code stays valid.
+            let secret = match session.get_totp_enrolment_secret().map_err(e500)? {
+                Some(secret) => secret,
+                None => {
+                    let secret = generate_totp_secret();
+                    session
+                        .insert_totp_enrolment_secret(secret.clone())
+                        .map_err(e500)?;
+                    secret
+                }
+            };
+            let username = get_username(&user_id, &pool).await.map_err(e500)?;
+            let uri = provisioning_uri(&secret, &username).map_err(e500)?;
+            let qr_code = qr_code_svg(&uri).map_err(e500)?;
+            let enrolment = Enrolment {
+                secret,
+                uri,
+                qr_code,
+            };
+            (Some(enrolment), 0)
+        };
+
+    html_page(&TwoFactorPage {
+        messages: flash_contents(&flash_messages),
+        csrf_token: csrf_token(&session)?,
+        enrolment,
+        unused_recovery_codes,
+    })
+}
+
+#[tracing::instrument(name = "Count unused recovery codes", skip(pool))]
+async fn count_unused_recovery_codes(
+    user_id: &str,
+    pool: &SqlitePool,
+) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!: i64"
+        FROM recovery_codes
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count unused recovery codes.")?;
+    Ok(row.count)
+}
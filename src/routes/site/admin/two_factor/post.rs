@@ -0,0 +1,86 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use askama::Template;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::{
+        two_factor::{self, current_matching_step, two_factor_enabled, verify_second_factor},
+        UserId,
+    },
+    session_state::TypedSession,
+    utils::{e500, html_page, see_other},
+};
+
+#[derive(Template)]
+#[template(path = "admin/recovery_codes.html")]
+struct RecoveryCodesPage {
+    recovery_codes: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: Secret<String>,
+}
+
+#[tracing::instrument {
+    name = "Enable two-factor authentication"
+    skip(form, pool, session)
+    fields(user_id=%&*user_id)
+}]
+pub async fn enable_two_factor(
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    if two_factor_enabled(&user_id, &pool).await.map_err(e500)? {
+        FlashMessage::error("Two-factor authentication is already enabled.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+    let Some(secret) = session.get_totp_enrolment_secret().map_err(e500)? else {
+        FlashMessage::error("Please scan the QR code before entering a code.").send();
+        return Ok(see_other("/admin/two-factor"));
+    };
+    // Proves the authenticator app is set up correctly before anyone relies on it.
+    let Some(step) = current_matching_step(&secret, form.0.code.expose_secret()).map_err(e500)?
+    else {
+        FlashMessage::error("The code is invalid.").send();
+        return Ok(see_other("/admin/two-factor"));
+    };
+
+    let recovery_codes = two_factor::enable_two_factor(&user_id, &secret, step, &pool)
+        .await
+        .map_err(e500)?;
+    session.remove_totp_enrolment_secret();
+
+    html_page(&RecoveryCodesPage { recovery_codes })
+}
+
+#[tracing::instrument {
+    name = "Disable two-factor authentication"
+    skip(form, pool)
+    fields(user_id=%&*user_id)
+}]
+pub async fn disable_two_factor(
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    // A stolen session alone must not be enough to weaken the account.
+    if !verify_second_factor(&user_id, form.0.code, &pool)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("The code is invalid.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+    two_factor::disable_two_factor(&user_id, &pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::error("Two-factor authentication has been disabled.").send();
+    Ok(see_other("/admin/two-factor"))
+}
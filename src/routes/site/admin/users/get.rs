@@ -0,0 +1,92 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::{csrf::csrf_token, Role, UserId},
+    routes::site::filters,
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page},
+};
+
+struct User {
+    user_id: String,
+    username: String,
+    email: Option<String>,
+    status: String,
+    role: String,
+}
+
+struct PendingInvitation {
+    email: String,
+    expires_at: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin/users.html")]
+struct UsersPage {
+    messages: Vec<String>,
+    csrf_token: String,
+    users: Vec<User>,
+    invitations: Vec<PendingInvitation>,
+    current_user_id: String,
+    roles: [Role; 4],
+    /// Preselected in the invitation form.
+    invitation_role: &'static str,
+}
+
+pub async fn list_users(
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let users = get_users(&pool).await.map_err(e500)?;
+    let invitations = get_pending_invitations(&pool).await.map_err(e500)?;
+    html_page(&UsersPage {
+        messages: flash_contents(&flash_messages),
+        csrf_token: csrf_token(&session)?,
+        users,
+        invitations,
+        current_user_id: user_id.into_inner().0,
+        roles: Role::ALL,
+        invitation_role: Role::Editor.as_str(),
+    })
+}
+
+#[tracing::instrument(name = "Get users", skip(pool))]
+async fn get_users(pool: &SqlitePool) -> Result<Vec<User>, anyhow::Error> {
+    let users = sqlx::query_as!(
+        User,
+        r#"
+        SELECT user_id, username, email, status, role
+        FROM users
+        ORDER BY username
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve users.")?;
+    Ok(users)
+}
+
+#[tracing::instrument(name = "Get pending invitations", skip(pool))]
+async fn get_pending_invitations(
+    pool: &SqlitePool,
+) -> Result<Vec<PendingInvitation>, anyhow::Error> {
+    let invitations = sqlx::query_as!(
+        PendingInvitation,
+        r#"
+        SELECT email, expires_at
+        FROM invitations
+        WHERE accepted_at IS NULL AND expires_at > unixepoch()
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve pending invitations.")?;
+    Ok(invitations)
+}
@@ -0,0 +1,249 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use tsid::create_tsid;
+
+use crate::{
+    authentication::{
+        invitation::{generate_invitation_token, hash_token, INVITATION_TTL},
+        Role, UserId,
+    },
+    domain::subscriber_email::SubscriberEmail,
+    email_client::EmailClient,
+    session::SqlxSqliteSessionStore,
+    startup::ApplicationBaseUrl,
+    utils::{e400, e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct InvitationFormData {
+    email: String,
+    role: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleFormData {
+    role: String,
+}
+
+#[tracing::instrument {
+    name = "Invite a user"
+    skip(form, pool, email_client, base_url)
+    fields(user_id=%&*user_id)
+}]
+pub async fn invite_user(
+    form: web::Form<InvitationFormData>,
+    pool: web::Data<SqlitePool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = match SubscriberEmail::parse(form.0.email.clone()) {
+        Ok(email) => email,
+        Err(_) => {
+            FlashMessage::error(format!("{} is not a valid email address.", form.email)).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    let role = Role::parse(&form.role).map_err(e400)?;
+    if user_exists(&pool, email.as_ref()).await.map_err(e500)? {
+        FlashMessage::error("There already is a user with this email address.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    // Both invitations could be accepted, the second one would clash with the first account.
+    if pending_invitation_exists(&pool, email.as_ref())
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("There already is a pending invitation for this email address.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Sqlite connection from the pool.")
+        .map_err(e500)?;
+    let invitation_token = generate_invitation_token();
+    insert_invitation(
+        &mut transaction,
+        email.as_ref(),
+        role,
+        &invitation_token,
+        &user_id,
+    )
+    .await
+    .context("Failed to store an invitation.")
+    .map_err(e500)?;
+    send_invitation_email(&email_client, &email, &base_url.0, &invitation_token)
+        .await
+        .context("Failed to send an invitation email.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store an invitation.")
+        .map_err(e500)?;
+
+    FlashMessage::error(format!(
+        "An invitation has been sent to {}.",
+        email.as_ref()
+    ))
+    .send();
+    Ok(see_other("/admin/users"))
+}
+
+async fn user_exists(pool: &SqlitePool, email: &str) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT user_id FROM users WHERE email = $1"#, email)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to look up a user by email.")?;
+    Ok(row.is_some())
+}
+
+async fn pending_invitation_exists(pool: &SqlitePool, email: &str) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT invitation_id FROM invitations
+        WHERE email = $1 AND accepted_at IS NULL AND expires_at > unixepoch()
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up pending invitations by email.")?;
+    Ok(row.is_some())
+}
+
+#[tracing::instrument(skip(transaction, invitation_token))]
+async fn insert_invitation(
+    transaction: &mut Transaction<'_, Sqlite>,
+    email: &str,
+    role: Role,
+    invitation_token: &str,
+    invited_by: &str,
+) -> Result<(), sqlx::Error> {
+    let invitation_id = create_tsid().to_string();
+    let token_hash = hash_token(invitation_token);
+    let role = role.as_str();
+    sqlx::query!(
+        r#"
+        INSERT INTO invitations (
+            invitation_id, email, token_hash, invited_by, created_at, expires_at, role
+        )
+        VALUES ($1, $2, $3, $4, unixepoch(), unixepoch() + $5, $6)
+        "#,
+        invitation_id,
+        email,
+        token_hash,
+        invited_by,
+        INVITATION_TTL,
+        role
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Send an invitation email",
+    skip(email_client, base_url, invitation_token)
+)]
+async fn send_invitation_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    base_url: &str,
+    invitation_token: &str,
+) -> Result<(), reqwest::Error> {
+    let invitation_link = format!(
+        "{}/invitations/accept?invitation_token={}",
+        base_url, invitation_token
+    );
+    let plain_body = format!(
+        "You have been invited to help run our newsletter!\nVisit {} to choose a username and a password.",
+        invitation_link
+    );
+    let html_body = format!(
+        "You have been invited to help run our newsletter!<br/>\
+                Click <a href=\"{}\">here</a> to choose a username and a password.",
+        invitation_link
+    );
+    email_client
+        .send_email(email, "Your invitation", &html_body, &plain_body)
+        .await
+}
+
+#[tracing::instrument {
+    name = "Deactivate a user"
+    skip(pool, session_store)
+    fields(user_id=%&*user_id)
+}]
+pub async fn deactivate_user(
+    target_user_id: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+    session_store: web::Data<SqlxSqliteSessionStore>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if *target_user_id == **user_id {
+        FlashMessage::error("You cannot deactivate your own account.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE users SET status = 'deactivated' WHERE user_id = $1 AND status = 'active'"#,
+        *target_user_id
+    )
+    .execute(&**pool)
+    .await
+    .context("Failed to deactivate a user.")
+    .map_err(e500)?;
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    // Whoever is logged in as them is logged out right away.
+    session_store
+        .delete_user_sessions(&target_user_id)
+        .await
+        .context("Failed to delete the sessions of a deactivated user.")
+        .map_err(e500)?;
+
+    FlashMessage::error("The user has been deactivated.").send();
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument {
+    name = "Change the role of a user"
+    skip(form, pool)
+    fields(user_id=%&*user_id)
+}]
+pub async fn change_role(
+    target_user_id: web::Path<String>,
+    form: web::Form<RoleFormData>,
+    pool: web::Data<SqlitePool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let role = Role::parse(&form.role).map_err(e400)?;
+    if *target_user_id == **user_id {
+        FlashMessage::error("You cannot change your own role.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let role = role.as_str();
+    let result = sqlx::query!(
+        r#"UPDATE users SET role = $1 WHERE user_id = $2 AND status = 'active'"#,
+        role,
+        *target_user_id
+    )
+    .execute(&**pool)
+    .await
+    .context("Failed to change the role of a user.")
+    .map_err(e500)?;
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    // Roles are loaded on every request: the change applies right away.
+    FlashMessage::error(format!("The user is now {role}.")).send();
+    Ok(see_other("/admin/users"))
+}
@@ -0,0 +1,27 @@
+//! Filters available to the page templates, e.g. `{{ issue.published_at|timestamp }}`.
+//! Modules deriving `Template` bring them in scope with `use crate::routes::site::filters;`.
+
+use std::borrow::Borrow;
+
+use crate::utils::format_timestamp;
+
+///
+/// Render a unix timestamp for humans, or nothing if there is none.
+pub fn timestamp<T>(timestamp: &T) -> askama::Result<String>
+where
+    T: Into<Option<i64>> + Copy,
+{
+    Ok((*timestamp)
+        .into()
+        .map(format_timestamp)
+        .unwrap_or_default())
+}
+
+///
+/// Render a rate as a percentage, or `-` when it cannot be computed.
+pub fn percentage(rate: impl Borrow<Option<f64>>) -> askama::Result<String> {
+    Ok(rate
+        .borrow()
+        .map(|r| format!("{:.1}%", 100.0 * r))
+        .unwrap_or_else(|| "-".into()))
+}
@@ -1,7 +1,12 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::HttpResponse;
+use askama::Template;
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+use crate::utils::html_page;
+
+#[derive(Template)]
+#[template(path = "home.html")]
+struct HomePage;
+
+pub async fn home() -> Result<HttpResponse, actix_web::Error> {
+    html_page(&HomePage)
 }
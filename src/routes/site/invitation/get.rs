@@ -0,0 +1,79 @@
+use actix_web::{http::StatusCode, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::{csrf::csrf_token, invitation::hash_token},
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page, html_page_with_status},
+};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    invitation_token: String,
+}
+
+pub struct Invitation {
+    pub invitation_id: String,
+    pub email: String,
+    pub role: String,
+}
+
+#[derive(Template)]
+#[template(path = "accept_invitation.html")]
+struct AcceptInvitationPage<'a> {
+    messages: Vec<String>,
+    csrf_token: String,
+    invitation_token: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "invalid_invitation.html")]
+struct InvalidInvitationPage;
+
+pub async fn accept_invitation_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let invitation = get_pending_invitation(&pool, &parameters.invitation_token)
+        .await
+        .map_err(e500)?;
+    if invitation.is_none() {
+        return invalid_invitation();
+    }
+    html_page(&AcceptInvitationPage {
+        messages: flash_contents(&flash_messages),
+        csrf_token: csrf_token(&session)?,
+        invitation_token: &parameters.invitation_token,
+    })
+}
+
+/// Unknown, expired and already used invitations all look the same.
+pub fn invalid_invitation() -> Result<HttpResponse, actix_web::Error> {
+    html_page_with_status(StatusCode::NOT_FOUND, &InvalidInvitationPage)
+}
+
+#[tracing::instrument(name = "Get a pending invitation", skip_all)]
+pub async fn get_pending_invitation(
+    pool: &SqlitePool,
+    invitation_token: &str,
+) -> Result<Option<Invitation>, anyhow::Error> {
+    let token_hash = hash_token(invitation_token);
+    let invitation = sqlx::query_as!(
+        Invitation,
+        r#"
+        SELECT invitation_id, email, role
+        FROM invitations
+        WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > unixepoch()
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve an invitation.")?;
+    Ok(invitation)
+}
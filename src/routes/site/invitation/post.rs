@@ -0,0 +1,148 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+use tsid::create_tsid;
+
+use crate::{
+    authentication::{compute_password_hash, validate_new_password},
+    telemetry::spawn_block_with_tracing,
+    utils::{e500, see_other},
+};
+
+use super::get::{get_pending_invitation, invalid_invitation, Invitation};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    invitation_token: String,
+    username: String,
+    password: Secret<String>,
+    password_check: Secret<String>,
+}
+
+impl FormData {
+    fn validate(&self) -> Result<(), String> {
+        if self.username.trim().is_empty() {
+            return Err("You must choose a username.".into());
+        }
+        if self.password.expose_secret() != self.password_check.expose_secret() {
+            return Err(
+                "You entered two different passwords - the field values must match.".into(),
+            );
+        }
+        validate_new_password(&self.password)
+    }
+}
+
+#[tracing::instrument {
+    name = "Accept an invitation"
+    skip(form, pool)
+    fields(username = %form.username)
+}]
+pub async fn accept_invitation(
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(invitation) = get_pending_invitation(&pool, &form.invitation_token)
+        .await
+        .map_err(e500)?
+    else {
+        return invalid_invitation();
+    };
+    let form_url = format!(
+        "/invitations/accept?invitation_token={}",
+        urlencoding::encode(&form.invitation_token)
+    );
+    if let Err(e) = form.validate() {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&form_url));
+    }
+    if username_is_taken(&pool, form.username.trim())
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("This username is already taken.").send();
+        return Ok(see_other(&form_url));
+    }
+
+    let FormData {
+        username, password, ..
+    } = form.0;
+    let password_hash = spawn_block_with_tracing(move || compute_password_hash(password))
+        .await
+        .map_err(e500)?
+        .context("Failed to hash password")
+        .map_err(e500)?;
+    let created = create_user(&pool, &invitation, username.trim(), password_hash)
+        .await
+        .map_err(e500)?;
+    if !created {
+        // Another request used the invitation in the meantime, or it just expired.
+        return invalid_invitation();
+    }
+
+    FlashMessage::error("Your account has been created, you can now log in.").send();
+    Ok(see_other("/login"))
+}
+
+async fn username_is_taken(pool: &SqlitePool, username: &str) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT user_id FROM users WHERE username = $1"#, username)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to look up a user by username.")?;
+    Ok(row.is_some())
+}
+
+///
+/// Returns whether the user was created: the invitation is claimed first,
+/// so concurrent requests cannot turn it into more than one account.
+#[tracing::instrument(name = "Create an invited user", skip(pool, invitation, password_hash))]
+async fn create_user(
+    pool: &SqlitePool,
+    invitation: &Invitation,
+    username: &str,
+    password_hash: Secret<String>,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Sqlite connection from the pool.")?;
+    // An invitation can only be used once.
+    let accepted = sqlx::query!(
+        r#"
+        UPDATE invitations
+        SET accepted_at = unixepoch()
+        WHERE invitation_id = $1 AND accepted_at IS NULL AND expires_at > unixepoch()
+        "#,
+        invitation.invitation_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to mark an invitation as accepted.")?;
+    if accepted.rows_affected() != 1 {
+        return Ok(false);
+    }
+
+    let user_id = create_tsid().to_string();
+    let password_hash = password_hash.expose_secret();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, email, status, role)
+        VALUES ($1, $2, $3, $4, 'active', $5)
+        "#,
+        user_id,
+        username,
+        password_hash,
+        invitation.email,
+        invitation.role
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to insert an invited user.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to create an invited user.")?;
+    Ok(true)
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    email_html,
+    routes::site::filters,
+    startup::ApplicationBaseUrl,
+    templating::{Escaping, MergeTemplate, ISSUE_VARIABLES},
+    utils::{e500, html_page},
+};
+
+/// How many issues are listed on each page of the archive.
+const ISSUES_PER_PAGE: i64 = 20;
+/// Stands in for the subscriber's name when an issue is read on the web.
+const ARCHIVE_READER_NAME: &str = "reader";
+
+#[derive(serde::Deserialize)]
+pub struct Pagination {
+    page: Option<u32>,
+}
+
+struct PublishedIssue {
+    newsletter_issue_id: String,
+    title: String,
+    published_at: Option<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "issues.html")]
+struct IssuesPage {
+    issues: Vec<PublishedIssue>,
+    page: u32,
+    has_older_issues: bool,
+}
+
+#[derive(Template)]
+#[template(path = "issue.html")]
+struct IssuePage {
+    title: String,
+    published_at: Option<i64>,
+    content_html: String,
+}
+
+pub async fn list_published_issues(
+    pagination: web::Query<Pagination>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = pagination.page.unwrap_or(1).max(1);
+    let mut issues = get_published_issues(&pool, page).await.map_err(e500)?;
+    // One issue more than a page's worth is fetched to know whether there is a next page.
+    let has_older_issues = issues.len() as i64 > ISSUES_PER_PAGE;
+    issues.truncate(ISSUES_PER_PAGE as usize);
+
+    html_page(&IssuesPage {
+        issues,
+        page,
+        has_older_issues,
+    })
+}
+
+pub async fn published_issue(
+    issue_id: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, html_content, published_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND status = 'published'
+        "#,
+        *issue_id
+    )
+    .fetch_optional(&**pool)
+    .await
+    .context("Failed to retrieve a published issue.")
+    .map_err(e500)?;
+    // Drafts, scheduled and cancelled issues do not exist as far as the public is concerned.
+    let Some(issue) = issue else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let content_html =
+        render_for_the_web(&base_url.0, &issue.newsletter_issue_id, &issue.html_content);
+    html_page(&IssuePage {
+        title: issue.title,
+        published_at: issue.published_at,
+        content_html,
+    })
+}
+
+///
+/// Fill in the merge variables of an issue's HTML for readers who are not subscribers,
+/// e.g. in the archive or in feed readers.
+/// The result is sanitised too: issues published before publishing sanitised HTML are stored as typed.
+pub fn render_for_the_web(base_url: &str, issue_id: &str, html_content: &str) -> String {
+    let values = HashMap::from([
+        ("name", ARCHIVE_READER_NAME.to_string()),
+        ("unsubscribe_url", format!("{}/", base_url)),
+        ("issue_url", format!("{}/issues/{}", base_url, issue_id)),
+        ("tracking_opt_out_url", format!("{}/", base_url)),
+    ]);
+    let html = match MergeTemplate::parse(html_content, &ISSUE_VARIABLES) {
+        Ok(content) => content.render(&values, Escaping::Html),
+        // Issues published before merge variables existed may contain stray braces.
+        Err(_) => html_content.to_string(),
+    };
+    email_html::sanitise(&html, base_url)
+}
+
+#[tracing::instrument(name = "Get published issues", skip(pool))]
+async fn get_published_issues(
+    pool: &SqlitePool,
+    page: u32,
+) -> Result<Vec<PublishedIssue>, anyhow::Error> {
+    let limit = ISSUES_PER_PAGE + 1;
+    let offset = (i64::from(page) - 1) * ISSUES_PER_PAGE;
+    let issues = sqlx::query_as!(
+        PublishedIssue,
+        r#"
+        SELECT newsletter_issue_id, title, published_at
+        FROM newsletter_issues
+        WHERE status = 'published'
+        ORDER BY published_at DESC, newsletter_issue_id DESC
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve published issues.")?;
+    Ok(issues)
+}
@@ -1,43 +1,27 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::HttpResponse;
 use actix_web_flash_messages::{IncomingFlashMessages, Level};
-use std::fmt::Write;
+use askama::Template;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
-    let mut error_html = String::new();
+use crate::{authentication::csrf::csrf_token, session_state::TypedSession, utils::html_page};
 
-    for m in flash_messages.iter().filter(|m| m.level() == Level::Error) {
-        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginPage {
+    messages: Vec<String>,
+    csrf_token: String,
+}
 
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-<meta http-equiv="content-type" content="text/html; charset=utf-8">
-<title>Login</title>
-</head>
-<body>
-{error_html}
-<form action="/login" method="post">
-    <label>Username
-        <input
-            type="text"
-            placeholder="Enter Username"
-            name="username"
-        >
-    </label>
-    <label>Password
-        <input
-            type="password"
-            placeholder="Enter Password"
-            name="password"
-        >
-    </label>
-    <button type="submit">Login</button>
-</form>
-</body>
-</html>"#,
-        ))
+pub async fn login_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let messages = flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error)
+        .map(|m| m.content().to_owned())
+        .collect();
+    html_page(&LoginPage {
+        messages,
+        csrf_token: csrf_token(&session)?,
+    })
 }
@@ -1,12 +1,17 @@
-use actix_web::{error::InternalError, http::header::LOCATION, web, HttpResponse};
+use actix_web::{error::InternalError, http::header::LOCATION, web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::SqlitePool;
 
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
+    authentication::{
+        throttling::{clear_failed_logins, login_retry_after, record_failed_login},
+        two_factor::two_factor_enabled,
+        validate_credentials, AuthError, Credentials,
+    },
     routes::subscriptions::error_chain_fmt,
     session_state::TypedSession,
+    utils::peer_ip_address,
 };
 
 #[derive(serde::Deserialize)]
@@ -17,13 +22,14 @@ pub struct FormData {
 
 #[tracing::instrument {
     name = "Logging in using POST endpoint",
-    skip(form, pool, session),
+    skip(form, pool, session, request),
     fields(username = tracing::field::Empty, user_id=tracing::field::Empty)
 }]
 pub async fn post(
     form: web::Form<FormData>,
     pool: web::Data<SqlitePool>,
     session: TypedSession,
+    request: HttpRequest,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credentials = Credentials {
         username: form.0.username,
@@ -32,18 +38,53 @@ pub async fn post(
 
     tracing::Span::current().record("username", tracing::field::display(&credentials.username));
 
+    let ip_address = peer_ip_address(&request);
+    let username = credentials.username.clone();
+    // Refuse early, so hammering the endpoint does not cost an argon2 hash each time.
+    if let Some(retry_after) = login_retry_after(&pool, &username, &ip_address)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        tracing::warn!(retry_after, "Login attempt throttled.");
+        return Err(login_redirect(LoginError::TooManyAttempts));
+    }
+
     match validate_credentials(credentials, &pool).await {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let two_factor = two_factor_enabled(&user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             session.renew();
+            if two_factor {
+                // The user is not logged in until the second factor is checked too.
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                // Failures are only forgotten once the second factor is checked too.
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, "/login/two-factor"))
+                    .finish());
+            }
+            clear_failed_logins(&pool, &username)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             session
                 .insert_user_id(user_id)
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            session
+                .insert_client_info(&request)
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
                 .finish())
         }
         Err(e) => {
+            if let AuthError::InvalidCredentials(_) = e {
+                record_failed_login(&pool, &username, &ip_address)
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            }
             let e = match e {
                 AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
@@ -71,6 +112,8 @@ fn login_redirect(e: LoginError) -> InternalError<LoginError> {
 pub enum LoginError {
     #[error("Authentication Failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, please try again later.")]
+    TooManyAttempts,
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
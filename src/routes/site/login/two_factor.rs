@@ -0,0 +1,106 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages, Level};
+use askama::Template;
+use secrecy::Secret;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::{
+        csrf::csrf_token,
+        throttling::{clear_failed_logins, login_retry_after, record_failed_login},
+        two_factor::verify_second_factor,
+    },
+    routes::{admin::dashboard::get_username, login::post::LoginError},
+    session_state::TypedSession,
+    utils::{e500, html_page, peer_ip_address, see_other},
+};
+
+/// Invalid codes tolerated before the password has to be entered again.
+const MAX_TWO_FACTOR_ATTEMPTS: u32 = 5;
+
+#[derive(Template)]
+#[template(path = "login_two_factor.html")]
+struct TwoFactorPage {
+    messages: Vec<String>,
+    csrf_token: String,
+}
+
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_user_id().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    let messages = flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error)
+        .map(|m| m.content().to_owned())
+        .collect();
+    html_page(&TwoFactorPage {
+        messages,
+        csrf_token: csrf_token(&session)?,
+    })
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: Secret<String>,
+}
+
+#[tracing::instrument {
+    name = "Verify the second factor of a login",
+    skip(form, pool, session, request),
+    fields(user_id=tracing::field::Empty)
+}]
+pub async fn verify_two_factor(
+    form: web::Form<FormData>,
+    pool: web::Data<SqlitePool>,
+    session: TypedSession,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(user_id) = session.get_pending_user_id().map_err(e500)? else {
+        return Ok(see_other("/login"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    // Codes are throttled like passwords: six digits do not take long to guess.
+    let username = get_username(&user_id, &pool).await.map_err(e500)?;
+    let ip_address = peer_ip_address(&request);
+    if let Some(retry_after) = login_retry_after(&pool, &username, &ip_address)
+        .await
+        .map_err(e500)?
+    {
+        tracing::warn!(retry_after, "Second factor attempt throttled.");
+        return Ok(restart_login(&session));
+    }
+
+    if !verify_second_factor(&user_id, form.0.code, &pool)
+        .await
+        .map_err(e500)?
+    {
+        record_failed_login(&pool, &username, &ip_address)
+            .await
+            .map_err(e500)?;
+        if session.record_two_factor_failure().map_err(e500)? >= MAX_TWO_FACTOR_ATTEMPTS {
+            return Ok(restart_login(&session));
+        }
+        FlashMessage::error("The code is invalid.").send();
+        return Ok(see_other("/login/two-factor"));
+    }
+
+    clear_failed_logins(&pool, &username).await.map_err(e500)?;
+    session.renew();
+    session.remove_pending_user_id();
+    session.insert_user_id(user_id).map_err(e500)?;
+    session.insert_client_info(&request).map_err(e500)?;
+    Ok(see_other("/admin/dashboard"))
+}
+
+///
+/// Forget the checked password, so the next attempt has to start over from it.
+fn restart_login(session: &TypedSession) -> HttpResponse {
+    session.remove_pending_user_id();
+    FlashMessage::error(LoginError::TooManyAttempts.to_string()).send();
+    see_other("/login")
+}
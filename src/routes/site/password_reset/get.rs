@@ -0,0 +1,68 @@
+use actix_web::{http::StatusCode, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::SqlitePool;
+
+use crate::{
+    authentication::{csrf::csrf_token, password_reset::reset_token_is_valid},
+    session_state::TypedSession,
+    utils::{e500, flash_contents, html_page, html_page_with_status},
+};
+
+#[derive(Template)]
+#[template(path = "forgot_password.html")]
+struct ForgotPasswordPage {
+    messages: Vec<String>,
+    csrf_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "reset_password.html")]
+struct ResetPasswordPage<'a> {
+    messages: Vec<String>,
+    csrf_token: String,
+    reset_token: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "invalid_reset_link.html")]
+struct InvalidResetLinkPage;
+
+pub async fn forgot_password_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    html_page(&ForgotPasswordPage {
+        messages: flash_contents(&flash_messages),
+        csrf_token: csrf_token(&session)?,
+    })
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    reset_token: String,
+}
+
+pub async fn reset_password_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<SqlitePool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !reset_token_is_valid(&pool, &parameters.reset_token)
+        .await
+        .map_err(e500)?
+    {
+        return invalid_reset_link();
+    }
+    html_page(&ResetPasswordPage {
+        messages: flash_contents(&flash_messages),
+        csrf_token: csrf_token(&session)?,
+        reset_token: &parameters.reset_token,
+    })
+}
+
+/// Unknown, expired and already used links all look the same.
+pub fn invalid_reset_link() -> Result<HttpResponse, actix_web::Error> {
+    html_page_with_status(StatusCode::NOT_FOUND, &InvalidResetLinkPage)
+}
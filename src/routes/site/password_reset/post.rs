@@ -0,0 +1,211 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+use tracing::Instrument;
+
+use crate::{
+    authentication::{
+        invitation::generate_invitation_token,
+        password_reset::{
+            allow_reset_request, reset_password as reset_user_password, store_reset_token,
+        },
+        validate_new_password,
+    },
+    domain::subscriber_email::SubscriberEmail,
+    email_client::EmailClient,
+    session::SqlxSqliteSessionStore,
+    startup::ApplicationBaseUrl,
+    utils::{e500, peer_ip_address, see_other},
+};
+
+use super::get::invalid_reset_link;
+
+#[derive(serde::Deserialize)]
+pub struct ForgotPasswordFormData {
+    username: String,
+}
+
+#[tracing::instrument {
+    name = "Request a password reset"
+    skip(request, form, pool, email_client, base_url)
+    fields(username = %form.username)
+}]
+pub async fn request_password_reset(
+    request: HttpRequest,
+    form: web::Form<ForgotPasswordFormData>,
+    pool: web::Data<SqlitePool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Looking the user up and emailing them is left out of the request,
+    // so the response takes as long whether or not the username exists.
+    tokio::spawn(
+        send_reset_link(
+            pool.get_ref().clone(),
+            email_client.clone(),
+            base_url.0.clone(),
+            form.0.username.trim().to_string(),
+            peer_ip_address(&request),
+        )
+        .in_current_span(),
+    );
+
+    // Whether or not the username exists, the response is the same.
+    FlashMessage::error(
+        "If this account has an email address, a link to reset its password has been sent to it.",
+    )
+    .send();
+    Ok(see_other("/login"))
+}
+
+///
+/// Email a reset link to `username`, if it is a user with an email address
+/// and not too many links were asked for recently.
+/// Nobody is waiting for the outcome: failures are logged, the user can ask again.
+async fn send_reset_link(
+    pool: SqlitePool,
+    email_client: web::Data<EmailClient>,
+    base_url: String,
+    username: String,
+    ip_address: String,
+) {
+    let result: Result<(), anyhow::Error> = async {
+        if !allow_reset_request(&pool, &username, &ip_address).await? {
+            tracing::warn!("Ignoring a password reset request: too many were made recently.");
+            return Ok(());
+        }
+        let Some((user_id, email)) = get_user_email(&pool, &username).await? else {
+            return Ok(());
+        };
+        let reset_token = generate_invitation_token();
+        store_reset_token(&pool, &user_id, &reset_token).await?;
+        send_reset_email(&email_client, &email, &base_url, &reset_token)
+            .await
+            .context("Failed to send a password reset email.")
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a password reset link."
+        );
+    }
+}
+
+#[tracing::instrument(name = "Get the email address of a user", skip(pool))]
+async fn get_user_email(
+    pool: &SqlitePool,
+    username: &str,
+) -> Result<Option<(String, SubscriberEmail)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, email
+        FROM users
+        WHERE username = $1 AND status = 'active'
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up a user by username.")?;
+    // The seeded admin has no email address: it cannot reset its password this way.
+    let Some((user_id, Some(email))) = row.map(|r| (r.user_id, r.email)) else {
+        return Ok(None);
+    };
+    match SubscriberEmail::parse(email) {
+        Ok(email) => Ok(Some((user_id, email))),
+        Err(e) => {
+            tracing::warn!(error = %e, "Skipping a user with an invalid email address.");
+            Ok(None)
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Send a password reset email",
+    skip(email_client, base_url, reset_token)
+)]
+async fn send_reset_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    base_url: &str,
+    reset_token: &str,
+) -> Result<(), reqwest::Error> {
+    let reset_link = format!(
+        "{}/login/reset-password?reset_token={}",
+        base_url, reset_token
+    );
+    let plain_body = format!(
+        "Somebody asked to reset your password.\nVisit {} within an hour to choose a new one.\nIf it was not you, you can ignore this email.",
+        reset_link
+    );
+    let html_body = format!(
+        "Somebody asked to reset your password.<br/>\
+                Click <a href=\"{}\">here</a> within an hour to choose a new one.<br/>\
+                If it was not you, you can ignore this email.",
+        reset_link
+    );
+    email_client
+        .send_email(email, "Reset your password", &html_body, &plain_body)
+        .await
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetPasswordFormData {
+    reset_token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+impl ResetPasswordFormData {
+    fn validate(&self) -> Result<(), String> {
+        if self.new_password.expose_secret() != self.new_password_check.expose_secret() {
+            return Err(
+                "You entered two different new passwords - the field values must match.".into(),
+            );
+        }
+        validate_new_password(&self.new_password)
+    }
+}
+
+#[tracing::instrument(
+    name = "Reset a password with an emailed link",
+    skip(form, pool, session_store)
+)]
+pub async fn reset_password(
+    form: web::Form<ResetPasswordFormData>,
+    pool: web::Data<SqlitePool>,
+    session_store: web::Data<SqlxSqliteSessionStore>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Err(e) = form.validate() {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&format!(
+            "/login/reset-password?reset_token={}",
+            urlencoding::encode(&form.reset_token)
+        )));
+    }
+
+    let ResetPasswordFormData {
+        reset_token,
+        new_password,
+        ..
+    } = form.0;
+    let Some(user_id) = reset_user_password(&pool, &reset_token, new_password)
+        .await
+        .map_err(e500)?
+    else {
+        return invalid_reset_link();
+    };
+    // Whoever got in with the old password is out.
+    session_store
+        .delete_user_sessions(&user_id)
+        .await
+        .context("Failed to delete the sessions of a user who reset their password.")
+        .map_err(e500)?;
+
+    FlashMessage::error("Your password has been reset, you can now log in.").send();
+    Ok(see_other("/login"))
+}
@@ -1,4 +1,7 @@
-use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use actix_web::{
+    http::{header::ACCEPT_LANGUAGE, StatusCode},
+    web, HttpRequest, HttpResponse, ResponseError,
+};
 use anyhow::Context;
 use chrono::Utc;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
@@ -7,11 +10,13 @@ use sqlx::{Sqlite, SqlitePool, Transaction};
 use tsid::create_tsid;
 
 use crate::{
+    confirmation_email::{confirmation_link, get_confirmation_email, ConfirmationEmail},
     domain::{
         new_subscriber::NewSubscriber, subscriber_email::SubscriberEmail,
         subscriber_name::SubscriberName,
     },
     email_client::EmailClient,
+    i18n::Locale,
     startup::ApplicationBaseUrl,
 };
 
@@ -19,6 +24,8 @@ use crate::{
 pub struct FormData {
     name: String,
     email: String,
+    /// The language the subscriber picked, if any: it takes precedence over their browser's.
+    locale: Option<String>,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -95,7 +102,7 @@ impl ResponseError for SubscribeError {
 
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(form, request, pool, email_client, base_url),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -103,10 +110,23 @@ impl ResponseError for SubscribeError {
 )]
 pub async fn subscribe(
     form: web::Form<FormData>,
+    request: HttpRequest,
     pool: web::Data<SqlitePool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
 ) -> Result<HttpResponse, SubscribeError> {
+    let locale = form
+        .locale
+        .as_deref()
+        .and_then(Locale::parse)
+        .unwrap_or_else(|| {
+            let accept_language = request
+                .headers()
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            Locale::negotiate(accept_language)
+        });
     let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
 
     let mut transaction = pool
@@ -114,7 +134,7 @@ pub async fn subscribe(
         .await
         .context("Failed to acquire a Sqlite connection from the pool.")?;
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber, locale)
         .await
         .context("Failed to insert new subscriber in the databas.e")?;
 
@@ -124,9 +144,11 @@ pub async fn subscribe(
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
 
+    let confirmation_email = get_confirmation_email(&pool, locale).await?;
     send_confirmation_email(
         &email_client,
         new_subscriber,
+        &confirmation_email,
         &base_url.0,
         &subscription_token,
     )
@@ -173,31 +195,28 @@ pub async fn store_token(
 
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url)
+    skip(email_client, new_subscriber, confirmation_email, base_url)
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
+    confirmation_email: &ConfirmationEmail,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
-    let confirmation_link = format!(
-        "{}/subscriptions/confirm?subscription_token={}",
-        base_url, subscription_token
-    );
-    let plain_body = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription",
-        confirmation_link
-    );
-    let html_body = format!(
-        "Welcome to our newsletter!<br/>\
-                Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
+) -> Result<(), anyhow::Error> {
+    let (html_body, plain_body) = confirmation_email
+        .render(&confirmation_link(base_url, subscription_token))
+        .context("The confirmation email has invalid merge variables.")?;
 
     email_client
-        .send_email(&new_subscriber.email, "Welcome", &html_body, &plain_body)
-        .await
+        .send_email(
+            &new_subscriber.email,
+            &confirmation_email.subject,
+            &html_body,
+            &plain_body,
+        )
+        .await?;
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -207,21 +226,24 @@ pub async fn send_confirmation_email(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Sqlite>,
     new_subscriber: &NewSubscriber,
+    locale: Locale,
 ) -> Result<String, sqlx::Error> {
     let subscriber_id = create_tsid();
     let subscriber_id_string = subscriber_id.to_string();
     let creation_time = Utc::now().to_string();
     let subscriber_name = new_subscriber.name.as_ref();
     let subscriber_email = new_subscriber.email.as_ref();
+    let locale = locale.code();
     sqlx::query!(
         r#"
-        INSERT INTO subscriptions(id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions(id, email, name, subscribed_at, status, locale)
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)
         "#,
         subscriber_id_string,
         subscriber_email,
         subscriber_name,
-        creation_time
+        creation_time,
+        locale
     )
     .execute(&mut **transaction)
     .await?;
@@ -1,7 +1,13 @@
-use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpResponse, ResponseError,
+};
 use anyhow::Context;
+use askama::Template;
 use sqlx::SqlitePool;
 
+use crate::i18n::{Locale, Message};
+
 use super::subscriptions::error_chain_fmt;
 
 #[derive(serde::Deserialize)]
@@ -32,6 +38,64 @@ impl ResponseError for ConfirmationError {
     }
 }
 
+/// What subscribers see after following a link from one of our emails, in their language.
+#[derive(Template)]
+#[template(path = "subscription_status.html")]
+struct SubscriptionStatusPage {
+    lang: &'static str,
+    title: &'static str,
+    message: &'static str,
+    /// The button confirming the action the subscriber was asked about, if any.
+    action: Option<&'static str>,
+    home: &'static str,
+    past_issues: &'static str,
+}
+
+pub async fn subscription_status_page(
+    pool: &SqlitePool,
+    subscriber_id: &str,
+    title: Message,
+    message: Message,
+) -> Result<HttpResponse, ConfirmationError> {
+    subscription_page(pool, subscriber_id, title, message, None).await
+}
+
+///
+/// Ask subscribers to confirm what the link they followed is for: the form posts back to the same URL.
+/// Email scanners and link previews fetch links, so a `GET` must not change anything.
+pub async fn subscription_action_page(
+    pool: &SqlitePool,
+    subscriber_id: &str,
+    message: Message,
+    action: Message,
+) -> Result<HttpResponse, ConfirmationError> {
+    subscription_page(pool, subscriber_id, action, message, Some(action)).await
+}
+
+async fn subscription_page(
+    pool: &SqlitePool,
+    subscriber_id: &str,
+    title: Message,
+    message: Message,
+    action: Option<Message>,
+) -> Result<HttpResponse, ConfirmationError> {
+    let locale = get_subscriber_locale(pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the locale of the subscriber")?;
+    let page = SubscriptionStatusPage {
+        lang: locale.code(),
+        title: locale.message(title),
+        message: locale.message(message),
+        action: action.map(|action| locale.message(action)),
+        home: locale.message(Message::Home),
+        past_issues: locale.message(Message::PastIssues),
+    };
+    let body = page.render().context("Failed to render the page")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
+
 #[tracing::instrument {
     name = "Confirm a pending subscriber"
     skip(parameters, pool)
@@ -45,20 +109,23 @@ pub async fn confirm(
         .context("Failed to retrieve subscriber id associated with the provided token")?
         .ok_or(ConfirmationError::UnknownToken)?;
 
-    confirm_subscriber(&pool, subscriber_id)
+    confirm_subscriber(&pool, &subscriber_id)
         .await
         .context("Failed to update the status to `confirmed`")?;
-    Ok(HttpResponse::Ok().finish())
+    subscription_status_page(
+        &pool,
+        &subscriber_id,
+        Message::SubscriptionConfirmedTitle,
+        Message::SubscriptionConfirmed,
+    )
+    .await
 }
 
 #[tracing::instrument {
     name =" Mark a subscriber as confirmed",
     skip(subscriber_id, pool)
 }]
-pub async fn confirm_subscriber(
-    pool: &SqlitePool,
-    subscriber_id: String,
-) -> Result<(), sqlx::Error> {
+pub async fn confirm_subscriber(pool: &SqlitePool, subscriber_id: &str) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
         subscriber_id,
@@ -85,3 +152,24 @@ pub async fn get_subscriber_id_from_token(
     .await?;
     Ok(result.map(|r| r.subscriber_id))
 }
+
+///
+/// Subscribers whose language is no longer supported get English.
+#[tracing::instrument {
+    name = "Get the locale of a subscriber",
+    skip(pool)
+}]
+pub async fn get_subscriber_locale(
+    pool: &SqlitePool,
+    subscriber_id: &str,
+) -> Result<Locale, sqlx::Error> {
+    let result = sqlx::query!(
+        "SELECT locale FROM subscriptions WHERE id = $1",
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result
+        .and_then(|r| Locale::parse(&r.locale))
+        .unwrap_or_default())
+}
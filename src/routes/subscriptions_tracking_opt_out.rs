@@ -0,0 +1,68 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::SqlitePool;
+
+use crate::i18n::Message;
+
+use super::subscriptions_confirm::{
+    get_subscriber_id_from_token, subscription_action_page, subscription_status_page,
+    ConfirmationError,
+};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    subscription_token: String,
+}
+
+#[tracing::instrument {
+    name = "Ask a subscriber to confirm opting out of tracking"
+    skip(parameters, pool)
+}]
+pub async fn opt_out_of_tracking_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, ConfirmationError> {
+    let subscriber_id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to retrieve subscriber id associated with the provided token")?
+        .ok_or(ConfirmationError::UnknownToken)?;
+
+    subscription_action_page(
+        &pool,
+        &subscriber_id,
+        Message::ConfirmDisableTracking,
+        Message::DisableTracking,
+    )
+    .await
+}
+
+///
+/// The parameters are read from the query string, as the confirmation form posts back to the link.
+#[tracing::instrument {
+    name = "Opt a subscriber out of tracking"
+    skip(parameters, pool)
+}]
+pub async fn opt_out_of_tracking(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, ConfirmationError> {
+    let subscriber_id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to retrieve subscriber id associated with the provided token")?
+        .ok_or(ConfirmationError::UnknownToken)?;
+
+    sqlx::query!(
+        r#"UPDATE subscriptions SET tracking_opt_out = TRUE WHERE id = $1"#,
+        subscriber_id,
+    )
+    .execute(&**pool)
+    .await
+    .context("Failed to opt the subscriber out of tracking")?;
+    subscription_status_page(
+        &pool,
+        &subscriber_id,
+        Message::TrackingDisabledTitle,
+        Message::TrackingDisabled,
+    )
+    .await
+}
@@ -0,0 +1,102 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::SqlitePool;
+
+use crate::{i18n::Message, issue_delivery_worker::record_delivery_event};
+
+use super::subscriptions_confirm::{
+    get_subscriber_id_from_token, subscription_action_page, subscription_status_page,
+    ConfirmationError,
+};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    subscription_token: String,
+    /// The issue whose link was followed, if any: unsubscribes are attributed to it.
+    newsletter_issue_id: Option<String>,
+}
+
+#[tracing::instrument {
+    name = "Ask a subscriber to confirm unsubscribing"
+    skip(parameters, pool)
+}]
+pub async fn unsubscribe_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, ConfirmationError> {
+    let subscriber_id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to retrieve subscriber id associated with the provided token")?
+        .ok_or(ConfirmationError::UnknownToken)?;
+
+    subscription_action_page(
+        &pool,
+        &subscriber_id,
+        Message::ConfirmUnsubscribe,
+        Message::Unsubscribe,
+    )
+    .await
+}
+
+///
+/// The parameters are read from the query string, as the confirmation form posts back to the link.
+#[tracing::instrument {
+    name = "Unsubscribe a subscriber"
+    skip(parameters, pool)
+}]
+pub async fn unsubscribe(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, ConfirmationError> {
+    let subscriber_id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to retrieve subscriber id associated with the provided token")?
+        .ok_or(ConfirmationError::UnknownToken)?;
+
+    let unsubscribed = unsubscribe_subscriber(&pool, &subscriber_id)
+        .await
+        .context("Failed to update the status to `unsubscribed`")?;
+    if let (true, Some(issue_id)) = (unsubscribed, &parameters.newsletter_issue_id) {
+        if let Err(e) =
+            record_delivery_event(&**pool, issue_id, &subscriber_id, "unsubscribed", None).await
+        {
+            // e.g. the link was tampered with and points at no issue
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to attribute an unsubscribe to an issue"
+            );
+        }
+    }
+    subscription_status_page(
+        &pool,
+        &subscriber_id,
+        Message::UnsubscribedTitle,
+        Message::Unsubscribed,
+    )
+    .await
+}
+
+///
+/// Returns whether the subscriber was still subscribed,
+/// so that confirming twice does not count twice.
+#[tracing::instrument {
+    name = "Mark a subscriber as unsubscribed",
+    skip(subscriber_id, pool)
+}]
+pub async fn unsubscribe_subscriber(
+    pool: &SqlitePool,
+    subscriber_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed', unsubscribed_at = unixepoch()
+        WHERE id = $1 AND status != 'unsubscribed'
+        "#,
+        subscriber_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
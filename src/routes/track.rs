@@ -0,0 +1,103 @@
+use actix_web::{
+    http::header::{self, CacheControl, CacheDirective},
+    web, HttpResponse,
+};
+use sqlx::SqlitePool;
+
+use crate::{
+    issue_delivery_worker::record_delivery_event, startup::HmacSecret, tracking::TrackedEvent,
+};
+
+/// The smallest transparent GIF there is.
+const TRANSPARENT_GIF: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+///
+/// Record that an email was opened and answer with an invisible image.
+/// The image is served whatever happens, a broken image in an email helps nobody.
+#[tracing::instrument(name = "Record an open", skip_all)]
+pub async fn open_pixel(
+    token: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> HttpResponse {
+    match TrackedEvent::from_token(&token, &hmac_secret.0) {
+        Ok(TrackedEvent::Open {
+            newsletter_issue_id,
+            subscriber_id,
+        }) => {
+            if let Err(e) = record_delivery_event(
+                &**pool,
+                &newsletter_issue_id,
+                &subscriber_id,
+                "opened",
+                None,
+            )
+            .await
+            {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to record an open"
+                );
+            }
+        }
+        Ok(_) => tracing::warn!("Ignoring an open with a token issued for another event"),
+        Err(e) => tracing::warn!(error.message = %e, "Ignoring an open with an invalid token"),
+    }
+
+    HttpResponse::Ok()
+        // Every open has to reach us, not a cache.
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .content_type("image/gif")
+        .body(TRANSPARENT_GIF.to_vec())
+}
+
+///
+/// Record that a link was followed and send the reader on to its destination.
+/// Only signed links lead anywhere: the endpoint cannot be used as an open redirect.
+#[tracing::instrument(name = "Record a click", skip_all)]
+pub async fn click_redirect(
+    token: web::Path<String>,
+    pool: web::Data<SqlitePool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> HttpResponse {
+    let Ok(TrackedEvent::Click {
+        newsletter_issue_id,
+        subscriber_id,
+        url,
+    }) = TrackedEvent::from_token(&token, &hmac_secret.0)
+    else {
+        tracing::warn!("Ignoring a click with an invalid token");
+        return HttpResponse::NotFound().finish();
+    };
+    // Tracked links are web links, anything else was never issued by us.
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    if let Err(e) = record_delivery_event(
+        &**pool,
+        &newsletter_issue_id,
+        &subscriber_id,
+        "clicked",
+        Some(&url),
+    )
+    .await
+    {
+        // Losing a click is better than losing the reader.
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to record a click"
+        );
+    }
+
+    HttpResponse::Found()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .insert_header((header::LOCATION, url))
+        .finish()
+}
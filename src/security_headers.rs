@@ -0,0 +1,162 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::header::{
+        HeaderMap, HeaderName, HeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY,
+        STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    },
+    middleware::Next,
+    web,
+};
+
+use crate::configuration::SecurityHeadersSettings;
+
+/// Pages anyone can see, which embed content from elsewhere: archived issues come with
+/// remote images and inline styles. Tracking links and feeds are public too.
+const PUBLIC_PATHS: [&str; 7] = [
+    "/issues",
+    "/feed.xml",
+    "/atom.xml",
+    "/t/",
+    "/r/",
+    "/subscriptions",
+    "/health_check",
+];
+
+/// The headers added to every response, by kind of page.
+#[derive(Clone)]
+pub struct SecurityHeaders {
+    strict: Vec<(HeaderName, HeaderValue)>,
+    public: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl SecurityHeaders {
+    ///
+    /// HSTS is only sent when the site is served over HTTPS: browsers would
+    /// otherwise be told to never reach a plain HTTP site again.
+    pub fn new(settings: &SecurityHeadersSettings, base_url: &str) -> Result<Self, anyhow::Error> {
+        let mut common = vec![(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"))];
+        if base_url.starts_with("https://") && settings.hsts_max_age_seconds > 0 {
+            common.push((
+                STRICT_TRANSPORT_SECURITY,
+                HeaderValue::from_str(&format!("max-age={}", settings.hsts_max_age_seconds))?,
+            ));
+        }
+
+        let mut strict = common.clone();
+        strict.extend([
+            (
+                CONTENT_SECURITY_POLICY,
+                HeaderValue::from_str(&settings.admin_content_security_policy)?,
+            ),
+            (X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+            // Same origin only: re-authentication sends users back to the page they came from.
+            (REFERRER_POLICY, HeaderValue::from_static("same-origin")),
+        ]);
+        let mut public = common;
+        public.extend([
+            (
+                CONTENT_SECURITY_POLICY,
+                HeaderValue::from_str(&settings.public_content_security_policy)?,
+            ),
+            (X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN")),
+            // Links out of an issue, and tracking redirects, only give away the origin.
+            (
+                REFERRER_POLICY,
+                HeaderValue::from_static("strict-origin-when-cross-origin"),
+            ),
+        ]);
+        Ok(Self { strict, public })
+    }
+
+    fn apply(&self, path: &str, headers: &mut HeaderMap) {
+        let headers_to_add = if is_public(path) {
+            &self.public
+        } else {
+            &self.strict
+        };
+        for (name, value) in headers_to_add {
+            // Routes that know better keep their own.
+            if !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+fn is_public(path: &str) -> bool {
+    path == "/" || PUBLIC_PATHS.iter().any(|prefix| path.starts_with(prefix))
+}
+
+///
+/// Add the security headers to every response, errors included.
+pub async fn add_security_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let security_headers = req
+        .app_data::<web::Data<SecurityHeaders>>()
+        .expect("The security headers are registered as application data.")
+        .clone();
+    let path = req.path().to_owned();
+
+    match next.call(req).await {
+        Ok(mut response) => {
+            security_headers.apply(&path, response.headers_mut());
+            Ok(response)
+        }
+        // Guards reject requests with errors: render them here, to add the headers.
+        Err(e) => {
+            let mut response = e.error_response();
+            security_headers.apply(&path, response.headers_mut());
+            Err(InternalError::from_response(e, response).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderMap, STRICT_TRANSPORT_SECURITY};
+
+    use crate::{configuration::SecurityHeadersSettings, security_headers::SecurityHeaders};
+
+    fn settings(hsts_max_age_seconds: u64) -> SecurityHeadersSettings {
+        SecurityHeadersSettings {
+            admin_content_security_policy: "default-src 'none'".into(),
+            public_content_security_policy: "default-src 'self'".into(),
+            hsts_max_age_seconds,
+        }
+    }
+
+    fn hsts(settings: &SecurityHeadersSettings, base_url: &str, path: &str) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        SecurityHeaders::new(settings, base_url)
+            .unwrap()
+            .apply(path, &mut headers);
+        headers
+            .get(STRICT_TRANSPORT_SECURITY)
+            .map(|h| h.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn hsts_is_sent_on_every_page_of_an_https_site() {
+        let settings = settings(600);
+        for path in ["/admin/dashboard", "/login", "/", "/issues/0H5N0000000A1"] {
+            assert_eq!(
+                hsts(&settings, "https://example.com", path).as_deref(),
+                Some("max-age=600")
+            );
+        }
+    }
+
+    #[test]
+    fn hsts_is_not_sent_over_plain_http() {
+        assert_eq!(hsts(&settings(600), "http://127.0.0.1", "/login"), None);
+    }
+
+    #[test]
+    fn hsts_can_be_disabled() {
+        assert_eq!(hsts(&settings(0), "https://example.com", "/login"), None);
+    }
+}
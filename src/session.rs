@@ -1,25 +1,40 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
 
 use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
 use chrono::Utc;
+use lru::LruCache;
 use rand::distributions::{Alphanumeric, DistString};
 use sqlx::SqlitePool;
 
+use crate::session_state::TypedSession;
+
 #[derive(Clone)]
 struct CacheConfiguration {
     cache_keygen: Arc<dyn Fn(&str) -> String + Send + Sync>,
+    /// Sessions kept in memory in front of the database. `None` disables the cache.
+    capacity: Option<NonZeroUsize>,
+    /// Expiry extensions shorter than this, in seconds, are not written to the database.
+    expiry_write_threshold: i64,
 }
 
 #[derive(Clone)]
 pub struct SqlxSqliteSessionStore {
     configuration: CacheConfiguration,
     pool: SqlitePool,
+    /// Shared by every clone of the store, i.e. by every worker.
+    cache: Option<Arc<Mutex<SessionCache>>>,
 }
 
 impl Default for CacheConfiguration {
     fn default() -> Self {
         Self {
             cache_keygen: Arc::new(str::to_owned),
+            capacity: None,
+            expiry_write_threshold: 0,
         }
     }
 }
@@ -37,9 +52,14 @@ pub struct SqlxSqliteSessionStoreBuilder {
 
 impl SqlxSqliteSessionStoreBuilder {
     pub fn build(self) -> SqlxSqliteSessionStore {
+        let cache = self
+            .configuration
+            .capacity
+            .map(|capacity| Arc::new(Mutex::new(SessionCache(LruCache::new(capacity)))));
         SqlxSqliteSessionStore {
             pool: self.pool,
             configuration: self.configuration,
+            cache,
         }
     }
 
@@ -51,6 +71,24 @@ impl SqlxSqliteSessionStoreBuilder {
         self.configuration.cache_keygen = Arc::new(keygen);
         self
     }
+
+    /// Keep up to `capacity` sessions in memory, evicting the least recently used first.
+    /// A capacity of 0 disables the cache.
+    ///
+    /// The cache assumes sessions are only changed through this store, in this process.
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.configuration.capacity = NonZeroUsize::new(capacity);
+        self
+    }
+
+    /// Only write a session's new expiry when it moves by at least `seconds`.
+    /// The stored expiry can then lag behind the real one by up to that long.
+    ///
+    /// It needs the cache, which knows the stored expiries: without it, every expiry is written.
+    pub fn expiry_write_threshold(mut self, seconds: i64) -> Self {
+        self.configuration.expiry_write_threshold = seconds;
+        self
+    }
 }
 
 impl SqlxSqliteSessionStore {
@@ -73,14 +111,197 @@ impl SqlxSqliteSessionStore {
         Self::builder_pooled(pool).build()
     }
 
-    pub async fn cleanup(&self) -> Result<(), anyhow::Error> {
-        sqlx::query!(r#"DELETE FROM sessions WHERE expires > unixepoch()"#)
+    /// Log a user out everywhere, e.g. when their account is deactivated.
+    /// Logins waiting for their second factor are dropped too.
+    pub async fn delete_user_sessions(&self, user_id: &str) -> Result<(), anyhow::Error> {
+        // Session values are stored JSON-encoded, hence the quoting.
+        let path = format!("$.{}", TypedSession::USER_ID_KEY);
+        let pending_path = format!("$.{}", TypedSession::PENDING_USER_ID_KEY);
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE json_extract(session, $1) = json_quote($3)
+                OR json_extract(session, $2) = json_quote($3)
+            "#,
+            path,
+            pending_path,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        self.evict_from_cache(|state| {
+            state_value(state, TypedSession::USER_ID_KEY).as_deref() == Some(user_id)
+                || state_value(state, TypedSession::PENDING_USER_ID_KEY).as_deref() == Some(user_id)
+        });
+        Ok(())
+    }
+
+    /// The sessions a user is logged in with, most recently used first.
+    pub async fn list_user_sessions(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<SessionInfo>, anyhow::Error> {
+        let sessions = sqlx::query_as!(
+            SessionInfo,
+            r#"
+            SELECT handle, ip_address, user_agent, created_at, last_seen_at
+            FROM sessions
+            WHERE user_id = $1 AND expires > unixepoch()
+            ORDER BY last_seen_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(sessions)
+    }
+
+    /// Log a user out of one of their sessions. Returns whether there was such a session.
+    pub async fn delete_user_session(
+        &self,
+        user_id: &str,
+        handle: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query!(
+            r#"DELETE FROM sessions WHERE user_id = $1 AND handle = $2"#,
+            user_id,
+            handle
+        )
+        .execute(&self.pool)
+        .await?;
+        self.evict_from_cache(|state| {
+            state_value(state, TypedSession::USER_ID_KEY).as_deref() == Some(user_id)
+                && state_value(state, TypedSession::SESSION_HANDLE_KEY).as_deref() == Some(handle)
+        });
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Log everyone out, e.g. after a suspected breach.
+    pub async fn delete_all_sessions(&self) -> Result<(), anyhow::Error> {
+        sqlx::query!(r#"DELETE FROM sessions"#)
             .execute(&self.pool)
-            .await
-            .map_err(Into::into)
-            .map_err(UpdateError::Other)?;
+            .await?;
+        self.with_cache(|cache| cache.clear());
+        Ok(())
+    }
+
+    /// Record that a session is in use. Only written once a minute at most.
+    pub async fn touch(&self, handle: &str) -> Result<(), anyhow::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET last_seen_at = unixepoch()
+            WHERE handle = $1 AND (last_seen_at IS NULL OR last_seen_at < unixepoch() - 60)
+            "#,
+            handle
+        )
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
+
+    /// Delete up to `batch_size` expired sessions, returning how many were deleted.
+    pub async fn cleanup(&self, batch_size: u32) -> Result<u64, anyhow::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE id IN (SELECT id FROM sessions WHERE expires <= unixepoch() LIMIT $1)
+            "#,
+            batch_size
+        )
+        .execute(&self.pool)
+        .await?;
+        let now = Utc::now().timestamp();
+        self.with_cache(|cache| cache.retain(|session| session.stored_expires > now));
+        Ok(result.rows_affected())
+    }
+
+    fn with_cache<T>(&self, f: impl FnOnce(&mut SessionCache) -> T) -> Option<T> {
+        self.cache.as_ref().map(|cache| {
+            let mut cache = cache.lock().expect("The session cache lock is poisoned.");
+            f(&mut cache)
+        })
+    }
+
+    /// Drop the cached sessions whose state matches, after deleting them from the database.
+    fn evict_from_cache(&self, matches: impl Fn(&SessionState) -> bool) {
+        self.with_cache(|cache| cache.retain(|session| !matches(&session.state)));
+    }
+}
+
+/// A least recently used cache of sessions, by cache key.
+struct SessionCache(LruCache<String, CachedSession>);
+
+/// A session, with the expiry it is stored with in the database.
+struct CachedSession {
+    state: SessionState,
+    expires: i64,
+    /// Behind `expires` when an extension was too small to be written.
+    stored_expires: i64,
+}
+
+impl SessionCache {
+    /// The state of a live session. Expired sessions are evicted on the way.
+    fn get(&mut self, key: &str, now: i64) -> Option<SessionState> {
+        match self.0.get(key) {
+            Some(session) if session.expires > now => Some(session.state.clone()),
+            Some(_) => {
+                self.0.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache a session as it was just read from or written to the database.
+    fn put(&mut self, key: String, state: SessionState, expires: i64) {
+        self.0.put(
+            key,
+            CachedSession {
+                state,
+                expires,
+                stored_expires: expires,
+            },
+        );
+    }
+
+    fn stored_expires(&self, key: &str) -> Option<i64> {
+        self.0.peek(key).map(|session| session.stored_expires)
+    }
+
+    fn set_expires(&mut self, key: &str, expires: i64, stored: bool) {
+        if let Some(session) = self.0.peek_mut(key) {
+            session.expires = expires;
+            if stored {
+                session.stored_expires = expires;
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.0.pop(key);
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn retain(&mut self, keep: impl Fn(&CachedSession) -> bool) {
+        let evicted: Vec<String> = self
+            .0
+            .iter()
+            .filter(|(_, session)| !keep(session))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in evicted {
+            self.0.pop(&key);
+        }
+    }
+}
+
+/// Expiries are unix timestamps, like every other point in time in the database.
+fn expires_at(ttl: &actix_web::cookie::time::Duration) -> i64 {
+    (Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())).timestamp()
 }
 
 pub fn generate_session_key() -> SessionKey {
@@ -92,15 +313,56 @@ pub fn generate_session_key() -> SessionKey {
 
 pub(crate) type SessionState = HashMap<String, String>;
 
+pub struct SessionInfo {
+    pub handle: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: Option<i64>,
+    pub last_seen_at: Option<i64>,
+}
+
+/// The values of the session state that are also stored in their own columns.
+struct SessionMetadata {
+    user_id: Option<String>,
+    handle: Option<String>,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl SessionMetadata {
+    fn from_state(state: &SessionState) -> Self {
+        Self {
+            user_id: state_value(state, TypedSession::USER_ID_KEY),
+            handle: state_value(state, TypedSession::SESSION_HANDLE_KEY),
+            ip_address: state_value(state, TypedSession::IP_ADDRESS_KEY),
+            user_agent: state_value(state, TypedSession::USER_AGENT_KEY),
+        }
+    }
+}
+
+/// A string value of the session state. State values are JSON-encoded.
+fn state_value(state: &SessionState, key: &str) -> Option<String> {
+    state
+        .get(key)
+        .and_then(|value| serde_json::from_str::<String>(value).ok())
+}
+
 impl SessionStore for SqlxSqliteSessionStore {
     async fn load(
         &self,
         session_key: &actix_session::storage::SessionKey,
     ) -> Result<Option<SessionState>, actix_session::storage::LoadError> {
         let cache_key = (self.configuration.cache_keygen)(session_key.as_ref());
+        let now = Utc::now().timestamp();
+        if let Some(state) = self
+            .with_cache(|cache| cache.get(&cache_key, now))
+            .flatten()
+        {
+            return Ok(Some(state));
+        }
 
         let row = sqlx::query!(
-            "SELECT session FROM sessions where id = $1 AND expires > unixepoch()",
+            "SELECT session, expires FROM sessions where id = $1 AND expires > unixepoch()",
             cache_key
         )
         .fetch_optional(&self.pool)
@@ -114,6 +376,7 @@ impl SessionStore for SqlxSqliteSessionStore {
                 let state: SessionState = serde_json::from_str(&r.session)
                     .map_err(Into::into)
                     .map_err(LoadError::Deserialization)?;
+                self.with_cache(|cache| cache.put(cache_key, state.clone(), r.expires));
                 Ok(Some(state))
             }
         }
@@ -128,14 +391,32 @@ impl SessionStore for SqlxSqliteSessionStore {
             .map_err(Into::into)
             .map_err(SaveError::Serialization)?;
         let key = generate_session_key();
-        let expires = Utc::now() + chrono::Duration::seconds(ttl.whole_seconds());
+        let expires = expires_at(ttl);
         let cache_key = (self.configuration.cache_keygen)(key.as_ref());
+        let metadata = SessionMetadata::from_state(&session_state);
 
-        sqlx::query!(r#"INSERT INTO sessions(id, session, expires) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING"#, cache_key, body, expires)
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions(
+                id, session, expires, user_id, handle, ip_address, user_agent,
+                created_at, last_seen_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, unixepoch(), unixepoch())
+            ON CONFLICT DO NOTHING
+            "#,
+            cache_key,
+            body,
+            expires,
+            metadata.user_id,
+            metadata.handle,
+            metadata.ip_address,
+            metadata.user_agent
+        )
         .execute(&self.pool)
         .await
         .map_err(Into::into)
         .map_err(SaveError::Other)?;
+        self.with_cache(|cache| cache.put(cache_key, session_state, expires));
         Ok(key)
     }
 
@@ -149,18 +430,36 @@ impl SessionStore for SqlxSqliteSessionStore {
             .map_err(Into::into)
             .map_err(UpdateError::Serialization)?;
         let cache_key = (self.configuration.cache_keygen)(session_key.as_ref());
-        let new_expires = Utc::now() + chrono::Duration::seconds(ttl.whole_seconds());
+        let new_expires = expires_at(ttl);
+        let metadata = SessionMetadata::from_state(&session_state);
 
-        sqlx::query!(
-            r#"UPDATE sessions SET session = $1, expires = $2 WHERE id = $3"#,
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET session = $1, expires = $2, user_id = $3, handle = $4, ip_address = $5,
+                user_agent = $6, last_seen_at = unixepoch()
+            WHERE id = $7
+            "#,
             body,
             new_expires,
+            metadata.user_id,
+            metadata.handle,
+            metadata.ip_address,
+            metadata.user_agent,
             cache_key
         )
         .execute(&self.pool)
         .await
         .map_err(Into::into)
         .map_err(UpdateError::Other)?;
+        // The session was deleted in the meantime, e.g. by logging out everywhere.
+        if result.rows_affected() != 1 {
+            self.with_cache(|cache| cache.remove(&cache_key));
+            return Err(UpdateError::Other(anyhow::anyhow!(
+                "There is no session to update."
+            )));
+        }
+        self.with_cache(|cache| cache.put(cache_key, session_state, new_expires));
 
         Ok(session_key)
     }
@@ -170,9 +469,19 @@ impl SessionStore for SqlxSqliteSessionStore {
         session_key: &actix_session::storage::SessionKey,
         ttl: &actix_web::cookie::time::Duration,
     ) -> Result<(), anyhow::Error> {
-        let new_expires = Utc::now() + chrono::Duration::seconds(ttl.whole_seconds());
+        let new_expires = expires_at(ttl);
         let key = (self.configuration.cache_keygen)(session_key.as_ref());
-        sqlx::query!(
+        let threshold = self.configuration.expiry_write_threshold;
+        // The cache knows the stored expiry: only the write is skipped if it is close enough,
+        // the session still lives as long as it was asked to.
+        let stored_expires = self
+            .with_cache(|cache| cache.stored_expires(&key))
+            .flatten();
+        if stored_expires.is_some_and(|expires| (new_expires - expires).abs() < threshold) {
+            self.with_cache(|cache| cache.set_expires(&key, new_expires, false));
+            return Ok(());
+        }
+        let result = sqlx::query!(
             r#"UPDATE sessions SET expires = $1 WHERE id = $2"#,
             new_expires,
             key
@@ -181,6 +490,11 @@ impl SessionStore for SqlxSqliteSessionStore {
         .await
         .map_err(Into::into)
         .map_err(UpdateError::Other)?;
+        if result.rows_affected() > 0 {
+            self.with_cache(|cache| cache.set_expires(&key, new_expires, true));
+        } else {
+            self.with_cache(|cache| cache.remove(&key));
+        }
 
         Ok(())
     }
@@ -195,6 +509,7 @@ impl SessionStore for SqlxSqliteSessionStore {
             .await
             .map_err(Into::into)
             .map_err(UpdateError::Other)?;
+        self.with_cache(|cache| cache.remove(&key));
         Ok(())
     }
 }
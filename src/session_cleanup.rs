@@ -0,0 +1,71 @@
+use std::{future::Future, num::NonZeroU32};
+
+use actix_web::rt::time;
+
+use crate::{
+    configuration::{SessionCleanupSettings, Settings},
+    session::SqlxSqliteSessionStore,
+    utils::get_connection_pool,
+};
+
+///
+/// Delete every expired session, one batch at a time.
+/// Returns how many sessions were deleted.
+#[tracing::instrument(name = "Delete expired sessions", skip(session_store))]
+pub async fn delete_expired_sessions(
+    session_store: &SqlxSqliteSessionStore,
+    batch_size: NonZeroU32,
+) -> Result<u64, anyhow::Error> {
+    let mut deleted = 0;
+    loop {
+        let n_deleted = session_store.cleanup(batch_size.get()).await?;
+        deleted += n_deleted;
+        if n_deleted < u64::from(batch_size.get()) {
+            break;
+        }
+    }
+    Ok(deleted)
+}
+
+///
+/// Delete expired sessions every `settings.interval_seconds`, until `shutdown` completes.
+pub async fn run_session_cleanup(
+    session_store: SqlxSqliteSessionStore,
+    settings: SessionCleanupSettings,
+    shutdown: impl Future<Output = ()>,
+) {
+    let mut interval = time::interval(settings.interval());
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => {
+                tracing::info!("Session cleanup stopped.");
+                return;
+            }
+            _ = interval.tick() => {
+                match delete_expired_sessions(&session_store, settings.batch_size).await {
+                    Ok(deleted) => tracing::info!(deleted, "Deleted expired sessions."),
+                    // Try again at the next tick.
+                    Err(e) => tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to delete expired sessions."
+                    ),
+                }
+            }
+        }
+    }
+}
+
+///
+/// Run the cleanup against the configured database until `shutdown` completes,
+/// e.g. once the HTTP server has stopped.
+pub async fn run_session_cleanup_until_stopped(
+    configuration: Settings,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database, None).await;
+    let session_store = SqlxSqliteSessionStore::new_pooled(connection_pool);
+    run_session_cleanup(session_store, configuration.session_cleanup, shutdown).await;
+    Ok(())
+}
@@ -1,25 +1,170 @@
 use std::future::{ready, Ready};
 
 use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
-use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use actix_web::{dev::Payload, http::header::USER_AGENT, FromRequest, HttpRequest};
+use chrono::Utc;
+use rand::distributions::{Alphanumeric, DistString};
+use tsid::create_tsid;
+
+use crate::{configuration::SessionSettings, utils::peer_ip_address};
 
 pub struct TypedSession(Session);
 
 impl TypedSession {
-    const USER_ID_KEY: &'static str = "user_id";
+    pub const USER_ID_KEY: &'static str = "user_id";
+    /// Set once the password is checked, while the second factor is still to be checked.
+    pub const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    /// Invalid codes entered since the password was checked.
+    const TWO_FACTOR_FAILURES_KEY: &'static str = "two_factor_failures";
+    /// Identifies the session on `/admin/sessions`, along with where it was opened from.
+    pub const SESSION_HANDLE_KEY: &'static str = "session_handle";
+    pub const IP_ADDRESS_KEY: &'static str = "ip_address";
+    pub const USER_AGENT_KEY: &'static str = "user_agent";
+    /// The TOTP secret being enrolled, until the user confirms it with a code.
+    const TOTP_ENROLMENT_SECRET_KEY: &'static str = "totp_enrolment_secret";
+    /// Unix timestamps: when the user logged in, last did something,
+    /// and last entered their password.
+    pub const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    pub const LAST_ACTIVITY_AT_KEY: &'static str = "last_activity_at";
+    pub const AUTHENTICATED_AT_KEY: &'static str = "authenticated_at";
+    /// The synchronizer token every form of the session must send back.
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
+    /// Activity is only written back this often, so browsing does not rewrite the session every time.
+    const ACTIVITY_RESOLUTION: i64 = 60;
 
     pub fn renew(&self) {
         self.0.renew()
     }
 
     pub fn insert_user_id(&self, user_id: String) -> Result<(), SessionInsertError> {
-        self.0.insert(Self::USER_ID_KEY, user_id)
+        let now = Utc::now().timestamp();
+        // A token handed out before logging in could have been planted by someone else.
+        self.0.remove(Self::CSRF_TOKEN_KEY);
+        self.0.insert(Self::USER_ID_KEY, user_id)?;
+        self.0.insert(Self::LOGGED_IN_AT_KEY, now)?;
+        self.0.insert(Self::LAST_ACTIVITY_AT_KEY, now)?;
+        self.0.insert(Self::AUTHENTICATED_AT_KEY, now)
     }
 
     pub fn get_user_id(&self) -> Result<Option<String>, SessionGetError> {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    ///
+    /// Remember where the user logged in from, under a new handle.
+    pub fn insert_client_info(&self, request: &HttpRequest) -> Result<(), SessionInsertError> {
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default();
+        self.0
+            .insert(Self::SESSION_HANDLE_KEY, create_tsid().to_string())?;
+        self.0
+            .insert(Self::IP_ADDRESS_KEY, peer_ip_address(request))?;
+        self.0.insert(Self::USER_AGENT_KEY, user_agent)
+    }
+
+    ///
+    /// Whether the session went idle for too long or outlived its absolute lifetime.
+    /// Sessions without timestamps predate them, and have expired too.
+    pub fn has_expired(
+        &self,
+        settings: &SessionSettings,
+        now: i64,
+    ) -> Result<bool, SessionGetError> {
+        let logged_in_at: Option<i64> = self.0.get(Self::LOGGED_IN_AT_KEY)?;
+        let last_activity_at: Option<i64> = self.0.get(Self::LAST_ACTIVITY_AT_KEY)?;
+        Ok(match (logged_in_at, last_activity_at) {
+            (Some(logged_in_at), Some(last_activity_at)) => {
+                now - logged_in_at >= settings.absolute_lifetime_seconds
+                    || now - last_activity_at >= settings.idle_timeout_seconds
+            }
+            _ => true,
+        })
+    }
+
+    pub fn record_activity(&self, now: i64) -> Result<(), anyhow::Error> {
+        let last_activity_at: Option<i64> = self.0.get(Self::LAST_ACTIVITY_AT_KEY)?;
+        if last_activity_at.is_none_or(|t| now - t >= Self::ACTIVITY_RESOLUTION) {
+            self.0.insert(Self::LAST_ACTIVITY_AT_KEY, now)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Whether the password has to be entered again before a sensitive action.
+    pub fn needs_reauthentication(
+        &self,
+        settings: &SessionSettings,
+        now: i64,
+    ) -> Result<bool, SessionGetError> {
+        let authenticated_at: Option<i64> = self.0.get(Self::AUTHENTICATED_AT_KEY)?;
+        Ok(authenticated_at.is_none_or(|t| now - t >= settings.reauthentication_window_seconds))
+    }
+
+    pub fn insert_authenticated_at(&self, now: i64) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::AUTHENTICATED_AT_KEY, now)
+    }
+
+    pub fn get_session_handle(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::SESSION_HANDLE_KEY)
+    }
+
+    pub fn insert_pending_user_id(&self, user_id: String) -> Result<(), SessionInsertError> {
+        self.0.remove(Self::TWO_FACTOR_FAILURES_KEY);
+        self.0.remove(Self::CSRF_TOKEN_KEY);
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.0.remove(Self::TWO_FACTOR_FAILURES_KEY);
+    }
+
+    ///
+    /// Count an invalid second factor, returning how many were entered since the password.
+    pub fn record_two_factor_failure(&self) -> Result<u32, anyhow::Error> {
+        let failures = self
+            .0
+            .get::<u32>(Self::TWO_FACTOR_FAILURES_KEY)?
+            .unwrap_or(0)
+            + 1;
+        self.0.insert(Self::TWO_FACTOR_FAILURES_KEY, failures)?;
+        Ok(failures)
+    }
+
+    pub fn insert_totp_enrolment_secret(&self, secret: String) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::TOTP_ENROLMENT_SECRET_KEY, secret)
+    }
+
+    pub fn get_totp_enrolment_secret(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::TOTP_ENROLMENT_SECRET_KEY)
+    }
+
+    pub fn remove_totp_enrolment_secret(&self) {
+        self.0.remove(Self::TOTP_ENROLMENT_SECRET_KEY);
+    }
+
+    ///
+    /// The CSRF token of the session, created the first time a form is rendered.
+    pub fn get_or_insert_csrf_token(&self) -> Result<String, anyhow::Error> {
+        if let Some(token) = self.get_csrf_token()? {
+            return Ok(token);
+        }
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        self.0.insert(Self::CSRF_TOKEN_KEY, &token)?;
+        Ok(token)
+    }
+
+    pub fn get_csrf_token(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
     pub fn log_out(self) {
         self.0.purge()
     }
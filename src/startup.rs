@@ -1,14 +1,19 @@
-use crate::authentication::middleware::reject_anonymous_users;
-use crate::configuration::{get_environment, Settings};
+use crate::authentication::csrf::verify_csrf_token;
+use crate::authentication::middleware::{
+    reject_anonymous_users, require_analyst, require_editor, require_owner,
+    require_recent_authentication,
+};
+use crate::configuration::{get_environment, ApplicationSettings, SessionSettings, Settings};
 use crate::email_client::EmailClient;
 use crate::routes::{self, site};
+use crate::security_headers::{add_security_headers, SecurityHeaders};
 use crate::session::SqlxSqliteSessionStore;
 use crate::utils::get_connection_pool;
+use actix_session::config::BrowserSession;
 use actix_session::SessionMiddleware;
-use actix_web::cookie::Key;
+use actix_web::cookie::{time::Duration, Key};
 use actix_web::dev::Server;
 use actix_web::middleware::from_fn;
-use actix_web::rt::time;
 use actix_web::{web, App, HttpServer};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::{FlashMessagesFramework, Level};
@@ -18,7 +23,6 @@ use std::env;
 use std::io::Error;
 use std::net::TcpListener;
 use std::path::Path;
-use std::time::Duration;
 use tracing_actix_web::TracingLogger;
 
 pub struct Application {
@@ -55,8 +59,7 @@ impl Application {
             listener,
             connection_pool,
             email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
+            configuration.application,
         )?;
         Ok(Self { port, server })
     }
@@ -72,42 +75,51 @@ impl Application {
 
 pub struct ApplicationBaseUrl(pub String);
 
+/// Issue content is posted as a form: leave room for emails past Gmail's clipping threshold,
+/// so editors can preview them and get warned, instead of hitting actix-web's 16KB default.
+const FORM_PAYLOAD_LIMIT: usize = 1024 * 1024;
+
 pub fn run(
     listener: TcpListener,
     db_pool: SqlitePool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
+    application: ApplicationSettings,
 ) -> Result<Server, Error> {
-    let session_store = SqlxSqliteSessionStore::new_pooled(db_pool.clone());
+    let ApplicationSettings {
+        base_url,
+        hmac_secret,
+        client_ip_header,
+        session: session_settings,
+        security_headers,
+        ..
+    } = application;
+    let security_headers =
+        web::Data::new(SecurityHeaders::new(&security_headers, &base_url).map_err(Error::other)?);
+    let session_store = SqlxSqliteSessionStore::builder_pooled(db_pool.clone())
+        .cache_capacity(session_settings.cache_capacity)
+        .expiry_write_threshold(session_settings.expiry_write_threshold_seconds)
+        .build();
 
     let db_pool_web = web::Data::new(db_pool);
     let email_client = web::Data::new(email_client);
     let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let client_ip_header = web::Data::new(ClientIpHeader(client_ip_header));
     let secret_key: Key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store)
         .minimum_level(Level::Debug)
         .build();
 
-    let session_store_clone = session_store.clone();
-    actix_web::rt::task::spawn_blocking(move || async move {
-        let mut interval = time::interval(Duration::from_secs(300));
-        loop {
-            interval.tick().await;
-            let result = session_store_clone.cleanup().await;
-            println!("300 sec {:?}", result);
-        }
-    });
-
     let server = HttpServer::new(move || {
         App::new()
             .wrap(message_framework.clone())
             .wrap(TracingLogger::default())
-            .wrap(SessionMiddleware::new(
+            .wrap(session_middleware(
                 session_store.clone(),
                 secret_key.clone(),
+                &session_settings,
             ))
+            .wrap(from_fn(add_security_headers))
             .route(
                 "/health_check",
                 web::get().to(routes::health_check::health_check),
@@ -120,11 +132,85 @@ pub fn run(
                 "/subscriptions/confirm",
                 web::get().to(routes::subscriptions_confirm::confirm),
             )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(routes::subscriptions_unsubscribe::unsubscribe_form),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::post().to(routes::subscriptions_unsubscribe::unsubscribe),
+            )
+            .route(
+                "/subscriptions/tracking-opt-out",
+                web::get().to(routes::subscriptions_tracking_opt_out::opt_out_of_tracking_form),
+            )
+            .route(
+                "/subscriptions/tracking-opt-out",
+                web::post().to(routes::subscriptions_tracking_opt_out::opt_out_of_tracking),
+            )
             .route("/", web::get().to(site::home::home))
+            .route(
+                "/issues",
+                web::get().to(site::issues::list_published_issues),
+            )
+            .route(
+                "/issues/{issue_id}",
+                web::get().to(site::issues::published_issue),
+            )
+            .route("/feed.xml", web::get().to(routes::feeds::rss_feed))
+            .route("/atom.xml", web::get().to(routes::feeds::atom_feed))
+            .route("/t/{token}", web::get().to(routes::track::open_pixel))
+            .route("/r/{token}", web::get().to(routes::track::click_redirect))
             .route("/login", web::get().to(site::login::get::login_form))
-            .route("/login", web::post().to(site::login::post::post))
+            .route(
+                "/invitations/accept",
+                web::get().to(site::invitation::get::accept_invitation_form),
+            )
+            .route(
+                "/invitations/accept",
+                web::post()
+                    .to(site::invitation::post::accept_invitation)
+                    .wrap(from_fn(verify_csrf_token)),
+            )
+            .route(
+                "/login",
+                web::post()
+                    .to(site::login::post::post)
+                    .wrap(from_fn(verify_csrf_token)),
+            )
+            .route(
+                "/login/forgot-password",
+                web::get().to(site::password_reset::get::forgot_password_form),
+            )
+            .route(
+                "/login/forgot-password",
+                web::post()
+                    .to(site::password_reset::post::request_password_reset)
+                    .wrap(from_fn(verify_csrf_token)),
+            )
+            .route(
+                "/login/reset-password",
+                web::get().to(site::password_reset::get::reset_password_form),
+            )
+            .route(
+                "/login/reset-password",
+                web::post()
+                    .to(site::password_reset::post::reset_password)
+                    .wrap(from_fn(verify_csrf_token)),
+            )
+            .route(
+                "/login/two-factor",
+                web::get().to(site::login::two_factor::two_factor_form),
+            )
+            .route(
+                "/login/two-factor",
+                web::post()
+                    .to(site::login::two_factor::verify_two_factor)
+                    .wrap(from_fn(verify_csrf_token)),
+            )
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(verify_csrf_token))
                     .wrap(from_fn(reject_anonymous_users))
                     .route(
                         "/dashboard",
@@ -132,26 +218,220 @@ pub fn run(
                     )
                     .route(
                         "/password",
-                        web::get().to(site::admin::password::get::change_password_form),
+                        web::get()
+                            .to(site::admin::password::get::change_password_form)
+                            .wrap(from_fn(require_recent_authentication)),
                     )
                     .route(
                         "/password",
-                        web::post().to(site::admin::password::post::change_password),
+                        web::post()
+                            .to(site::admin::password::post::change_password)
+                            .wrap(from_fn(require_recent_authentication)),
+                    )
+                    .route(
+                        "/two-factor",
+                        web::get()
+                            .to(site::admin::two_factor::get::two_factor_settings)
+                            .wrap(from_fn(require_recent_authentication)),
+                    )
+                    .route(
+                        "/two-factor/enable",
+                        web::post()
+                            .to(site::admin::two_factor::post::enable_two_factor)
+                            .wrap(from_fn(require_recent_authentication)),
+                    )
+                    .route(
+                        "/two-factor/disable",
+                        web::post()
+                            .to(site::admin::two_factor::post::disable_two_factor)
+                            .wrap(from_fn(require_recent_authentication)),
+                    )
+                    .route(
+                        "/sessions",
+                        web::get().to(site::admin::sessions::get::list_sessions),
+                    )
+                    .route(
+                        "/sessions/revoke-all",
+                        web::post().to(site::admin::sessions::post::revoke_all_sessions),
+                    )
+                    .route(
+                        "/sessions/revoke-everyone",
+                        web::post()
+                            .to(site::admin::sessions::post::revoke_everyone)
+                            .wrap(from_fn(require_recent_authentication))
+                            .wrap(from_fn(require_owner)),
+                    )
+                    .route(
+                        "/sessions/{handle}/revoke",
+                        web::post().to(site::admin::sessions::post::revoke_session),
+                    )
+                    .route(
+                        "/reauthenticate",
+                        web::get().to(site::admin::reauthenticate::get::reauthenticate_form),
+                    )
+                    .route(
+                        "/reauthenticate",
+                        web::post().to(site::admin::reauthenticate::post::reauthenticate),
                     )
                     .route("/logout", web::post().to(site::admin::logout::log_out))
+                    .route(
+                        "/analytics",
+                        web::get()
+                            .to(site::admin::analytics::get::analytics)
+                            .wrap(from_fn(require_analyst)),
+                    )
+                    .route(
+                        "/analytics.json",
+                        web::get()
+                            .to(site::admin::analytics::export::analytics_json)
+                            .wrap(from_fn(require_analyst)),
+                    )
+                    .route(
+                        "/analytics/issues.csv",
+                        web::get()
+                            .to(site::admin::analytics::export::issues_csv)
+                            .wrap(from_fn(require_analyst)),
+                    )
+                    .route(
+                        "/analytics/months.csv",
+                        web::get()
+                            .to(site::admin::analytics::export::months_csv)
+                            .wrap(from_fn(require_analyst)),
+                    )
                     .route(
                         "/newsletters",
-                        web::get().to(site::admin::newsletter::get::get),
+                        web::get()
+                            .to(site::admin::newsletter::get::get)
+                            .wrap(from_fn(require_recent_authentication))
+                            .wrap(from_fn(require_editor)),
                     )
                     .route(
                         "/newsletters",
-                        web::post().to(site::admin::newsletter::post::publish_newsletter),
+                        web::post()
+                            .to(site::admin::newsletter::post::publish_newsletter)
+                            .wrap(from_fn(require_recent_authentication))
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/newsletters/preview",
+                        web::post()
+                            .to(site::admin::newsletter::preview::preview_newsletter)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/newsletters/test",
+                        web::post()
+                            .to(site::admin::newsletter::send_test::send_test_email)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/issues",
+                        web::get().to(site::admin::issues::get::list_issues),
+                    )
+                    .route(
+                        "/issues/{issue_id}",
+                        web::get().to(site::admin::issues::status::issue_status),
+                    )
+                    .route(
+                        "/issues/{issue_id}/cancel",
+                        web::post()
+                            .to(site::admin::issues::post::cancel_issue)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/issues/{issue_id}/schedule",
+                        web::post()
+                            .to(site::admin::issues::post::reschedule_issue)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/templates",
+                        web::get().to(site::admin::templates::get::list_templates),
+                    )
+                    .route(
+                        "/templates",
+                        web::post()
+                            .to(site::admin::templates::post::create_template)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/templates/new",
+                        web::get()
+                            .to(site::admin::templates::get::new_template_form)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/templates/{template_id}",
+                        web::get().to(site::admin::templates::get::edit_template_form),
+                    )
+                    .route(
+                        "/templates/{template_id}",
+                        web::post()
+                            .to(site::admin::templates::post::update_template)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/confirmation-email",
+                        web::get()
+                            .to(site::admin::confirmation_email::get::confirmation_email_form),
+                    )
+                    .route(
+                        "/confirmation-email",
+                        web::post()
+                            .to(site::admin::confirmation_email::post::update_confirmation_email)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/confirmation-email/preview",
+                        web::post()
+                            .to(site::admin::confirmation_email::preview::preview_confirmation_email)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/confirmation-email/test",
+                        web::post()
+                            .to(site::admin::confirmation_email::send_test::send_test_confirmation_email)
+                            .wrap(from_fn(require_editor)),
+                    )
+                    .route(
+                        "/users",
+                        web::get()
+                            .to(site::admin::users::get::list_users)
+                            .wrap(from_fn(require_owner)),
+                    )
+                    .route(
+                        "/users/invitations",
+                        web::post()
+                            .to(site::admin::users::post::invite_user)
+                            .wrap(from_fn(require_recent_authentication))
+                            .wrap(from_fn(require_owner)),
+                    )
+                    .route(
+                        "/users/{user_id}/role",
+                        web::post()
+                            .to(site::admin::users::post::change_role)
+                            .wrap(from_fn(require_recent_authentication))
+                            .wrap(from_fn(require_owner)),
+                    )
+                    .route(
+                        "/users/{user_id}/deactivate",
+                        web::post()
+                            .to(site::admin::users::post::deactivate_user)
+                            .wrap(from_fn(require_recent_authentication))
+                            .wrap(from_fn(require_owner)),
                     ),
             )
+            .app_data(web::FormConfig::default().limit(FORM_PAYLOAD_LIMIT))
+            // The CSRF check reads form bodies before the handlers do.
+            .app_data(web::PayloadConfig::new(FORM_PAYLOAD_LIMIT))
             .app_data(db_pool_web.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
             .app_data(web::Data::new(HmacSecret(hmac_secret.clone())))
+            .app_data(client_ip_header.clone())
+            .app_data(web::Data::new(session_store.clone()))
+            .app_data(web::Data::new(session_settings.clone()))
+            .app_data(security_headers.clone())
     })
     .listen(listener)?
     .run();
@@ -159,9 +439,32 @@ pub fn run(
     Ok(server)
 }
 
+///
+/// Cookie attributes come from the settings. The store keeps a session for as long as
+/// the idle timeout, `TypedSession` enforces the timeout and the absolute lifetime.
+fn session_middleware(
+    session_store: SqlxSqliteSessionStore,
+    secret_key: Key,
+    settings: &SessionSettings,
+) -> SessionMiddleware<SqlxSqliteSessionStore> {
+    SessionMiddleware::builder(session_store, secret_key)
+        .cookie_name(settings.cookie_name.clone())
+        .cookie_secure(settings.cookie_secure)
+        .cookie_same_site(settings.cookie_same_site.into())
+        .cookie_domain(settings.cookie_domain.clone())
+        .cookie_http_only(true)
+        .session_lifecycle(
+            BrowserSession::default().state_ttl(Duration::seconds(settings.idle_timeout_seconds)),
+        )
+        .build()
+}
+
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
+/// See `ApplicationSettings::client_ip_header`.
+pub struct ClientIpHeader(pub Option<String>);
+
 pub async fn run_migration(db_pool: &SqlitePool) {
     let migrations = if env::var("APP_ENVIRONMENT") == Ok("production".to_string()) {
         Path::new("/app/migrations").join("")
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use htmlescape::encode_attribute;
+
+/// The merge variables available in issue content and layouts, rendered per recipient.
+pub const ISSUE_VARIABLES: [&str; 4] = [
+    "name",
+    "unsubscribe_url",
+    "issue_url",
+    "tracking_opt_out_url",
+];
+/// The merge variables available in confirmation emails.
+pub const CONFIRMATION_VARIABLES: [&str; 1] = [CONFIRMATION_LINK_VARIABLE];
+/// Where a confirmation email links to the page confirming the subscription.
+pub const CONFIRMATION_LINK_VARIABLE: &str = "confirmation_link";
+/// Where a layout template embeds the content of the issue.
+pub const CONTENT_VARIABLE: &str = "content";
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("`{{{{ {0} }}}}` is not a known merge variable.")]
+    UnknownVariable(String),
+    #[error("A `{{{{` is never closed with `}}}}`.")]
+    UnclosedTag,
+    #[error("The template must contain `{{{{ {0} }}}}`.")]
+    MissingVariable(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Variable(String),
+}
+
+/// How merge values are written into the output.
+#[derive(Debug, Clone, Copy)]
+pub enum Escaping {
+    Html,
+    PlainText,
+}
+
+///
+/// A template with `{{ variable }}` placeholders.
+/// Only the variables it was parsed with are accepted, so a typo is caught when
+/// the template is saved or the issue is published rather than when it is sent.
+/// A literal `{{` is written `\{{`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeTemplate {
+    parts: Vec<Part>,
+}
+
+impl MergeTemplate {
+    pub fn parse(source: &str, allowed_variables: &[&str]) -> Result<MergeTemplate, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            if let Some(literal) = rest[..start].strip_suffix('\\') {
+                parts.push(Part::Literal(format!("{literal}{{{{")));
+                rest = &rest[start + 2..];
+                continue;
+            }
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let after_open = &rest[start + 2..];
+            let end = after_open.find("}}").ok_or(TemplateError::UnclosedTag)?;
+            let name = after_open[..end].trim();
+            if !allowed_variables.contains(&name) {
+                return Err(TemplateError::UnknownVariable(name.to_string()));
+            }
+            parts.push(Part::Variable(name.to_string()));
+            rest = &after_open[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Parse a layout: it accepts the issue variables and must embed the content.
+    pub fn parse_layout(source: &str) -> Result<MergeTemplate, TemplateError> {
+        let mut allowed_variables = ISSUE_VARIABLES.to_vec();
+        allowed_variables.push(CONTENT_VARIABLE);
+        let layout = Self::parse(source, &allowed_variables)?;
+        layout.require(CONTENT_VARIABLE)?;
+        Ok(layout)
+    }
+
+    /// Fail unless `variable` appears in the template.
+    pub fn require(&self, variable: &str) -> Result<(), TemplateError> {
+        if self.contains(variable) {
+            Ok(())
+        } else {
+            Err(TemplateError::MissingVariable(variable.to_string()))
+        }
+    }
+
+    pub fn contains(&self, variable: &str) -> bool {
+        self.parts
+            .iter()
+            .any(|p| matches!(p, Part::Variable(v) if v == variable))
+    }
+
+    /// Replace every `{{ variable }}` with the parts of another template.
+    pub fn embed(&self, variable: &str, inner: &MergeTemplate) -> MergeTemplate {
+        let parts = self
+            .parts
+            .iter()
+            .flat_map(|part| match part {
+                Part::Variable(v) if v == variable => inner.parts.clone(),
+                part => vec![part.clone()],
+            })
+            .collect();
+        Self { parts }
+    }
+
+    /// Fill in the variables. Missing values are rendered as empty strings.
+    pub fn render(&self, values: &HashMap<&str, String>, escaping: Escaping) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => output.push_str(literal),
+                Part::Variable(name) => {
+                    let value = values.get(name.as_str()).map(String::as_str).unwrap_or("");
+                    // Values may end up in attributes, e.g. an `href`, so quotes are escaped too.
+                    match escaping {
+                        Escaping::Html => output.push_str(&encode_attribute(value)),
+                        Escaping::PlainText => output.push_str(value),
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use claims::{assert_err, assert_ok};
+
+    use crate::templating::{Escaping, MergeTemplate, TemplateError, ISSUE_VARIABLES};
+
+    fn values() -> HashMap<&'static str, String> {
+        HashMap::from([
+            ("name", "Ada <Lovelace>".to_string()),
+            (
+                "unsubscribe_url",
+                "https://example.com/u?a=1&b=2".to_string(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn variables_are_rendered_with_or_without_spaces() {
+        let template = MergeTemplate::parse("Hi {{name}}, {{ name }}!", &ISSUE_VARIABLES).unwrap();
+        assert_eq!(
+            template.render(&values(), Escaping::PlainText),
+            "Hi Ada <Lovelace>, Ada <Lovelace>!"
+        );
+    }
+
+    #[test]
+    fn values_are_escaped_in_html() {
+        let template = MergeTemplate::parse(
+            r#"<p>Hi {{ name }}</p><a href="{{ unsubscribe_url }}">x</a>"#,
+            &ISSUE_VARIABLES,
+        )
+        .unwrap();
+        assert_eq!(
+            template.render(&values(), Escaping::Html),
+            r#"<p>Hi Ada&#x20;&lt;Lovelace&gt;</p><a href="https&#x3A;&#x2F;&#x2F;example&#x2E;com&#x2F;u&#x3F;a&#x3D;1&amp;b&#x3D;2">x</a>"#
+        );
+    }
+
+    #[test]
+    fn quotes_in_values_cannot_break_out_of_attributes() {
+        let template =
+            MergeTemplate::parse(r#"<a title='{{ name }}' href="x">"#, &ISSUE_VARIABLES).unwrap();
+        let values = HashMap::from([("name", r#"" onclick="alert(1)' x='"#.to_string())]);
+        let html = template.render(&values, Escaping::Html);
+        assert_eq!(html.matches('\'').count(), 2);
+        assert_eq!(html.matches('"').count(), 2);
+        assert_eq!(
+            htmlescape::decode_html(&html).unwrap(),
+            r#"<a title='" onclick="alert(1)' x='' href="x">"#
+        );
+    }
+
+    #[test]
+    fn escaped_braces_are_rendered_literally() {
+        let template =
+            MergeTemplate::parse(r"Use \{{ name }} to greet {{ name }}", &ISSUE_VARIABLES).unwrap();
+        assert_eq!(
+            template.render(&values(), Escaping::PlainText),
+            "Use {{ name }} to greet Ada <Lovelace>"
+        );
+    }
+
+    #[test]
+    fn unknown_variables_are_rejected() {
+        assert_eq!(
+            MergeTemplate::parse("Hi {{ nmae }}", &ISSUE_VARIABLES),
+            Err(TemplateError::UnknownVariable("nmae".into()))
+        );
+    }
+
+    #[test]
+    fn unclosed_tags_are_rejected() {
+        assert_eq!(
+            MergeTemplate::parse("Hi {{ name", &ISSUE_VARIABLES),
+            Err(TemplateError::UnclosedTag)
+        );
+    }
+
+    #[test]
+    fn templates_without_variables_are_fine() {
+        let template = MergeTemplate::parse("Just text }}", &ISSUE_VARIABLES).unwrap();
+        assert_eq!(template.render(&values(), Escaping::Html), "Just text }}");
+    }
+
+    #[test]
+    fn required_variables_are_enforced() {
+        let template = MergeTemplate::parse("Hi {{ name }}", &ISSUE_VARIABLES).unwrap();
+        assert_ok!(template.require("name"));
+        assert_err!(template.require("unsubscribe_url"));
+    }
+
+    #[test]
+    fn layouts_must_embed_the_content() {
+        assert_eq!(
+            MergeTemplate::parse_layout("<main>{{ name }}</main>"),
+            Err(TemplateError::MissingVariable("content".into()))
+        );
+    }
+
+    #[test]
+    fn content_is_embedded_in_a_layout() {
+        let layout = MergeTemplate::parse_layout("<main>{{ content }}</main>").unwrap();
+        let content = MergeTemplate::parse("Hi {{ name }}", &ISSUE_VARIABLES).unwrap();
+        let email = layout.embed("content", &content);
+        assert_eq!(
+            email.render(&values(), Escaping::Html),
+            "<main>Hi Ada&#x20;&lt;Lovelace&gt;</main>"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+/// Something a recipient did with an issue, as carried by the links in their email.
+#[derive(Debug, PartialEq)]
+pub enum TrackedEvent {
+    Open {
+        newsletter_issue_id: String,
+        subscriber_id: String,
+    },
+    Click {
+        newsletter_issue_id: String,
+        subscriber_id: String,
+        url: String,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("The tracking token is invalid.")]
+pub struct InvalidToken;
+
+impl TrackedEvent {
+    ///
+    /// Encode the event in a URL-safe token, signed so that it cannot be forged
+    /// to record events for other recipients or issues.
+    pub fn token(&self, hmac_secret: &Secret<String>) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(self.payload());
+        let signature = URL_SAFE_NO_PAD.encode(sign(hmac_secret, payload.as_bytes()));
+        format!("{payload}.{signature}")
+    }
+
+    pub fn from_token(token: &str, hmac_secret: &Secret<String>) -> Result<Self, InvalidToken> {
+        let (payload, signature) = token.split_once('.').ok_or(InvalidToken)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| InvalidToken)?;
+        let mut mac = hmac(hmac_secret);
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| InvalidToken)?;
+
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| InvalidToken)?;
+        let payload = String::from_utf8(payload).map_err(|_| InvalidToken)?;
+        // The URL comes last: whatever it contains is part of it.
+        match payload.splitn(4, '\n').collect::<Vec<_>>().as_slice() {
+            ["open", newsletter_issue_id, subscriber_id] => Ok(Self::Open {
+                newsletter_issue_id: newsletter_issue_id.to_string(),
+                subscriber_id: subscriber_id.to_string(),
+            }),
+            ["click", newsletter_issue_id, subscriber_id, url] => Ok(Self::Click {
+                newsletter_issue_id: newsletter_issue_id.to_string(),
+                subscriber_id: subscriber_id.to_string(),
+                url: url.to_string(),
+            }),
+            _ => Err(InvalidToken),
+        }
+    }
+
+    /// The kind of event comes first, so a token can only ever be used for what it was issued for.
+    fn payload(&self) -> String {
+        match self {
+            Self::Open {
+                newsletter_issue_id,
+                subscriber_id,
+            } => format!("open\n{newsletter_issue_id}\n{subscriber_id}"),
+            Self::Click {
+                newsletter_issue_id,
+                subscriber_id,
+                url,
+            } => format!("click\n{newsletter_issue_id}\n{subscriber_id}\n{url}"),
+        }
+    }
+}
+
+///
+/// Route the web links of an email through `/r/{token}`, so that clicks get recorded.
+/// Other links (`mailto:`, anchors...) and the ones in `excluded_prefixes` are left alone.
+pub fn track_clicks(
+    html: &str,
+    base_url: &str,
+    newsletter_issue_id: &str,
+    subscriber_id: &str,
+    excluded_prefixes: &[&str],
+    hmac_secret: &Secret<String>,
+) -> Result<String, anyhow::Error> {
+    let html = rewrite_str(
+        html,
+        RewriteStrSettings::new().append_element_content_handler(element!("a[href]", |el| {
+            let Some(href) = el.get_attribute("href") else {
+                return Ok(());
+            };
+            // Attribute values come with their HTML entities, e.g. `&amp;` in query strings.
+            let url = htmlescape::decode_html(&href).unwrap_or(href);
+            let is_web_link = url.starts_with("https://") || url.starts_with("http://");
+            if !is_web_link || excluded_prefixes.iter().any(|p| url.starts_with(p)) {
+                return Ok(());
+            }
+            let click = TrackedEvent::Click {
+                newsletter_issue_id: newsletter_issue_id.to_string(),
+                subscriber_id: subscriber_id.to_string(),
+                url,
+            };
+            el.set_attribute(
+                "href",
+                &format!("{}/r/{}", base_url, click.token(hmac_secret)),
+            )?;
+            Ok(())
+        })),
+    )?;
+    Ok(html)
+}
+
+fn hmac(hmac_secret: &Secret<String>) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size.")
+}
+
+fn sign(hmac_secret: &Secret<String>, payload: &[u8]) -> Vec<u8> {
+    let mut mac = hmac(hmac_secret);
+    mac.update(payload);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok_eq};
+    use secrecy::Secret;
+
+    use crate::tracking::{track_clicks, TrackedEvent};
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-secret".to_string())
+    }
+
+    fn open() -> TrackedEvent {
+        TrackedEvent::Open {
+            newsletter_issue_id: "0H5N0000000A1".into(),
+            subscriber_id: "0H5N0000000S1".into(),
+        }
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        let token = open().token(&secret());
+        assert_ok_eq!(TrackedEvent::from_token(&token, &secret()), open());
+    }
+
+    #[test]
+    fn click_tokens_round_trip() {
+        let click = TrackedEvent::Click {
+            newsletter_issue_id: "0H5N0000000A1".into(),
+            subscriber_id: "0H5N0000000S1".into(),
+            url: "https://example.com/?a=1&b=2\n".into(),
+        };
+        let token = click.token(&secret());
+        assert_ok_eq!(TrackedEvent::from_token(&token, &secret()), click);
+    }
+
+    #[test]
+    fn web_links_are_routed_through_the_redirect_endpoint() {
+        let html = r#"<a href="https://example.com/?a=1&amp;b=2">Post</a><a href="mailto:me@example.com">Mail</a><a href="https://news.example.com/subscriptions/unsubscribe?subscription_token=abc">Unsubscribe</a>"#;
+
+        let tracked = track_clicks(
+            html,
+            "https://news.example.com",
+            "0H5N0000000A1",
+            "0H5N0000000S1",
+            &["https://news.example.com/subscriptions/"],
+            &secret(),
+        )
+        .unwrap();
+
+        let prefix = r#"<a href="https://news.example.com/r/"#;
+        assert!(tracked.starts_with(prefix));
+        let token = &tracked[prefix.len()..tracked.find(r#"">Post"#).unwrap()];
+        assert_ok_eq!(
+            TrackedEvent::from_token(token, &secret()),
+            TrackedEvent::Click {
+                newsletter_issue_id: "0H5N0000000A1".into(),
+                subscriber_id: "0H5N0000000S1".into(),
+                url: "https://example.com/?a=1&b=2".into(),
+            }
+        );
+        assert!(tracked.contains(r#"<a href="mailto:me@example.com">Mail</a>"#));
+        assert!(tracked.contains(
+            r#"<a href="https://news.example.com/subscriptions/unsubscribe?subscription_token=abc">"#
+        ));
+    }
+
+    #[test]
+    fn tokens_signed_with_another_secret_are_rejected() {
+        let token = open().token(&Secret::new("another-secret".to_string()));
+        assert_err!(TrackedEvent::from_token(&token, &secret()));
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let token = open().token(&secret());
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = TrackedEvent::Open {
+            newsletter_issue_id: "0H5N0000000A1".into(),
+            subscriber_id: "0H5N0000000S2".into(),
+        }
+        .token(&secret());
+        let (forged_payload, _) = forged.split_once('.').unwrap();
+
+        let token = format!("{forged_payload}.{signature}");
+        assert_err!(TrackedEvent::from_token(&token, &secret()));
+        assert_err!(TrackedEvent::from_token("garbage", &secret()));
+    }
+}
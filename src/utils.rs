@@ -1,8 +1,15 @@
-use actix_web::{http::header::LOCATION, HttpResponse};
+use actix_web::{
+    http::{header::ContentType, header::LOCATION, StatusCode},
+    web, HttpRequest, HttpResponse,
+};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use chrono::DateTime;
 use secrecy::ExposeSecret;
 use sqlx::SqlitePool;
+use std::net::IpAddr;
 
-use crate::configuration::DatabaseSettings;
+use crate::{configuration::DatabaseSettings, startup::ClientIpHeader};
 
 pub async fn get_connection_pool(
     database_configuration: &DatabaseSettings,
@@ -35,6 +42,31 @@ pub fn see_other(location: &str) -> HttpResponse {
         .finish()
 }
 
+///
+/// Render a page of the site. Values are HTML-escaped by the template.
+pub fn html_page(page: &impl Template) -> Result<HttpResponse, actix_web::Error> {
+    html_page_with_status(StatusCode::OK, page)
+}
+
+pub fn html_page_with_status(
+    status: StatusCode,
+    page: &impl Template,
+) -> Result<HttpResponse, actix_web::Error> {
+    let body = page.render().map_err(e500)?;
+    Ok(HttpResponse::build(status)
+        .content_type(ContentType::html())
+        .body(body))
+}
+
+///
+/// The content of the flash messages, of all levels, for a page to display.
+pub fn flash_contents(flash_messages: &IncomingFlashMessages) -> Vec<String> {
+    flash_messages
+        .iter()
+        .map(|m| m.content().to_owned())
+        .collect()
+}
+
 ///
 /// Return a 400 with the user-representation of the validation error as body.
 /// The error root cause is preserved for logging purposes
@@ -44,3 +76,73 @@ where
 {
     actix_web::error::ErrorBadRequest(e)
 }
+
+///
+/// Render a unix timestamp for humans, e.g. `2024-09-02 08:15 UTC`.
+pub fn format_timestamp(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+///
+/// The address the request came from.
+/// Behind a reverse proxy, that is the header the proxy was configured to set: the peer is the proxy.
+/// Other forwarded headers are ignored, the client chooses those.
+pub fn peer_ip_address(request: &HttpRequest) -> String {
+    let trusted_header = request
+        .app_data::<web::Data<ClientIpHeader>>()
+        .and_then(|header| header.0.as_deref());
+    if let Some(ip_address) = trusted_header
+        .and_then(|name| request.headers().get(name))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<IpAddr>().ok())
+    {
+        return ip_address.to_string();
+    }
+    request
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test::TestRequest, web};
+
+    use crate::{startup::ClientIpHeader, utils::peer_ip_address};
+
+    const PROXY: &str = "172.16.0.1:443";
+
+    fn request(client_ip_header: Option<&str>, fly_client_ip: Option<&str>) -> TestRequest {
+        let mut request = TestRequest::default()
+            .peer_addr(PROXY.parse().unwrap())
+            .app_data(web::Data::new(ClientIpHeader(
+                client_ip_header.map(String::from),
+            )));
+        if let Some(ip) = fly_client_ip {
+            request = request.insert_header(("Fly-Client-IP", ip));
+        }
+        request
+    }
+
+    #[test]
+    fn the_configured_header_is_trusted() {
+        let request = request(Some("Fly-Client-IP"), Some("203.0.113.7")).to_http_request();
+        assert_eq!(peer_ip_address(&request), "203.0.113.7");
+    }
+
+    #[test]
+    fn headers_are_ignored_unless_configured() {
+        let request = request(None, Some("203.0.113.7")).to_http_request();
+        assert_eq!(peer_ip_address(&request), "172.16.0.1");
+    }
+
+    #[test]
+    fn the_peer_is_the_fallback() {
+        for header in [None, Some("not an address")] {
+            let request = request(Some("Fly-Client-IP"), header).to_http_request();
+            assert_eq!(peer_ip_address(&request), "172.16.0.1");
+        }
+    }
+}
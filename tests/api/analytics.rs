@@ -0,0 +1,191 @@
+use sqlx::SqlitePool;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber,
+    insert_issue, spawn_app, TestApp,
+};
+
+/// Publish a tracked issue to the confirmed subscribers, whose email server answers with `status`.
+async fn publish_and_deliver(app: &TestApp, status: u16) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain text. Unsubscribe: {{ unsubscribe_url }}",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "track_opens": "on",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+}
+
+/// Follow the link of an email with the given prefix, e.g. `/t/`.
+fn find_link(app: &TestApp, body: &str, prefix: &str) -> reqwest::Url {
+    let start = body.find(&format!("{}{}", app.base_url, prefix)).unwrap();
+    let end = start
+        + body[start..]
+            .find(|c: char| c == '"' || c.is_whitespace())
+            .unwrap_or(body.len() - start);
+    let mut link = reqwest::Url::parse(&body[start..end]).unwrap();
+    link.set_port(Some(app.port)).unwrap();
+    link
+}
+
+async fn follow_link(app: &TestApp, body: &str, prefix: &str) {
+    reqwest::get(find_link(app, body, prefix))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_see_analytics(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    for path in ["", ".json", "/issues.csv", "/months.csv"] {
+        let response = app.get_analytics(path).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+#[sqlx::test]
+async fn analytics_aggregate_what_happened_to_each_issue(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    publish_and_deliver(&app, 200).await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    follow_link(&app, email["HtmlBody"].as_str().unwrap(), "/t/").await;
+    // Unsubscribing twice only counts once
+    let unsubscribe_link = find_link(
+        &app,
+        email["TextBody"].as_str().unwrap(),
+        "/subscriptions/unsubscribe",
+    );
+    for _ in 0..2 {
+        reqwest::Client::new()
+            .post(unsubscribe_link.clone())
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    let response = app.get_analytics(".json").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["subscribers"]["confirmed"], 1);
+    assert_eq!(report["subscribers"]["pending_confirmation"], 1);
+    assert_eq!(report["subscribers"]["unsubscribed"], 1);
+    let confirmation_rate = report["subscribers"]["confirmation_rate"].as_f64().unwrap();
+    assert!((confirmation_rate - 2.0 / 3.0).abs() < 1e-9);
+
+    let issue = &report["issues"][0];
+    assert_eq!(issue["title"], "Newsletter Title");
+    assert_eq!(issue["sends"], 2);
+    assert_eq!(issue["deliveries"], 2);
+    assert_eq!(issue["failed_sends"], 0);
+    assert_eq!(issue["opens"], 1);
+    assert_eq!(issue["unique_opens"], 1);
+    assert_eq!(issue["clicks"], 0);
+    assert_eq!(issue["unsubscribes"], 1);
+
+    let months = report["months"].as_array().unwrap();
+    assert_eq!(months.len(), 1);
+    assert_eq!(months[0]["signups"], 3);
+    assert_eq!(months[0]["confirmed_signups"], 2);
+    assert_eq!(months[0]["unsubscribes"], 1);
+    assert_eq!(months[0]["subscribers"], 1);
+    assert_eq!(months[0]["sends"], 2);
+    assert_eq!(months[0]["opens"], 1);
+
+    let html_page = app.get_analytics("").await.text().await.unwrap();
+    assert!(html_page.contains("<tr><th>Confirmation rate</th><td>66.7%</td></tr>"));
+    assert!(html_page.contains("<td>2</td><td>2</td><td>0</td><td>1</td><td>1</td><td>0</td><td>0</td><td>1</td><td>50.0%</td></tr>"));
+}
+
+#[sqlx::test]
+async fn emails_refused_by_the_email_api_count_as_failed_sends(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    publish_and_deliver(&app, 500).await;
+
+    let report: serde_json::Value = app.get_analytics(".json").await.json().await.unwrap();
+    assert_eq!(report["issues"][0]["sends"], 1);
+    assert_eq!(report["issues"][0]["deliveries"], 0);
+    assert_eq!(report["issues"][0]["failed_sends"], 1);
+}
+
+#[sqlx::test]
+async fn analytics_can_be_downloaded_as_csv(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    publish_and_deliver(&app, 200).await;
+
+    let response = app.get_analytics("/issues.csv").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let csv = response.text().await.unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "newsletter_issue_id,title,published_at,sends,deliveries,failed_sends,opens,unique_opens,clicks,unique_clicks,unsubscribes"
+    );
+    let row: Vec<_> = lines.next().unwrap().split(',').collect();
+    assert_eq!(row[1], "Newsletter Title");
+    assert_eq!(row[3..], ["1", "1", "0", "0", "0", "0", "0", "0"]);
+    assert_eq!(lines.next(), None);
+
+    let csv = app.get_analytics("/months.csv").await.text().await.unwrap();
+    assert!(csv.starts_with(
+        "month,signups,confirmed_signups,confirmation_rate,unsubscribes,subscribers,"
+    ));
+    assert_eq!(csv.lines().count(), 2);
+}
+
+#[sqlx::test]
+async fn titles_are_not_exported_as_spreadsheet_formulas(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    insert_issue(&app.db_pool, "0H5N0000000F1", "=1+1", "published").await;
+    insert_issue(&app.db_pool, "0H5N0000000F2", "@SUM(A1)", "published").await;
+    insert_issue(&app.db_pool, "0H5N0000000F3", "Plain title", "published").await;
+
+    let csv = app.get_analytics("/issues.csv").await.text().await.unwrap();
+
+    assert!(csv.contains(",'=1+1,"));
+    assert!(csv.contains(",'@SUM(A1),"));
+    assert!(csv.contains(",Plain title,"));
+}
@@ -0,0 +1,108 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{insert_issue, spawn_app};
+
+#[sqlx::test]
+async fn published_issues_can_be_read_on_the_web(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_issue(&app.db_pool, "0H5N0000000A1", "First <issue>", "published").await;
+
+    let html_page = app.get_archive_html(1).await;
+    assert!(html_page.contains(r#"<a href="/issues/0H5N0000000A1">First &lt;issue&gt;</a>"#));
+
+    let response = app.get_archived_issue("0H5N0000000A1").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>First &lt;issue&gt;</h1>"));
+    assert!(html_page.contains("<p>Hi reader</p>"));
+}
+
+#[sqlx::test]
+async fn issues_stored_unsanitised_are_sanitised_on_the_web(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_issue(&app.db_pool, "0H5N0000000A2", "Old issue", "published").await;
+    // Issues published before their HTML was sanitised are stored as they were typed.
+    sqlx::query!(
+        r#"UPDATE newsletter_issues SET html_content = '<p onclick="steal()">Hi</p><script>alert(1)</script>'"#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app.get_archived_issue("0H5N0000000A2").await;
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<p>Hi</p>"));
+    assert!(!html_page.contains("steal()"));
+    assert!(!html_page.contains("alert(1)"));
+
+    for feed in ["feed.xml", "atom.xml"] {
+        let response = app
+            .api_client
+            .get(format!("{}/{}", app.address, feed))
+            .send()
+            .await
+            .unwrap();
+        let xml = response.text().await.unwrap();
+        assert!(xml.contains("Hi"));
+        assert!(!xml.contains("steal()"));
+        assert!(!xml.contains("alert(1)"));
+    }
+}
+
+#[sqlx::test]
+async fn unpublished_issues_stay_hidden(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_issue(
+        &app.db_pool,
+        "0H5N0000000B1",
+        "Scheduled issue",
+        "scheduled",
+    )
+    .await;
+    insert_issue(
+        &app.db_pool,
+        "0H5N0000000B2",
+        "Cancelled issue",
+        "cancelled",
+    )
+    .await;
+
+    let html_page = app.get_archive_html(1).await;
+    assert!(!html_page.contains("Scheduled issue"));
+    assert!(!html_page.contains("Cancelled issue"));
+    assert!(html_page.contains("No issues have been published yet."));
+
+    for issue_id in ["0H5N0000000B1", "0H5N0000000B2", "unknown"] {
+        let response = app.get_archived_issue(issue_id).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[sqlx::test]
+async fn the_archive_is_paginated(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    for i in 0..25 {
+        let issue_id = format!("0H5N00000C{i:03}");
+        insert_issue(
+            &app.db_pool,
+            &issue_id,
+            &format!("Issue #{i:02}"),
+            "published",
+        )
+        .await;
+    }
+
+    let first_page = app.get_archive_html(1).await;
+    assert_eq!(first_page.matches("<li>").count(), 20);
+    // Newest first
+    assert!(first_page.contains("Issue #24"));
+    assert!(!first_page.contains("Issue #04"));
+    assert!(first_page.contains(r#"<a href="/issues?page=2">"#));
+    assert!(!first_page.contains("Newer issues"));
+
+    let second_page = app.get_archive_html(2).await;
+    assert_eq!(second_page.matches("<li>").count(), 5);
+    assert!(second_page.contains("Issue #04"));
+    assert!(second_page.contains(r#"<a href="/issues?page=1">"#));
+    assert!(!second_page.contains("Older issues"));
+}
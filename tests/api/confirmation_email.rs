@@ -0,0 +1,221 @@
+use sqlx::SqlitePool;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+
+fn customised_email(locale: &str) -> serde_json::Value {
+    serde_json::json!({
+        "locale": locale,
+        "subject": "Une dernière étape",
+        "html_content": r#"<p>Presque fini !</p><a href="{{ confirmation_link }}">Je confirme</a>"#,
+        "text_content": "Presque fini ! Je confirme : {{ confirmation_link }}",
+    })
+}
+
+/// Subscribe in `locale` and return the confirmation email that was sent.
+async fn subscribe(app: &TestApp, locale: &str) -> wiremock::Request {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = format!("name=Marie&email={locale}%40example.com&locale={locale}");
+    let response = app.post_subscriptions(body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_customise_the_confirmation_email(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    for action in ["", "/preview", "/test"] {
+        let response = app
+            .post_confirmation_email(action, &customised_email("fr"))
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+#[sqlx::test]
+async fn viewers_cannot_customise_the_confirmation_email(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    viewer.login(&app).await;
+
+    let response = app
+        .post_confirmation_email("", &customised_email("fr"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[sqlx::test]
+async fn the_default_email_is_shown_until_it_is_customised(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_confirmation_email_html("de").await;
+    assert!(html_page.contains(r#"name="subject" value="Willkommen""#));
+    assert!(html_page.contains(r#"<input type="hidden" name="locale" value="de">"#));
+
+    let response = app
+        .post_confirmation_email("", &customised_email("fr"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/confirmation-email?locale=fr");
+    let html_page = app.get_confirmation_email_html("fr").await;
+    assert!(html_page.contains("<p><i>The confirmation email has been saved.</i></p>"));
+    assert!(html_page.contains(r#"name="subject" value="Une dernière étape""#));
+}
+
+#[sqlx::test]
+async fn new_subscribers_get_the_customised_email_of_their_language(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    app.post_confirmation_email("", &customised_email("fr"))
+        .await;
+
+    let email_request = subscribe(&app, "fr").await;
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Une dernière étape");
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Presque fini ! Je confirme : "));
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Other languages keep the default email
+    let email_request = subscribe(&app, "en").await;
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Welcome");
+}
+
+#[sqlx::test]
+async fn the_confirmation_link_can_never_be_left_out(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let test_cases = [
+        (
+            "<p>Welcome!</p>",
+            "Confirm: {{ confirmation_link }}",
+            "The HTML version is invalid: The template must contain `{{ confirmation_link }}`.",
+        ),
+        (
+            r#"<a href="{{ confirmation_link }}">Confirm</a>"#,
+            "Welcome!",
+            "The plain text version is invalid: The template must contain `{{ confirmation_link }}`.",
+        ),
+        (
+            r#"<a href="{{ confirmation_link }}">Confirm</a>"#,
+            "Hi {{ name }}, confirm: {{ confirmation_link }}",
+            "The plain text version is invalid: `{{ name }}` is not a known merge variable.",
+        ),
+    ];
+
+    for (html_content, text_content, message) in test_cases {
+        let form = serde_json::json!({
+            "locale": "en",
+            "subject": "Welcome",
+            "html_content": html_content,
+            "text_content": text_content,
+            "test_recipients": "editor@example.com",
+        });
+        for action in ["", "/preview", "/test"] {
+            let response = app.post_confirmation_email(action, &form).await;
+            assert_eq!(response.status().as_u16(), 200);
+            let html_page = response.text().await.unwrap();
+            assert!(html_page.contains(&format!("<p><i>{message}</i></p>")));
+        }
+    }
+
+    let n_customised = sqlx::query!("SELECT COUNT(*) AS count FROM confirmation_emails")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_customised, 0);
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[sqlx::test]
+async fn unsupported_languages_are_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_confirmation_email("", &customised_email("es"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[sqlx::test]
+async fn the_preview_shows_the_email_without_saving_it(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_confirmation_email("/preview", &customised_email("fr"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h2>Une dernière étape</h2>"));
+    assert!(html_page.contains(&format!(
+        "Presque fini ! Je confirme : {}/subscriptions/confirm?subscription_token=preview",
+        app.base_url
+    )));
+    let n_customised = sqlx::query!("SELECT COUNT(*) AS count FROM confirmation_emails")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_customised, 0);
+}
+
+#[sqlx::test]
+async fn test_sends_deliver_the_email_as_typed(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let mut form = customised_email("fr");
+    form["test_recipients"] = "editor@example.com, owner@example.com".into();
+    let response = app.post_confirmation_email("/test", &form).await;
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(
+        "<p><i>A test email has been sent to editor@example.com, owner@example.com.</i></p>"
+    ));
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Une dernière étape");
+    // The link confirms nobody
+    let confirmation_links = app.get_confirmation_links(email_request);
+    assert_eq!(
+        confirmation_links.html.query(),
+        Some("subscription_token=preview")
+    );
+}
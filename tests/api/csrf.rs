@@ -0,0 +1,132 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{assert_is_redirect_to, csrf_token, spawn_app, TestApp};
+
+fn assert_is_rejected(response: reqwest::Response) {
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+async fn post_without_helpers(
+    app: &TestApp,
+    path: &str,
+    body: &serde_json::Value,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}{}", &app.address, path))
+        .form(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[sqlx::test]
+async fn forms_render_the_csrf_token_of_the_session(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let anonymous_token = csrf_token(&app.api_client, &app.address).await;
+    app.test_user.login(&app).await;
+    let token = csrf_token(&app.api_client, &app.address).await;
+
+    // Logging in issues a new token: one handed out before could have been planted.
+    assert_ne!(token, anonymous_token);
+    let field = format!(r#"<input type="hidden" name="csrf_token" value="{token}">"#);
+    assert!(app.get_admin_dashboard_html().await.contains(&field));
+    assert!(app.get_change_password_html().await.contains(&field));
+    assert!(app.get_newsletter_html().await.contains(&field));
+}
+
+#[sqlx::test]
+async fn logging_in_without_a_csrf_token_is_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    csrf_token(&app.api_client, &app.address).await;
+
+    let response = post_without_helpers(&app, "/login", &app.test_user.credentials()).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("This form has expired."));
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn admin_forms_without_a_csrf_token_are_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = post_without_helpers(&app, "/admin/logout", &serde_json::json!({})).await;
+    assert_is_rejected(response);
+    let response = post_without_helpers(
+        &app,
+        "/admin/password",
+        &serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "a-new-and-long-password",
+            "new_password_check": "a-new-and-long-password",
+        }),
+    )
+    .await;
+    assert_is_rejected(response);
+
+    // Still logged in, with the same password.
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[sqlx::test]
+async fn a_wrong_csrf_token_is_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let token = csrf_token(&app.api_client, &app.address).await;
+
+    let response = post_without_helpers(
+        &app,
+        "/admin/logout",
+        &serde_json::json!({ "csrf_token": format!("{token}x") }),
+    )
+    .await;
+    assert_is_rejected(response);
+    let response = post_without_helpers(
+        &app,
+        "/admin/logout",
+        &serde_json::json!({ "csrf_token": "x".repeat(token.len()) }),
+    )
+    .await;
+    assert_is_rejected(response);
+}
+
+#[sqlx::test]
+async fn the_token_of_another_session_is_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let attacker = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let attacker_token = csrf_token(&attacker, &app.address).await;
+    app.test_user.login(&app).await;
+
+    let response = post_without_helpers(
+        &app,
+        "/admin/logout",
+        &serde_json::json!({ "csrf_token": attacker_token }),
+    )
+    .await;
+
+    assert_is_rejected(response);
+}
+
+#[sqlx::test]
+async fn the_csrf_token_can_be_sent_as_a_header(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let token = csrf_token(&app.api_client, &app.address).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .header("X-CSRF-Token", token)
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,109 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{insert_issue, spawn_app, TestApp};
+
+async fn get_feed(app: &TestApp, feed: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/{}", app.address, feed))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[sqlx::test]
+async fn feeds_list_published_issues_only(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_issue(
+        &app.db_pool,
+        "0H5N0000000D1",
+        "Tips & <tricks>",
+        "published",
+    )
+    .await;
+    insert_issue(&app.db_pool, "0H5N0000000D2", "Coming soon", "scheduled").await;
+
+    let response = get_feed(&app, "feed.xml").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/rss+xml; charset=utf-8"
+    );
+    let rss = response.text().await.unwrap();
+    assert!(rss.contains("<title>Tips &amp; &lt;tricks&gt;</title>"));
+    assert!(rss.contains(r#"<guid isPermaLink="false">urn:newsletter-issue:0H5N0000000D1</guid>"#));
+    assert!(rss.contains("<description>&lt;p&gt;Hi reader&lt;/p&gt;</description>"));
+    assert!(!rss.contains("Coming soon"));
+
+    let response = get_feed(&app, "atom.xml").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    let atom = response.text().await.unwrap();
+    assert!(atom.contains("<title>Tips &amp; &lt;tricks&gt;</title>"));
+    assert!(atom.contains("<id>urn:newsletter-issue:0H5N0000000D1</id>"));
+    assert!(atom.contains(&format!(
+        r#"<link href="{}/issues/0H5N0000000D1"/>"#,
+        app.base_url
+    )));
+    assert!(!atom.contains("Coming soon"));
+}
+
+#[sqlx::test]
+async fn feeds_support_conditional_requests(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_issue(&app.db_pool, "0H5N0000000E1", "First issue", "published").await;
+
+    for feed in ["feed.xml", "atom.xml"] {
+        let response = get_feed(&app, feed).await;
+        let etag = response.headers()["ETag"].clone();
+        let last_modified = response.headers()["Last-Modified"].clone();
+
+        let response = app
+            .api_client
+            .get(format!("{}/{}", app.address, feed))
+            .header("If-None-Match", etag.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+
+        let response = app
+            .api_client
+            .get(format!("{}/{}", app.address, feed))
+            .header("If-Modified-Since", last_modified)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+
+        let response = app
+            .api_client
+            .get(format!("{}/{}", app.address, feed))
+            .header("If-None-Match", "\"something-else\"")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[sqlx::test]
+async fn publishing_an_issue_changes_the_etag(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_issue(&app.db_pool, "0H5N0000000F1", "First issue", "published").await;
+    let etag = get_feed(&app, "feed.xml").await.headers()["ETag"].clone();
+
+    insert_issue(&app.db_pool, "0H5N0000000F2", "Second issue", "published").await;
+    let response = app
+        .api_client
+        .get(format!("{}/feed.xml", app.address))
+        .header("If-None-Match", etag)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Second issue"));
+}
@@ -1,16 +1,25 @@
 use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
+use fake::{
+    faker::{internet::en::SafeEmail, name::en::Name},
+    Fake,
+};
 use linkify::{LinkFinder, LinkKind};
 use once_cell::sync::Lazy;
 use rand::rngs::OsRng;
 use reqwest::Client;
+use secrecy::Secret;
 use sqlx::SqlitePool;
 use tsid::create_tsid;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
 use zero2prod::{
     configuration::get_configuration,
     email_client::EmailClient,
     issue_delivery_worker::{try_execute_task, ExecutionOutcome},
+    issue_scheduler::{try_publish_due_issue, SchedulingOutcome},
     startup::Application,
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -23,9 +32,19 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: Client,
     pub email_client: EmailClient,
+    pub base_url: String,
+    pub hmac_secret: Secret<String>,
 }
 
 impl TestApp {
+    /// Submit a form from the test client, with the CSRF token of its session.
+    pub async fn post_form<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        post_form(&self.api_client, &self.address, path, body).await
+    }
+
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
             .post(&format!("{}/subscriptions", &self.address))
@@ -51,7 +70,9 @@ impl TestApp {
             confirmation_link
         };
 
-        let html = get_link(&body["HtmlBody"].as_str().unwrap());
+        // Merge values are entity-encoded in HTML, as they may end up in attributes.
+        let html_body = htmlescape::decode_html(body["HtmlBody"].as_str().unwrap()).unwrap();
+        let html = get_link(&html_body);
         let plain_text = get_link(&body["TextBody"].as_str().unwrap());
 
         ConfirmationLinks { html, plain_text }
@@ -61,24 +82,28 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
-            .form(body)
-            .send()
-            .await
-            .expect("Failed to execute request.")
+        self.post_form("/admin/newsletters", body).await
+    }
+
+    pub async fn post_preview_newsletter<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/admin/newsletters/preview", body).await
+    }
+
+    pub async fn post_test_newsletter<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/admin/newsletters/test", body).await
     }
 
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
-        self.api_client
-            .post(&format!("{}/login", &self.address))
-            .form(body)
-            .send()
-            .await
-            .expect("Failed to execute request.")
+        self.post_form("/login", body).await
     }
 
     pub async fn get_login_html(&self) -> String {
@@ -127,20 +152,105 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
+        self.post_form("/admin/password", body).await
+    }
+
+    pub async fn post_forgot_password(&self, username: &str) -> reqwest::Response {
+        self.post_form(
+            "/login/forgot-password",
+            &serde_json::json!({ "username": username }),
+        )
+        .await
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/login/reset-password", body).await
+    }
+
+    pub async fn post_login_two_factor(&self, code: &str) -> reqwest::Response {
+        self.post_form("/login/two-factor", &serde_json::json!({ "code": code }))
+            .await
+    }
+
+    pub async fn get_two_factor_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_two_factor(&self, action: &str, code: &str) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/two-factor/{action}"),
+            &serde_json::json!({ "code": code }),
+        )
+        .await
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
-            .form(body)
+            .get(format!("{}/admin/sessions", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
     }
 
-    pub async fn post_logout(&self) -> reqwest::Response {
+    pub async fn post_revoke_sessions(&self, path: &str) -> reqwest::Response {
+        self.post_form(&format!("/admin/sessions/{path}"), &serde_json::json!({}))
+            .await
+    }
+
+    pub async fn get_reauthenticate_html(&self, next: &str) -> String {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .get(format!("{}/admin/reauthenticate", &self.address))
+            .query(&[("next", next)])
             .send()
             .await
             .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_reauthenticate<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/admin/reauthenticate", body).await
+    }
+
+    /// Move a timestamp of every stored session `seconds` into the past.
+    pub async fn age_sessions(&self, key: &str, seconds: i64) {
+        let path = format!("$.{key}");
+        sqlx::query(
+            r#"
+            UPDATE sessions
+            SET session = json_set(
+                session, $1, CAST(CAST(json_extract(session, $1) AS INTEGER) - $2 AS TEXT)
+            )
+            WHERE json_extract(session, $1) IS NOT NULL
+            "#,
+        )
+        .bind(path)
+        .bind(seconds)
+        .execute(&self.db_pool)
+        .await
+        .unwrap();
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.post_form("/admin/logout", &serde_json::json!({}))
+            .await
     }
 
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
@@ -155,12 +265,192 @@ impl TestApp {
         self.get_publish_newsletter().await.text().await.unwrap()
     }
 
+    pub async fn get_issues_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/issues", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_invitation(&self, email: &str, role: &str) -> reqwest::Response {
+        self.post_form(
+            "/admin/users/invitations",
+            &serde_json::json!({ "email": email, "role": role }),
+        )
+        .await
+    }
+
+    pub async fn post_change_role(&self, user_id: &str, role: &str) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/users/{}/role", user_id),
+            &serde_json::json!({ "role": role }),
+        )
+        .await
+    }
+
+    pub async fn post_deactivate_user(&self, user_id: &str) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/users/{}/deactivate", user_id),
+            &serde_json::json!({}),
+        )
+        .await
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/invitations/accept", body).await
+    }
+
+    /// `path` is relative to `/admin/analytics`, e.g. `.json` or `/issues.csv`.
+    pub async fn get_analytics(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/analytics{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issue_status(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/issues/{}", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_cancel_issue(&self, issue_id: &str) -> reqwest::Response {
+        self.post_form(
+            &format!("/admin/issues/{}/cancel", issue_id),
+            &serde_json::json!({}),
+        )
+        .await
+    }
+
+    pub async fn post_reschedule_issue<Body>(
+        &self,
+        issue_id: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(&format!("/admin/issues/{}/schedule", issue_id), body)
+            .await
+    }
+
+    pub async fn get_archive_html(&self, page: u32) -> String {
+        self.api_client
+            .get(format!("{}/issues?page={}", &self.address, page))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_archived_issue(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/issues/{}", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_templates_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/templates", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_template<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form("/admin/templates", body).await
+    }
+
+    pub async fn post_update_template<Body>(
+        &self,
+        template_id: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(&format!("/admin/templates/{}", template_id), body)
+            .await
+    }
+
+    pub async fn get_confirmation_email_html(&self, locale: &str) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/confirmation-email?locale={}",
+                &self.address, locale
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// `action` is empty to save, `/preview` or `/test`.
+    pub async fn post_confirmation_email<Body>(
+        &self,
+        action: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(&format!("/admin/confirmation-email{}", action), body)
+            .await
+    }
+
+    pub async fn publish_due_issues(&self) {
+        loop {
+            if let SchedulingOutcome::NothingDue =
+                try_publish_due_issue(&self.db_pool).await.unwrap()
+            {
+                break;
+            }
+        }
+    }
+
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+                &self.hmac_secret,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -190,6 +480,8 @@ pub async fn spawn_app(pool: SqlitePool) -> TestApp {
         let mut c = get_configuration().expect("Failed to read configuration.");
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // Tests edit sessions in the database directly, behind the back of a cache.
+        c.application.session.cache_capacity = 0;
         c
     };
 
@@ -214,6 +506,8 @@ pub async fn spawn_app(pool: SqlitePool) -> TestApp {
         test_user: TestUser::generate(),
         api_client: client,
         email_client: configuration.email_client.client(),
+        base_url: configuration.application.base_url,
+        hmac_secret: configuration.application.hmac_secret,
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
@@ -224,30 +518,82 @@ pub struct ConfirmationLinks {
     pub plain_text: reqwest::Url,
 }
 
+/// Use the public API of the application under test to create an unconfirmed subscriber.
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": name,
+        "email": email
+    }))
+    .unwrap();
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // The confirmation links are in the request received by the mock Postmark server.
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(email_request)
+}
+
+/// Subscribe, then follow the confirmation link.
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let confirmation_links = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
 pub struct TestUser {
     pub user_id: String,
     pub username: String,
     pub password: String,
+    pub role: &'static str,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::with_role("owner")
+    }
+
+    pub fn with_role(role: &'static str) -> Self {
         Self {
             user_id: create_tsid().to_string(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            role,
         }
     }
 
-    pub async fn login(&self, app: &TestApp) {
-        app.post_login(&serde_json::json!({
+    pub fn credentials(&self) -> serde_json::Value {
+        serde_json::json!({
             "username": &self.username,
             "password": &self.password
-        }))
-        .await;
+        })
+    }
+
+    pub async fn login(&self, app: &TestApp) {
+        app.post_login(&self.credentials()).await;
     }
 
-    async fn store(&self, pool: &SqlitePool) {
+    pub async fn store(&self, pool: &SqlitePool) {
         let salt = SaltString::generate(&mut OsRng);
         let password_hash = Argon2::default()
             .hash_password(self.password.as_bytes(), &salt)
@@ -255,11 +601,12 @@ impl TestUser {
             .to_string();
 
         sqlx::query!(
-            "INSERT INTO users(user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users(user_id, username, password_hash, role)
+            VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
-            password_hash
+            password_hash,
+            self.role
         )
         .execute(pool)
         .await
@@ -267,6 +614,65 @@ impl TestUser {
     }
 }
 
+/// Store an issue directly, bypassing the admin form, with the given status.
+pub async fn insert_issue(pool: &SqlitePool, issue_id: &str, title: &str, status: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, status, published_at
+        )
+        VALUES (
+            $1, $2, 'Hi {{ name }}', '<p>Hi {{ name }}</p>', $3,
+            CASE WHEN $3 = 'published' THEN unixepoch() ELSE NULL END
+        )
+        "#,
+        issue_id,
+        title,
+        status
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// The CSRF token of `client`'s session, as rendered in the login form.
+pub async fn csrf_token(client: &Client, address: &str) -> String {
+    let html_page = client
+        .get(format!("{address}/login"))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    html_page
+        .split(r#"name="csrf_token" value=""#)
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .expect("The login form carries a CSRF token.")
+        .to_owned()
+}
+
+/// Submit a form from `client`'s browser, the way its pages would: with the CSRF token.
+pub async fn post_form<Body>(
+    client: &Client,
+    address: &str,
+    path: &str,
+    body: &Body,
+) -> reqwest::Response
+where
+    Body: serde::Serialize,
+{
+    let mut form = serde_json::to_value(body).unwrap();
+    form["csrf_token"] = csrf_token(client, address).await.into();
+    client
+        .post(format!("{address}{path}"))
+        .form(&form)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
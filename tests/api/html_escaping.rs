@@ -0,0 +1,118 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{assert_is_redirect_to, insert_issue, post_form, spawn_app, TestUser};
+
+const MARKUP: &str = r#"<script>alert("pwned")</script>"#;
+const ESCAPED_MARKUP: &str = "&lt;script&gt;alert(&quot;pwned&quot;)&lt;/script&gt;";
+
+fn assert_is_escaped(html_page: &str) {
+    assert!(
+        !html_page.contains("<script>"),
+        "The markup was rendered as is."
+    );
+    assert!(html_page.contains(ESCAPED_MARKUP));
+}
+
+#[sqlx::test]
+async fn usernames_are_escaped_on_the_dashboard(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let user = TestUser {
+        username: MARKUP.into(),
+        ..TestUser::generate()
+    };
+    user.store(&app.db_pool).await;
+
+    let response = app.post_login(&user.credentials()).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("<p>Welcome {ESCAPED_MARKUP}!")));
+    assert_is_escaped(&html_page);
+}
+
+#[sqlx::test]
+async fn flash_messages_quoting_form_input_are_escaped(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_invitation(MARKUP, "editor").await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!(
+        "<p><i>{ESCAPED_MARKUP} is not a valid email address.</i></p>"
+    )));
+    assert_is_escaped(&html_page);
+}
+
+#[sqlx::test]
+async fn rejected_forms_escape_what_was_typed(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_test_newsletter(&serde_json::json!({
+            "title": MARKUP,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "test_recipients": MARKUP,
+        }))
+        .await;
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&format!(
+        "<p><i>{ESCAPED_MARKUP} is not a valid subscriber email.</i></p>"
+    )));
+    assert!(html_page.contains(&format!(r#"name="title" value="{ESCAPED_MARKUP}""#)));
+    assert_is_escaped(&html_page);
+}
+
+#[sqlx::test]
+async fn issue_titles_are_escaped(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_issue(&app.db_pool, "0H5N0000000A1", MARKUP, "published").await;
+
+    assert_is_escaped(&app.get_archive_html(1).await);
+    let response = app.get_archived_issue("0H5N0000000A1").await;
+    assert_is_escaped(&response.text().await.unwrap());
+
+    app.test_user.login(&app).await;
+    assert_is_escaped(&app.get_issues_html().await);
+}
+
+#[sqlx::test]
+async fn browsers_are_escaped_in_the_list_of_sessions(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .user_agent(MARKUP)
+        .build()
+        .unwrap();
+    let response = post_form(
+        &client,
+        &app.address,
+        "/login",
+        &app.test_user.credentials(),
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.test_user.login(&app).await;
+
+    assert_is_escaped(&app.get_sessions_html().await);
+}
+
+#[sqlx::test]
+async fn the_page_to_return_to_is_escaped(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app
+        .get_reauthenticate_html(&format!(r#"/admin/password">{MARKUP}"#))
+        .await;
+
+    assert!(html_page.contains(&format!(
+        r#"name="next" value="/admin/password&quot;&gt;{ESCAPED_MARKUP}""#
+    )));
+    assert_is_escaped(&html_page);
+}
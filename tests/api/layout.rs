@@ -0,0 +1,44 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::spawn_app;
+
+const SITE_NAVIGATION: &str = r#"<a href="/issues">Past issues</a>"#;
+const ADMIN_NAVIGATION: &str = r#"<a href="/admin/dashboard">Dashboard</a>"#;
+
+#[sqlx::test]
+async fn public_pages_share_the_site_layout(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    for path in ["/", "/login", "/login/forgot-password", "/issues"] {
+        let html_page = app
+            .api_client
+            .get(format!("{}{}", &app.address, path))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(html_page.starts_with("<!DOCTYPE html>"), "{path}");
+        assert!(html_page.contains(SITE_NAVIGATION), "{path}");
+        assert!(!html_page.contains(ADMIN_NAVIGATION), "{path}");
+    }
+}
+
+#[sqlx::test]
+async fn admin_pages_share_the_admin_layout(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let pages = [
+        app.get_admin_dashboard_html().await,
+        app.get_change_password_html().await,
+        app.get_sessions_html().await,
+        app.get_templates_html().await,
+    ];
+    for html_page in pages {
+        assert!(html_page.starts_with("<!DOCTYPE html>"));
+        assert!(html_page.contains(ADMIN_NAVIGATION));
+        assert!(!html_page.contains(SITE_NAVIGATION));
+    }
+}
@@ -0,0 +1,192 @@
+use sqlx::SqlitePool;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// Subscribe with the given form, as a browser sending `accept_language`, and return the confirmation email.
+async fn subscribe(app: &TestApp, body: &str, accept_language: &str) -> serde_json::Value {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept-Language", accept_language)
+        .body(body.to_owned())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    serde_json::from_slice(&email_request.body).unwrap()
+}
+
+async fn saved_locale(app: &TestApp) -> String {
+    sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .locale
+}
+
+async fn get_html(url: impl reqwest::IntoUrl) -> String {
+    let response = reqwest::get(url).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    response.text().await.unwrap()
+}
+
+#[sqlx::test]
+async fn the_language_picked_in_the_form_comes_first(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let email = subscribe(
+        &app,
+        "name=Marie&email=marie%40example.com&locale=fr",
+        "de-DE,de;q=0.9",
+    )
+    .await;
+
+    assert_eq!(saved_locale(&app).await, "fr");
+    assert_eq!(email["Subject"], "Bienvenue");
+    assert!(email["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("pour confirmer votre abonnement."));
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    let html_page = get_html(confirmation_links.html).await;
+    assert!(html_page.contains(r#"<html lang="fr">"#));
+    assert!(html_page.contains("<title>Abonnement confirmé</title>"));
+    assert!(html_page.contains(r#"<a href="/">Accueil</a>"#));
+}
+
+#[sqlx::test]
+async fn the_browser_language_is_used_when_none_is_picked(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let email = subscribe(
+        &app,
+        "name=Max&email=max%40example.com",
+        "es, de;q=0.8, en;q=0.5",
+    )
+    .await;
+
+    assert_eq!(saved_locale(&app).await, "de");
+    assert_eq!(email["Subject"], "Willkommen");
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("um Ihr Abonnement zu bestätigen."));
+}
+
+#[sqlx::test]
+async fn english_is_the_fallback(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let email = subscribe(
+        &app,
+        "name=Jorge&email=jorge%40example.com&locale=es",
+        "es-ES, pt;q=0.5",
+    )
+    .await;
+
+    assert_eq!(saved_locale(&app).await, "en");
+    assert_eq!(email["Subject"], "Welcome");
+    assert!(email["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Welcome to our newsletter!"));
+}
+
+#[sqlx::test]
+async fn issues_and_subscription_pages_are_in_the_subscribers_language(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    subscribe(&app, "name=Max&email=max%40example.com&locale=de", "en").await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    get_html(app.get_confirmation_links(email_request).html).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains(">Diese E-Mail im Browser anzeigen</a>"));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Diese E-Mail im Browser anzeigen: "));
+
+    let token = sqlx::query!("SELECT subscription_token FROM subscription_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .subscription_token;
+    let opt_out_url = format!(
+        "{}/subscriptions/tracking-opt-out?subscription_token={}",
+        app.address, token
+    );
+    let html_page = get_html(&opt_out_url).await;
+    assert!(html_page.contains(r#"<html lang="de">"#));
+    assert!(html_page.contains("<title>Tracking deaktivieren</title>"));
+    let response = reqwest::Client::new()
+        .post(&opt_out_url)
+        .send()
+        .await
+        .unwrap();
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<title>Tracking deaktiviert</title>"));
+    let unsubscribe_url = format!(
+        "{}/subscriptions/unsubscribe?subscription_token={}",
+        app.address, token
+    );
+    let html_page = get_html(&unsubscribe_url).await;
+    assert!(html_page.contains("<title>Abmelden</title>"));
+    assert!(html_page.contains(r#"<button type="submit">Abmelden</button>"#));
+    let response = reqwest::Client::new()
+        .post(&unsubscribe_url)
+        .send()
+        .await
+        .unwrap();
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<title>Abgemeldet</title>"));
+    assert!(html_page.contains("Sie wurden abgemeldet und erhalten keine weiteren Ausgaben."));
+}
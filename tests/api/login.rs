@@ -1,6 +1,6 @@
 use sqlx::SqlitePool;
 
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
 
 #[sqlx::test]
 async fn an_error_flash_message_is_set_on_failure(pool: SqlitePool) {
@@ -36,3 +36,127 @@ async fn redirect_to_admin_dashboard_after_login_success(pool: SqlitePool) {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+async fn fail_login(app: &TestApp, username: &str) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": username,
+            "password": "wrong-password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+async fn insert_failed_attempts(app: &TestApp, username: &str, count: usize, seconds_ago: i64) {
+    for _ in 0..count {
+        let id = uuid::Uuid::new_v4().to_string();
+        sqlx::query!(
+            r#"
+            INSERT INTO failed_login_attempts (failed_login_attempt_id, username, ip_address, attempted_at)
+            VALUES ($1, $2, '127.0.0.1', unixepoch() - $3)
+            "#,
+            id,
+            username,
+            seconds_ago
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+}
+
+async fn assert_login_is_throttled(app: &TestApp) {
+    // Even the right password has to wait.
+    let response = app.post_login(&app.test_user.credentials()).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(
+        html_page.contains("<p><i>Too many failed login attempts, please try again later.</i></p>")
+    );
+}
+
+#[sqlx::test]
+async fn failed_logins_are_recorded(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    fail_login(&app, &app.test_user.username).await;
+    fail_login(&app, "nobody-by-that-name").await;
+
+    let recorded = sqlx::query!(
+        r#"SELECT username, ip_address FROM failed_login_attempts ORDER BY attempted_at"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(recorded.len(), 2);
+    assert!(recorded.iter().all(|r| r.ip_address == "127.0.0.1"));
+}
+
+#[sqlx::test]
+async fn repeated_failures_delay_the_next_attempt(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    // Failures are read from the database, so they survive a restart.
+    insert_failed_attempts(&app, &app.test_user.username, 6, 0).await;
+
+    assert_login_is_throttled(&app).await;
+}
+
+#[sqlx::test]
+async fn throttling_does_not_reveal_whether_a_username_exists(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_failed_attempts(&app, "nobody-by-that-name", 6, 0).await;
+    insert_failed_attempts(&app, &app.test_user.username, 6, 0).await;
+
+    fail_login(&app, "nobody-by-that-name").await;
+    let unknown_page = app.get_login_html().await;
+    fail_login(&app, &app.test_user.username).await;
+    let known_page = app.get_login_html().await;
+
+    assert!(unknown_page.contains("Too many failed login attempts"));
+    assert_eq!(unknown_page, known_page);
+}
+
+#[sqlx::test]
+async fn too_many_failures_lock_the_account_out(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_failed_attempts(&app, &app.test_user.username, 10, 5 * 60).await;
+
+    assert_login_is_throttled(&app).await;
+}
+
+#[sqlx::test]
+async fn old_failures_are_forgotten(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_failed_attempts(&app, &app.test_user.username, 10, 2 * 60 * 60).await;
+
+    let response = app.post_login(&app.test_user.credentials()).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[sqlx::test]
+async fn one_address_trying_many_usernames_is_throttled(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    for i in 0..12 {
+        insert_failed_attempts(&app, &format!("guess-{i}"), 1, 0).await;
+    }
+
+    assert_login_is_throttled(&app).await;
+}
+
+#[sqlx::test]
+async fn a_successful_login_clears_previous_failures(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    for _ in 0..2 {
+        fail_login(&app, &app.test_user.username).await;
+    }
+    let response = app.post_login(&app.test_user.credentials()).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let remaining = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM failed_login_attempts WHERE username = $1"#,
+        app.test_user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining.count, 0);
+}
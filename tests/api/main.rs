@@ -1,7 +1,27 @@
 mod admin_dashboard;
+mod analytics;
+mod archive;
 mod change_password;
+mod confirmation_email;
+mod csrf;
+mod feeds;
 mod health_check;
 mod helpers;
+mod html_escaping;
+mod layout;
+mod localization;
 mod login;
 mod newsletter;
+mod password_reset;
+mod reauthentication;
+mod roles;
+mod security_headers;
+mod session_cache;
+mod session_cleanup;
+mod session_expiry;
+mod sessions;
 mod subscriptions;
+mod templates;
+mod tracking;
+mod two_factor;
+mod users;
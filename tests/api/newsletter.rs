@@ -1,59 +1,15 @@
 use std::time::Duration;
 
-use fake::{
-    faker::{internet::en::SafeEmail, name::en::Name},
-    Fake,
-};
 use sqlx::SqlitePool;
 use wiremock::{
     matchers::{any, method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
-
-/// Use the public API of the application under test to create an unconfirmed subscriber.
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(&serde_json::json!({
-        "name": name,
-        "email": email
-    }))
-    .unwrap();
-    let _mock_guard = Mock::given(path("/email"))
-        .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
-        .await;
-
-    app.post_subscriptions(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
-
-    // We now inspect the request received by the mock Postmakr server to retrieve the confirmation link and return it
-    let email_req = &app
-        .email_server
-        .received_requests()
-        .await
-        .unwrap()
-        .pop()
-        .unwrap();
-    app.get_confirmation_links(&email_req)
-}
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    // We can then reuse the same helper and add an extra step to actually call the confirmation link!
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
-    reqwest::get(confirmation_link.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    TestApp,
+};
 
 #[sqlx::test]
 async fn newsletters_are_not_delivered_to_unconfirmed_subscribers(pool: SqlitePool) {
@@ -232,3 +188,637 @@ async fn concurrent_form_submission_is_handled_gracefully(pool: SqlitePool) {
 
     app.dispatch_all_pending_emails().await;
 }
+
+fn a_week_from_now() -> String {
+    (chrono::Utc::now() + chrono::Duration::days(7))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string()
+}
+
+async fn schedule_newsletter(app: &TestApp) -> String {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter Title",
+        "text_content": "Newsletter body as plain test",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "scheduled_at": a_week_from_now(),
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+async fn make_issue_due(app: &TestApp, issue_id: &str) {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_at = unixepoch() - 60 WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[sqlx::test]
+async fn scheduled_newsletters_are_not_delivered_before_they_are_due(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    schedule_newsletter(&app).await;
+
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("The newsletter issue has been scheduled for"));
+
+    app.publish_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[sqlx::test]
+async fn due_scheduled_newsletters_are_delivered_exactly_once(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = schedule_newsletter(&app).await;
+    make_issue_due(&app, &issue_id).await;
+
+    // Act - Run the scheduler twice, as if the process had restarted
+    app.publish_due_issues().await;
+    app.publish_due_issues().await;
+
+    let issue = sqlx::query!(
+        "SELECT status, published_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(issue.status, "published");
+    assert!(issue.published_at.is_some());
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[sqlx::test]
+async fn cancelled_newsletters_are_never_delivered(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = schedule_newsletter(&app).await;
+    let response = app.post_cancel_issue(&issue_id).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    let html_page = app.get_issues_html().await;
+    assert!(html_page.contains("<p><i>The newsletter issue has been cancelled.</i></p>"));
+
+    make_issue_due(&app, &issue_id).await;
+    app.publish_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[sqlx::test]
+async fn cancelled_newsletters_can_be_rescheduled(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = schedule_newsletter(&app).await;
+    app.post_cancel_issue(&issue_id).await;
+
+    let response = app
+        .post_reschedule_issue(
+            &issue_id,
+            &serde_json::json!({ "scheduled_at": a_week_from_now() }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    let html_page = app.get_issues_html().await;
+    assert!(html_page.contains("The newsletter issue has been scheduled for"));
+
+    make_issue_due(&app, &issue_id).await;
+    app.publish_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[sqlx::test]
+async fn newsletters_cannot_be_scheduled_in_the_past(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter Title",
+        "text_content": "Newsletter body as plain test",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "scheduled_at": "2020-01-01T09:00",
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>2020-01-01T09:00 is not in the future.</i></p>"));
+
+    let n_issues = sqlx::query!("SELECT COUNT(*) AS count FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+}
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_manage_issues(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app.post_cancel_issue("an-issue").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn test_sends_are_delivered_without_touching_the_delivery_queue(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_test_newsletter(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain test",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "test_recipients": "editor@example.com, seed@example.com",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(
+        html_page.contains("A test email has been sent to editor@example.com, seed@example.com.")
+    );
+    // The draft is rendered back into the form
+    assert!(html_page.contains("&lt;p&gt;Newsletter body as HTML&lt;/p&gt;"));
+
+    let received = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&received.last().unwrap().body).unwrap();
+    let html_body = htmlescape::decode_html(body["HtmlBody"].as_str().unwrap()).unwrap();
+    assert!(html_body.ends_with("\n<p>Newsletter body as HTML</p>"));
+    // The draft is not in the archive yet: its view in browser link goes to the archive itself.
+    assert!(html_body.contains(&format!(
+        r#"<a href="{}/issues">View this email in your browser</a>"#,
+        app.base_url
+    )));
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(text_body.starts_with(&format!(
+        "View this email in your browser: {}/issues\n",
+        app.base_url
+    )));
+
+    let n_issues = sqlx::query!("SELECT COUNT(*) AS count FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+    let n_tasks = sqlx::query!("SELECT COUNT(*) AS count FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tasks, 0);
+}
+
+#[sqlx::test]
+async fn test_sends_reject_invalid_addresses(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_test_newsletter(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain test",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "test_recipients": "editor@example.com, not-an-email",
+        }))
+        .await;
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("not-an-email is not a valid subscriber email."));
+}
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_send_a_test_newsletter(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app
+        .post_test_newsletter(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain test",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "test_recipients": "editor@example.com",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn markdown_newsletters_are_delivered_as_html_and_plain_text(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let markdown =
+        "# Issue 1\n\nRead [the post](https://example.com/post).\n\n<script>alert('x')</script>";
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter Title",
+            "markdown_content": markdown,
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let issue =
+        sqlx::query!("SELECT markdown_content, html_content, text_content FROM newsletter_issues")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(issue.markdown_content.as_deref(), Some(markdown));
+
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(html_body.ends_with(&issue.html_content));
+    assert!(html_body.contains("<h1>Issue 1</h1>"));
+    assert!(!html_body.contains("<script"));
+    assert!(text_body.ends_with(&issue.text_content));
+    assert!(text_body.contains("Read the post (https://example.com/post)."));
+}
+
+#[sqlx::test]
+async fn markdown_and_html_content_cannot_be_mixed(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter Title",
+            "markdown_content": "# Issue 1",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>Use either Markdown or the HTML and plain text fields, not both.</i></p>"
+    ));
+}
+
+#[sqlx::test]
+async fn layouts_and_content_are_personalised_for_each_subscriber(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    app.post_template(&serde_json::json!({
+        "name": "Plain",
+        "html_layout": r#"<main>{{ content }}</main><a href="{{ unsubscribe_url }}">Unsubscribe</a>"#,
+        "text_layout": "{{ content }}\n\nUnsubscribe: {{ unsubscribe_url }}",
+    }))
+    .await;
+    let template = sqlx::query!("SELECT template_id FROM email_templates WHERE name = 'Plain'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Hi {{ name }}!",
+            "html_content": "<p>Hi {{name}}!</p>",
+            "template_id": template.template_id,
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let subscriber = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = htmlescape::decode_html(body["HtmlBody"].as_str().unwrap()).unwrap();
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(html_body.contains("<main><p>Hi "));
+    assert!(html_body.contains("/subscriptions/unsubscribe?subscription_token="));
+    assert!(text_body.contains(&format!("Hi {}!", subscriber.name)));
+
+    let mut finder = linkify::LinkFinder::new();
+    finder.kinds(&[linkify::LinkKind::Url]);
+    let links: Vec<_> = finder.links(text_body).collect();
+    // The link to the archive comes first, then the one to unsubscribe.
+    assert_eq!(links.len(), 2);
+    let mut unsubscribe_link = reqwest::Url::parse(links[1].as_str()).unwrap();
+    assert_eq!(unsubscribe_link.host_str().unwrap(), "127.0.0.1");
+    unsubscribe_link.set_port(Some(app.port)).unwrap();
+    // Following the link only asks for a confirmation, e.g. in case a mail scanner fetched it
+    let response = reqwest::get(unsubscribe_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains(r#"<form method="post">"#));
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "confirmed");
+
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "unsubscribed");
+}
+
+#[sqlx::test]
+async fn unsubscribed_subscribers_do_not_receive_later_issues(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+}
+
+#[sqlx::test]
+async fn unknown_merge_variables_are_rejected_at_publish(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Hi {{ nmae }}",
+            "html_content": "<p>Hi {{ nmae }}</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("`{{ nmae }}` is not a known merge variable."));
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(issues.is_empty());
+}
+
+#[sqlx::test]
+async fn unsubscribing_with_an_unknown_token_is_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let url = format!(
+        "{}/subscriptions/unsubscribe?subscription_token=unknown",
+        app.address
+    );
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = reqwest::Client::new().post(&url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[sqlx::test]
+async fn published_html_is_sanitised_inlined_and_uses_absolute_urls(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": r#"<style>p { color: red; }</style><p onclick="steal()">Read <a href="/issues">the archive</a></p><script>alert("x")</script>"#,
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let issue = sqlx::query!("SELECT html_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        issue.html_content,
+        format!(
+            r#"<p style="color: red;">Read <a href="{}/issues" rel="noopener noreferrer">the archive</a></p>"#,
+            app.base_url
+        )
+    );
+}
+
+#[sqlx::test]
+async fn the_preview_shows_the_rendered_email_and_its_warnings(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let paragraph = "<p>Lorem ipsum dolor sit amet.</p>";
+    let response = app
+        .post_preview_newsletter(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Hi {{ name }}",
+            "html_content": format!("<p>Hi {{{{ name }}}}</p><script></script>{}", paragraph.repeat(4000)),
+            "template_id": "",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Gmail clips messages larger than 102KB."));
+    // Merge values are entity-encoded, then the whole email is escaped into the iframe
+    assert!(html_page.contains("&lt;p&gt;Hi Test&amp;#x20;Subscriber&lt;/p&gt;&lt;p&gt;Lorem"));
+    assert!(html_page.contains("Hi Test Subscriber</pre>"));
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(issues.is_empty());
+}
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_preview_a_newsletter(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app
+        .post_preview_newsletter(&serde_json::json!({
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn every_email_links_to_its_page_in_the_archive(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(&serde_json::json!({
+        "title": "Newsletter Title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let issue = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let issue_url = format!("{}/issues/{}", app.base_url, issue.newsletter_issue_id);
+    let html_body = htmlescape::decode_html(body["HtmlBody"].as_str().unwrap()).unwrap();
+    assert!(html_body.contains(&format!(
+        r#"<a href="{issue_url}">View this email in your browser</a>"#
+    )));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with(&format!("View this email in your browser: {issue_url}")));
+
+    let response = app.get_archived_issue(&issue.newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<p>Newsletter body as HTML</p>"));
+}
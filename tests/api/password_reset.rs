@@ -0,0 +1,254 @@
+use sqlx::SqlitePool;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+const EMAIL: &str = "admin@example.com";
+
+async fn give_test_user_an_email(app: &TestApp) {
+    sqlx::query!(
+        "UPDATE users SET email = $1 WHERE user_id = $2",
+        EMAIL,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+/// Wait for `count` reset emails: they are sent after the response.
+async fn sent_emails(app: &TestApp, count: usize) -> Vec<wiremock::Request> {
+    for _ in 0..50 {
+        let requests = app.email_server.received_requests().await.unwrap();
+        if requests.len() >= count {
+            return requests;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("Fewer than {count} emails were sent.");
+}
+
+/// Ask for a reset link for the test user and return the token it carries.
+async fn request_reset_token(app: &TestApp) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app.post_forgot_password(&app.test_user.username).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let email_request = sent_emails(app, 1).await.pop().unwrap();
+    let links = app.get_confirmation_links(&email_request);
+    assert_eq!(links.html.path(), "/login/reset-password");
+    links
+        .html
+        .query_pairs()
+        .find(|(key, _)| key == "reset_token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+fn reset_form(token: &str, password: &str) -> serde_json::Value {
+    serde_json::json!({
+        "reset_token": token,
+        "new_password": password,
+        "new_password_check": password
+    })
+}
+
+#[sqlx::test]
+async fn the_login_page_links_to_the_forgot_password_form(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(r#"href="/login/forgot-password""#));
+}
+
+#[sqlx::test]
+async fn the_response_is_the_same_whether_or_not_the_username_exists(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    give_test_user_an_email(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let known = app.post_forgot_password(&app.test_user.username).await;
+    let known_status = known.status();
+    let known_location = known.headers().get("Location").cloned();
+    let known_page = app.get_login_html().await;
+
+    let unknown = app.post_forgot_password("nobody-by-that-name").await;
+    assert_eq!(unknown.status(), known_status);
+    assert_eq!(unknown.headers().get("Location").cloned(), known_location);
+    assert_eq!(app.get_login_html().await, known_page);
+    assert!(known_page.contains("a link to reset its password has been sent"));
+    sent_emails(&app, 1).await;
+}
+
+#[sqlx::test]
+async fn no_email_is_sent_to_users_without_an_email_address(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_forgot_password(&app.test_user.username).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn a_reset_link_sets_a_new_password(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    give_test_user_an_email(&app).await;
+    let token = request_reset_token(&app).await;
+
+    let response = reqwest::get(format!(
+        "{}/login/reset-password?reset_token={}",
+        app.address, token
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_password = uuid::Uuid::new_v4().to_string();
+    let response = app
+        .post_reset_password(&reset_form(&token, &new_password))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your password has been reset, you can now log in.</i></p>"));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[sqlx::test]
+async fn a_reset_link_can_only_be_used_once(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    give_test_user_an_email(&app).await;
+    let token = request_reset_token(&app).await;
+
+    let password = uuid::Uuid::new_v4().to_string();
+    app.post_reset_password(&reset_form(&token, &password))
+        .await;
+
+    let response = app
+        .post_reset_password(&reset_form(&token, "another-long-password"))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+    let response = reqwest::get(format!(
+        "{}/login/reset-password?reset_token={}",
+        app.address, token
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[sqlx::test]
+async fn an_expired_reset_link_is_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    give_test_user_an_email(&app).await;
+    let token = request_reset_token(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = unixepoch() - 1")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_reset_password(&reset_form(&token, "a-long-enough-password"))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[sqlx::test]
+async fn new_passwords_must_match_and_be_long_enough(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    give_test_user_an_email(&app).await;
+    let token = request_reset_token(&app).await;
+    let form_url = format!("/login/reset-password?reset_token={token}");
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "reset_token": &token,
+            "new_password": "a-long-enough-password",
+            "new_password_check": "another-long-enough-password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, &form_url);
+
+    let response = app.post_reset_password(&reset_form(&token, "short")).await;
+    assert_is_redirect_to(&response, &form_url);
+
+    // The link was not used up.
+    let response = app
+        .post_reset_password(&reset_form(&token, "a-long-enough-password"))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn resetting_a_password_logs_the_user_out_everywhere(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    give_test_user_an_email(&app).await;
+    app.test_user.login(&app).await;
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+
+    let token = request_reset_token(&app).await;
+    let password = uuid::Uuid::new_v4().to_string();
+    let response = app
+        .post_reset_password(&reset_form(&token, &password))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn too_many_reset_requests_are_ignored_without_locking_the_user_out(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    give_test_user_an_email(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..5 {
+        let response = app.post_forgot_password(&app.test_user.username).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+    sent_emails(&app, 3).await;
+    // Give an unexpected fourth email the time to be sent.
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 3);
+
+    let response = app.post_login(&app.test_user.credentials()).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -0,0 +1,140 @@
+use sqlx::SqlitePool;
+
+use crate::{
+    helpers::{assert_is_redirect_to, csrf_token, spawn_app, TestApp},
+    two_factor::enable_two_factor,
+};
+
+/// Log in, then let the reauthentication window pass.
+async fn log_in_a_while_ago(app: &TestApp) {
+    app.test_user.login(app).await;
+    app.age_sessions("authenticated_at", 5 * 60).await;
+}
+
+#[sqlx::test]
+async fn a_recent_login_does_not_have_to_reauthenticate(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_change_password().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[sqlx::test]
+async fn sensitive_pages_ask_for_the_password_again_after_a_while(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_a_while_ago(&app).await;
+
+    let response = app.get_change_password().await;
+    assert_is_redirect_to(&response, "/admin/reauthenticate?next=%2Fadmin%2Fpassword");
+
+    // Browsing the rest of the admin pages is not affected.
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[sqlx::test]
+async fn a_sensitive_form_goes_back_to_the_page_it_was_sent_from(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_a_while_ago(&app).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/newsletters", &app.address))
+        .header("Referer", format!("{}/admin/newsletters", &app.address))
+        .form(&serde_json::json!({
+            "csrf_token": csrf_token(&app.api_client, &app.address).await,
+            "title": "Newsletter Title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(
+        &response,
+        "/admin/reauthenticate?next=%2Fadmin%2Fnewsletters",
+    );
+}
+
+#[sqlx::test]
+async fn reauthenticating_goes_on_to_the_next_page(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_a_while_ago(&app).await;
+
+    let html_page = app.get_reauthenticate_html("/admin/password").await;
+    assert!(html_page.contains(r#"name="next" value="/admin/password""#));
+    assert!(!html_page.contains(r#"name="code""#));
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({
+            "password": &app.test_user.password,
+            "next": "/admin/password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+    let response = app.get_change_password().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[sqlx::test]
+async fn a_wrong_password_does_not_reauthenticate(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_a_while_ago(&app).await;
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({
+            "password": "wrong-password",
+            "next": "/admin/password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/reauthenticate?next=%2Fadmin%2Fpassword");
+
+    let html_page = app.get_reauthenticate_html("/admin/password").await;
+    assert!(html_page.contains("<p><i>The password is incorrect.</i></p>"));
+    let response = app.get_change_password().await;
+    assert_is_redirect_to(&response, "/admin/reauthenticate?next=%2Fadmin%2Fpassword");
+}
+
+#[sqlx::test]
+async fn reauthenticating_never_redirects_outside_the_admin_pages(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_a_while_ago(&app).await;
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({
+            "password": &app.test_user.password,
+            "next": "https://evil.example.com/admin/"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[sqlx::test]
+async fn reauthenticating_requires_the_second_factor_when_enabled(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+    app.age_sessions("authenticated_at", 5 * 60).await;
+
+    let html_page = app.get_reauthenticate_html("/admin/password").await;
+    assert!(html_page.contains(r#"name="code""#));
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({
+            "password": &app.test_user.password,
+            "next": "/admin/password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/reauthenticate?next=%2Fadmin%2Fpassword");
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({
+            "password": &app.test_user.password,
+            "code": &recovery_codes[0],
+            "next": "/admin/password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+}
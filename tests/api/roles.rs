@@ -0,0 +1,133 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{assert_is_redirect_to, post_form, spawn_app, TestApp, TestUser};
+
+/// Log in as a new user with the given role instead of the test user.
+async fn log_in_as(app: &TestApp, role: &'static str) -> TestUser {
+    let user = TestUser::with_role(role);
+    user.store(&app.db_pool).await;
+    user.login(app).await;
+    user
+}
+
+fn assert_is_forbidden(response: &reqwest::Response) {
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+async fn publish(app: &TestApp) -> reqwest::Response {
+    app.post_newsletters(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await
+}
+
+#[sqlx::test]
+async fn viewers_can_look_but_not_publish(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_as(&app, "viewer").await;
+
+    let response = publish(&app).await;
+    assert_is_forbidden(&response);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("You are not allowed to do this."));
+
+    assert_is_forbidden(&app.get_publish_newsletter().await);
+    assert_is_forbidden(&app.get_analytics("").await);
+    let issues = app
+        .api_client
+        .get(format!("{}/admin/issues", app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(issues.status().as_u16(), 200);
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("You are viewer."));
+    assert!(!html_page.contains(r#"href="/admin/newsletters""#));
+    assert!(!html_page.contains(r#"href="/admin/users""#));
+}
+
+#[sqlx::test]
+async fn analysts_can_see_analytics_but_not_publish(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_as(&app, "analyst").await;
+
+    assert_eq!(app.get_analytics("").await.status().as_u16(), 200);
+    assert_eq!(app.get_analytics(".json").await.status().as_u16(), 200);
+    assert_is_forbidden(&publish(&app).await);
+}
+
+#[sqlx::test]
+async fn editors_can_publish_but_not_manage_users(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_as(&app, "editor").await;
+
+    assert_is_redirect_to(&publish(&app).await, "/admin/newsletters");
+    assert_is_forbidden(
+        &app.api_client
+            .get(format!("{}/admin/users", app.address))
+            .send()
+            .await
+            .unwrap(),
+    );
+    assert_is_forbidden(&app.post_invitation("colleague@example.com", "owner").await);
+    assert_is_forbidden(&app.post_change_role(&app.test_user.user_id, "viewer").await);
+    assert_is_forbidden(&app.post_deactivate_user(&app.test_user.user_id).await);
+}
+
+#[sqlx::test]
+async fn role_changes_apply_right_away(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let editor = log_in_as(&app, "editor").await;
+    assert_is_redirect_to(&publish(&app).await, "/admin/newsletters");
+
+    // The owner demotes them, in another session
+    let owner = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    post_form(&owner, &app.address, "/login", &app.test_user.credentials()).await;
+    let response = post_form(
+        &owner,
+        &app.address,
+        &format!("/admin/users/{}/role", editor.user_id),
+        &serde_json::json!({ "role": "viewer" }),
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    assert_is_forbidden(&publish(&app).await);
+}
+
+#[sqlx::test]
+async fn owners_cannot_change_their_own_role(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_change_role(&app.test_user.user_id, "viewer").await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    assert!(app
+        .get_users_html()
+        .await
+        .contains("<p><i>You cannot change your own role.</i></p>"));
+}
+
+#[sqlx::test]
+async fn unknown_roles_are_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_change_role(&viewer.user_id, "superuser").await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .post_invitation("colleague@example.com", "superuser")
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
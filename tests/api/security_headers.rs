@@ -0,0 +1,70 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{insert_issue, spawn_app, TestUser};
+
+fn header<'a>(response: &'a reqwest::Response, name: &str) -> &'a str {
+    response
+        .headers()
+        .get(name)
+        .unwrap_or_else(|| panic!("The response has no {name} header."))
+        .to_str()
+        .unwrap()
+}
+
+fn assert_is_strict(response: &reqwest::Response) {
+    assert!(header(response, "Content-Security-Policy").contains("frame-ancestors 'none'"));
+    assert_eq!(header(response, "X-Frame-Options"), "DENY");
+    assert_eq!(header(response, "X-Content-Type-Options"), "nosniff");
+    assert_eq!(header(response, "Referrer-Policy"), "same-origin");
+    // The test configuration runs over plain HTTP.
+    assert!(response
+        .headers()
+        .get("Strict-Transport-Security")
+        .is_none());
+}
+
+#[sqlx::test]
+async fn admin_and_login_pages_get_strict_headers(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_strict(&response);
+
+    app.test_user.login(&app).await;
+    let response = app.get_admin_dashboard().await;
+    assert_is_strict(&response);
+}
+
+#[sqlx::test]
+async fn error_responses_get_the_headers_too(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.post_login(&viewer.credentials()).await;
+
+    let response = app.get_publish_newsletter().await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_is_strict(&response);
+}
+
+#[sqlx::test]
+async fn the_public_archive_gets_a_relaxed_policy(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    insert_issue(&app.db_pool, "0H5N0000000A1", "An issue", "published").await;
+
+    let response = app.get_archived_issue("0H5N0000000A1").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let csp = header(&response, "Content-Security-Policy");
+    // Issues embed remote images and inline styles.
+    assert!(csp.contains("img-src *"));
+    assert!(csp.contains("style-src 'unsafe-inline'"));
+    assert_eq!(header(&response, "X-Frame-Options"), "SAMEORIGIN");
+    assert_eq!(header(&response, "X-Content-Type-Options"), "nosniff");
+}
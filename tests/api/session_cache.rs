@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use actix_session::storage::{SessionKey, SessionStore};
+use actix_web::cookie::time::Duration;
+use sqlx::SqlitePool;
+use zero2prod::session::SqlxSqliteSessionStore;
+
+fn cached_store(pool: &SqlitePool, capacity: usize) -> SqlxSqliteSessionStore {
+    SqlxSqliteSessionStore::builder_pooled(pool.clone())
+        .cache_capacity(capacity)
+        .build()
+}
+
+/// A session state holding a single JSON-encoded value.
+fn state(key: &str, value: &str) -> HashMap<String, String> {
+    HashMap::from([(key.to_owned(), serde_json::to_string(value).unwrap())])
+}
+
+async fn save(store: &SqlxSqliteSessionStore, state: HashMap<String, String>) -> SessionKey {
+    store.save(state, &Duration::hours(1)).await.unwrap()
+}
+
+/// Change a session behind the back of the store.
+async fn overwrite_in_database(
+    pool: &SqlitePool,
+    key: &SessionKey,
+    state: HashMap<String, String>,
+) {
+    let key = key.as_ref();
+    let body = serde_json::to_string(&state).unwrap();
+    sqlx::query!(
+        r#"UPDATE sessions SET session = $1, expires = unixepoch() + 3600 WHERE id = $2"#,
+        body,
+        key
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+async fn stored_expiry(pool: &SqlitePool, key: &SessionKey) -> i64 {
+    let key = key.as_ref();
+    sqlx::query_scalar!(r#"SELECT expires FROM sessions WHERE id = $1"#, key)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn cached_sessions_are_loaded_from_memory(pool: SqlitePool) {
+    let store = cached_store(&pool, 10);
+    let key = save(&store, state("user_id", "cached")).await;
+    overwrite_in_database(&pool, &key, state("user_id", "stored")).await;
+
+    let loaded = store.load(&key).await.unwrap();
+    assert_eq!(loaded, Some(state("user_id", "cached")));
+
+    // Without a cache, every load reads the database.
+    let uncached_store = SqlxSqliteSessionStore::new_pooled(pool.clone());
+    let loaded = uncached_store.load(&key).await.unwrap();
+    assert_eq!(loaded, Some(state("user_id", "stored")));
+}
+
+#[sqlx::test]
+async fn updates_are_written_through_to_the_database(pool: SqlitePool) {
+    let store = cached_store(&pool, 10);
+    let key = save(&store, state("user_id", "before")).await;
+
+    let key = store
+        .update(key, state("user_id", "after"), &Duration::hours(1))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.load(&key).await.unwrap(),
+        Some(state("user_id", "after"))
+    );
+    let uncached_store = SqlxSqliteSessionStore::new_pooled(pool.clone());
+    assert_eq!(
+        uncached_store.load(&key).await.unwrap(),
+        Some(state("user_id", "after"))
+    );
+}
+
+#[sqlx::test]
+async fn deleted_sessions_are_evicted(pool: SqlitePool) {
+    let store = cached_store(&pool, 10);
+    let key = save(&store, state("user_id", "someone")).await;
+    let other_key = save(&store, state("user_id", "someone-else")).await;
+
+    store.delete(&key).await.unwrap();
+    store.delete_user_sessions("someone-else").await.unwrap();
+
+    assert_eq!(store.load(&key).await.unwrap(), None);
+    assert_eq!(store.load(&other_key).await.unwrap(), None);
+}
+
+#[sqlx::test]
+async fn the_least_recently_used_sessions_are_evicted(pool: SqlitePool) {
+    let store = cached_store(&pool, 1);
+    let key = save(&store, state("user_id", "first")).await;
+    save(&store, state("user_id", "second")).await;
+    overwrite_in_database(&pool, &key, state("user_id", "stored")).await;
+
+    let loaded = store.load(&key).await.unwrap();
+    assert_eq!(loaded, Some(state("user_id", "stored")));
+}
+
+#[sqlx::test]
+async fn expired_sessions_are_not_served_from_the_cache(pool: SqlitePool) {
+    let store = cached_store(&pool, 10);
+    let key = store
+        .save(state("user_id", "cached"), &Duration::seconds(1))
+        .await
+        .unwrap();
+
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    assert_eq!(store.load(&key).await.unwrap(), None);
+
+    // The expired entry is gone: a session revived in the database is read from there.
+    overwrite_in_database(&pool, &key, state("user_id", "stored")).await;
+    let loaded = store.load(&key).await.unwrap();
+    assert_eq!(loaded, Some(state("user_id", "stored")));
+}
+
+#[sqlx::test]
+async fn small_expiry_extensions_are_not_written(pool: SqlitePool) {
+    let store = SqlxSqliteSessionStore::builder_pooled(pool.clone())
+        .cache_capacity(10)
+        .expiry_write_threshold(60)
+        .build();
+    let key = save(&store, HashMap::new()).await;
+    let expires = stored_expiry(&pool, &key).await;
+
+    store
+        .update_ttl(&key, &(Duration::hours(1) + Duration::seconds(30)))
+        .await
+        .unwrap();
+    assert_eq!(stored_expiry(&pool, &key).await, expires);
+
+    store
+        .update_ttl(&key, &(Duration::hours(1) + Duration::seconds(120)))
+        .await
+        .unwrap();
+    assert!(stored_expiry(&pool, &key).await >= expires + 120);
+}
+
+#[sqlx::test]
+async fn without_a_cache_every_expiry_extension_is_written(pool: SqlitePool) {
+    let store = SqlxSqliteSessionStore::builder_pooled(pool.clone())
+        .expiry_write_threshold(60)
+        .build();
+    let key = save(&store, HashMap::new()).await;
+    let expires = stored_expiry(&pool, &key).await;
+
+    store
+        .update_ttl(&key, &(Duration::hours(1) + Duration::seconds(30)))
+        .await
+        .unwrap();
+    assert!(stored_expiry(&pool, &key).await >= expires + 30);
+}
+
+#[sqlx::test]
+async fn unwritten_expiry_extensions_keep_the_session_alive(pool: SqlitePool) {
+    let store = SqlxSqliteSessionStore::builder_pooled(pool.clone())
+        .cache_capacity(10)
+        .expiry_write_threshold(60)
+        .build();
+    let key = store
+        .save(state("user_id", "active"), &Duration::seconds(2))
+        .await
+        .unwrap();
+    let expires = stored_expiry(&pool, &key).await;
+
+    // Still in use after a second: the session now lasts four more seconds.
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    store.update_ttl(&key, &Duration::seconds(4)).await.unwrap();
+    assert_eq!(stored_expiry(&pool, &key).await, expires);
+
+    // Past the stored expiry, within the extended one.
+    tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+    assert_eq!(
+        store.load(&key).await.unwrap(),
+        Some(state("user_id", "active"))
+    );
+}
+
+#[sqlx::test]
+async fn updating_a_deleted_session_fails_and_evicts_it(pool: SqlitePool) {
+    let store = cached_store(&pool, 10);
+    let key = save(&store, state("user_id", "someone")).await;
+    // Deleted behind the back of the store.
+    SqlxSqliteSessionStore::new_pooled(pool.clone())
+        .delete(&key)
+        .await
+        .unwrap();
+
+    let same_key: SessionKey = key.as_ref().to_owned().try_into().unwrap();
+    let result = store
+        .update(same_key, state("user_id", "someone"), &Duration::hours(1))
+        .await;
+    assert!(result.is_err());
+    assert_eq!(store.load(&key).await.unwrap(), None);
+}
@@ -0,0 +1,108 @@
+use std::{
+    num::{NonZeroU32, NonZeroU64},
+    time::Duration,
+};
+
+use sqlx::SqlitePool;
+use zero2prod::{
+    configuration::SessionCleanupSettings,
+    session::SqlxSqliteSessionStore,
+    session_cleanup::{delete_expired_sessions, run_session_cleanup},
+};
+
+use crate::helpers::spawn_app;
+
+async fn insert_session(pool: &SqlitePool, id: &str, expires_in: i64) {
+    sqlx::query!(
+        r#"INSERT INTO sessions (id, session, expires) VALUES ($1, '{}', unixepoch() + $2)"#,
+        id,
+        expires_in
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+async fn session_ids(pool: &SqlitePool) -> Vec<String> {
+    sqlx::query_scalar!(r#"SELECT id FROM sessions ORDER BY id"#)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn only_expired_sessions_are_deleted(pool: SqlitePool) {
+    insert_session(&pool, "expired", -10).await;
+    insert_session(&pool, "live", 3600).await;
+    let session_store = SqlxSqliteSessionStore::new_pooled(pool.clone());
+
+    let deleted = delete_expired_sessions(&session_store, NonZeroU32::new(100).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(deleted, 1);
+    assert_eq!(session_ids(&pool).await, vec!["live"]);
+}
+
+#[sqlx::test]
+async fn expired_sessions_are_deleted_in_batches(pool: SqlitePool) {
+    for i in 0..5 {
+        insert_session(&pool, &format!("expired-{i}"), -10).await;
+    }
+    insert_session(&pool, "live", 3600).await;
+    let session_store = SqlxSqliteSessionStore::new_pooled(pool.clone());
+
+    assert_eq!(session_store.cleanup(2).await.unwrap(), 2);
+    assert_eq!(
+        delete_expired_sessions(&session_store, NonZeroU32::new(2).unwrap())
+            .await
+            .unwrap(),
+        3
+    );
+    assert_eq!(session_ids(&pool).await, vec!["live"]);
+}
+
+#[sqlx::test]
+async fn logged_in_sessions_survive_the_cleanup(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let session_store = SqlxSqliteSessionStore::new_pooled(app.db_pool.clone());
+
+    assert_eq!(
+        delete_expired_sessions(&session_store, NonZeroU32::new(100).unwrap())
+            .await
+            .unwrap(),
+        0
+    );
+
+    let expires = sqlx::query_scalar!(r#"SELECT expires FROM sessions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(expires > chrono::Utc::now().timestamp());
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[sqlx::test]
+async fn the_cleanup_job_runs_until_shutdown(pool: SqlitePool) {
+    insert_session(&pool, "expired", -10).await;
+    let session_store = SqlxSqliteSessionStore::new_pooled(pool.clone());
+    let settings = SessionCleanupSettings {
+        interval_seconds: NonZeroU64::new(3600).unwrap(),
+        batch_size: NonZeroU32::new(100).unwrap(),
+    };
+
+    // The first run is immediate, the next one would be an hour later.
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        run_session_cleanup(
+            session_store,
+            settings,
+            tokio::time::sleep(Duration::from_millis(200)),
+        ),
+    )
+    .await
+    .expect("The cleanup job did not stop on shutdown.");
+
+    assert!(session_ids(&pool).await.is_empty());
+}
@@ -0,0 +1,77 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[sqlx::test]
+async fn the_session_cookie_is_hardened(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app.post_login(&app.test_user.credentials()).await;
+
+    let cookie = response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|h| h.to_str().unwrap())
+        .find(|c| c.starts_with("id="))
+        .expect("The session cookie is set.");
+    assert!(cookie.contains("HttpOnly"));
+    assert!(cookie.contains("SameSite=Lax"));
+    // The test configuration runs over plain HTTP.
+    assert!(!cookie.contains("Secure"));
+}
+
+#[sqlx::test]
+async fn an_idle_session_expires(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    app.age_sessions("last_activity_at", 30 * 60).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your session has expired, please log in again.</i></p>"));
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn activity_keeps_a_session_alive(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    for _ in 0..3 {
+        app.age_sessions("last_activity_at", 25 * 60).await;
+        let response = app.get_admin_dashboard().await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[sqlx::test]
+async fn a_session_expires_after_its_absolute_lifetime_even_when_active(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    app.age_sessions("logged_in_at", 12 * 60 * 60).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn an_expired_session_is_deleted(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    app.age_sessions("last_activity_at", 30 * 60).await;
+
+    app.get_admin_dashboard().await;
+
+    let n_sessions = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) FROM sessions WHERE user_id = $1"#,
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(n_sessions, 0);
+}
@@ -0,0 +1,162 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{assert_is_redirect_to, post_form, spawn_app, TestApp, TestUser};
+
+/// Another browser, logged in as `user`.
+async fn log_in_elsewhere(app: &TestApp, user: &TestUser) -> reqwest::Client {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .user_agent("Another browser")
+        .build()
+        .unwrap();
+    let response = post_form(&client, &app.address, "/login", &user.credentials()).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    client
+}
+
+async fn dashboard_status(app: &TestApp, client: &reqwest::Client) -> u16 {
+    client
+        .get(format!("{}/admin/dashboard", app.address))
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .as_u16()
+}
+
+async fn session_handles(app: &TestApp, user_id: &str) -> Vec<String> {
+    sqlx::query_scalar!(
+        r#"SELECT handle AS "handle!" FROM sessions WHERE user_id = $1 ORDER BY created_at"#,
+        user_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_see_sessions(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/sessions", app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn sessions_are_listed_with_where_they_were_opened_from(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    log_in_elsewhere(&app, &app.test_user).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_sessions_html().await;
+    assert_eq!(html_page.matches("<td>127.0.0.1</td>").count(), 2);
+    assert!(html_page.contains("<td>Another browser</td>"));
+    assert_eq!(html_page.matches("This session").count(), 1);
+    assert_eq!(session_handles(&app, &app.test_user.user_id).await.len(), 2);
+}
+
+#[sqlx::test]
+async fn revoking_a_session_logs_it_out(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let other_browser = log_in_elsewhere(&app, &app.test_user).await;
+    let other_handle = session_handles(&app, &app.test_user.user_id).await[0].clone();
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_revoke_sessions(&format!("{other_handle}/revoke"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    assert_eq!(dashboard_status(&app, &other_browser).await, 303);
+    assert_eq!(dashboard_status(&app, &app.api_client).await, 200);
+}
+
+#[sqlx::test]
+async fn the_sessions_of_others_cannot_be_revoked(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let colleague = TestUser::with_role("owner");
+    colleague.store(&app.db_pool).await;
+    let colleague_browser = log_in_elsewhere(&app, &colleague).await;
+    let colleague_handle = session_handles(&app, &colleague.user_id).await[0].clone();
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_revoke_sessions(&format!("{colleague_handle}/revoke"))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(dashboard_status(&app, &colleague_browser).await, 200);
+}
+
+#[sqlx::test]
+async fn all_sessions_of_a_user_can_be_revoked(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let other_browser = log_in_elsewhere(&app, &app.test_user).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_revoke_sessions("revoke-all").await;
+    assert_is_redirect_to(&response, "/login");
+
+    assert_eq!(dashboard_status(&app, &other_browser).await, 303);
+    assert_eq!(dashboard_status(&app, &app.api_client).await, 303);
+}
+
+#[sqlx::test]
+async fn only_owners_can_sign_out_everyone(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&app.db_pool).await;
+    let editor_browser = log_in_elsewhere(&app, &editor).await;
+
+    let response = post_form(
+        &editor_browser,
+        &app.address,
+        "/admin/sessions/revoke-everyone",
+        &serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 403);
+    let html_page = editor_browser
+        .get(format!("{}/admin/sessions", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(!html_page.contains("Sign out everyone"));
+
+    app.test_user.login(&app).await;
+    assert!(app.get_sessions_html().await.contains("Sign out everyone"));
+    let response = app.post_revoke_sessions("revoke-everyone").await;
+    assert_is_redirect_to(&response, "/login");
+
+    assert_eq!(dashboard_status(&app, &editor_browser).await, 303);
+    assert_eq!(dashboard_status(&app, &app.api_client).await, 303);
+}
+
+#[sqlx::test]
+async fn using_a_session_updates_when_it_was_last_seen(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    sqlx::query!("UPDATE sessions SET last_seen_at = 0")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_eq!(dashboard_status(&app, &app.api_client).await, 200);
+
+    let last_seen_at = sqlx::query_scalar!(
+        r#"SELECT last_seen_at AS "last_seen_at!" FROM sessions WHERE user_id = $1"#,
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(last_seen_at > 0);
+}
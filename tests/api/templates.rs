@@ -0,0 +1,156 @@
+use sqlx::SqlitePool;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_manage_templates(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app
+        .post_template(&serde_json::json!({
+            "name": "Plain",
+            "html_layout": "{{ content }}",
+            "text_layout": "{{ content }}",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn the_default_template_is_listed(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_templates_html().await;
+
+    assert!(html_page.contains(r#"<a href="/admin/templates/0H5KD2T0CN1QP">Default</a>"#));
+}
+
+#[sqlx::test]
+async fn templates_can_be_created_and_edited(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_template(&serde_json::json!({
+            "name": "Plain",
+            "html_layout": "<main>{{ content }}</main>",
+            "text_layout": "{{ content }}",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/templates");
+    let html_page = app.get_templates_html().await;
+    assert!(html_page.contains("<p><i>The template has been created.</i></p>"));
+
+    let template = sqlx::query!("SELECT template_id FROM email_templates WHERE name = 'Plain'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app
+        .post_update_template(
+            &template.template_id,
+            &serde_json::json!({
+                "name": "Plain",
+                "html_layout": "<article>{{ content }}</article>",
+                "text_layout": "{{ content }}",
+            }),
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/templates/{}", template.template_id),
+    );
+
+    let template = sqlx::query!("SELECT html_layout FROM email_templates WHERE name = 'Plain'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(template.html_layout, "<article>{{ content }}</article>");
+}
+
+#[sqlx::test]
+async fn layouts_are_sanitised_when_saved(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_template(&serde_json::json!({
+            "name": "Scripted",
+            "html_layout": r#"<main onclick="steal()">{{ content }}</main><script>alert("x")</script>"#,
+            "text_layout": "{{ content }}",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/templates");
+
+    let template = sqlx::query!("SELECT html_layout FROM email_templates WHERE name = 'Scripted'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(template.html_layout, "<main>{{ content }}</main>");
+}
+
+#[sqlx::test]
+async fn invalid_layouts_are_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        (
+            "<main></main>",
+            "{{ content }}",
+            "The HTML layout is invalid: The template must contain `{{ content }}`.",
+        ),
+        (
+            "{{ content }}",
+            "{{ content }} {{ unsubscribe }}",
+            "The plain text layout is invalid: `{{ unsubscribe }}` is not a known merge variable.",
+        ),
+        (
+            "{{ content }} {{ name",
+            "{{ content }}",
+            "The HTML layout is invalid: A `{{` is never closed with `}}`.",
+        ),
+    ];
+
+    for (html_layout, text_layout, error_message) in test_cases {
+        let response = app
+            .post_template(&serde_json::json!({
+                "name": "Broken",
+                "html_layout": html_layout,
+                "text_layout": text_layout,
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+        let html_page = response.text().await.unwrap();
+        assert!(
+            html_page.contains(error_message),
+            "The page did not explain why {} / {} was rejected.",
+            html_layout,
+            text_layout
+        );
+    }
+    let templates = sqlx::query!("SELECT template_id FROM email_templates")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(templates.len(), 1);
+}
+
+#[sqlx::test]
+async fn template_names_must_be_unique(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_template(&serde_json::json!({
+            "name": "Default",
+            "html_layout": "{{ content }}",
+            "text_layout": "{{ content }}",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("A template named Default already exists."));
+}
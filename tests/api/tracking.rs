@@ -0,0 +1,254 @@
+use sqlx::SqlitePool;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+/// Publish an issue to the confirmed subscribers and return the HTML they received.
+/// `tracking` lists the tracking checkboxes to tick, e.g. `track_opens`.
+async fn publish_and_deliver(app: &TestApp, tracking: &[&str]) -> (String, String) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let mut body = serde_json::json!({
+        "title": "Newsletter Title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<p>Newsletter body as HTML</p><p><a href="https://example.com/post?a=1&amp;b=2">Read more</a></p>"#,
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    for checkbox in tracking {
+        body[*checkbox] = "on".into();
+    }
+    let response = app.post_newsletters(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let issue = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    (
+        issue.newsletter_issue_id,
+        body["HtmlBody"].as_str().unwrap().to_owned(),
+    )
+}
+
+/// Point a link from an email at the application under test.
+fn local_link(app: &TestApp, link: &str) -> reqwest::Url {
+    let mut link = reqwest::Url::parse(link).unwrap();
+    assert_eq!(link.host_str().unwrap(), "127.0.0.1");
+    link.set_port(Some(app.port)).unwrap();
+    link
+}
+
+fn pixel_url(html: &str) -> Option<&str> {
+    let start = html.find(r#"<img src=""#)? + r#"<img src=""#.len();
+    let end = start + html[start..].find('"')?;
+    Some(&html[start..end])
+}
+
+/// The links of an email that go through the redirect endpoint.
+fn tracked_links<'a>(app: &TestApp, html: &'a str) -> Vec<&'a str> {
+    let prefix = format!(r#"href="{}/r/"#, app.base_url);
+    html.match_indices(&prefix)
+        .map(|(start, _)| {
+            let start = start + r#"href=""#.len();
+            let end = start + html[start..].find('"').unwrap();
+            &html[start..end]
+        })
+        .collect()
+}
+
+#[sqlx::test]
+async fn opens_are_recorded_when_tracking_is_enabled(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let (issue_id, html) = publish_and_deliver(&app, &["track_opens"]).await;
+    let pixel_url = pixel_url(&html).expect("The email has no tracking pixel.");
+    assert!(pixel_url.starts_with(&format!("{}/t/", app.base_url)));
+
+    for _ in 0..2 {
+        let response = reqwest::get(local_link(&app, pixel_url)).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["Content-Type"], "image/gif");
+    }
+
+    let response = app.get_issue_status(&issue_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<tr><th>Sent</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Opens</th><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><th>Unique opens</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Open rate</th><td>100.0%</td></tr>"));
+}
+
+#[sqlx::test]
+async fn issues_are_not_tracked_unless_asked_to(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let (issue_id, html) = publish_and_deliver(&app, &[]).await;
+    assert_eq!(pixel_url(&html), None);
+    assert!(tracked_links(&app, &html).is_empty());
+    assert!(html.contains(r#"href="https://example.com/post?a=1&amp;b=2""#));
+
+    let html_page = app.get_issue_status(&issue_id).await.text().await.unwrap();
+    assert!(html_page.contains("<tr><th>Sent</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Open rate</th><td>Not tracked</td></tr>"));
+    assert!(html_page.contains("<tr><th>Click rate</th><td>Not tracked</td></tr>"));
+}
+
+#[sqlx::test]
+async fn clicks_are_recorded_and_redirected_when_tracking_is_enabled(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let (issue_id, html) = publish_and_deliver(&app, &["track_clicks"]).await;
+    // The view in browser link and the one in the content
+    let links = tracked_links(&app, &html);
+    assert_eq!(links.len(), 2);
+    assert!(!html.contains("https://example.com/post"));
+
+    for _ in 0..2 {
+        let response = app
+            .api_client
+            .get(local_link(&app, links[1]))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 302);
+        assert_eq!(
+            response.headers()["Location"],
+            "https://example.com/post?a=1&b=2"
+        );
+    }
+
+    let html_page = app.get_issue_status(&issue_id).await.text().await.unwrap();
+    assert!(html_page.contains("<tr><th>Clicks</th><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><th>Unique clicks</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Click rate</th><td>100.0%</td></tr>"));
+    assert!(html_page.contains(
+        r#"<tr><td><a href="https://example.com/post?a=1&amp;b=2">https://example.com/post?a=1&amp;b=2</a></td><td>2</td><td>1</td></tr>"#
+    ));
+}
+
+#[sqlx::test]
+async fn forged_click_tokens_do_not_redirect(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/r/Y2xpY2sKeAp5Cmh0dHBzOi8vZXZpbC5jb20.sig",
+            app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert!(response.headers().get("Location").is_none());
+}
+
+#[sqlx::test]
+async fn subscribers_who_opted_out_are_not_tracked(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let subscription_token = sqlx::query!("SELECT subscription_token FROM subscription_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .subscription_token;
+
+    let opt_out_url = format!(
+        "{}/subscriptions/tracking-opt-out?subscription_token={}",
+        app.address, subscription_token
+    );
+    // Following the link only asks for a confirmation, e.g. in case a mail scanner fetched it
+    let response = reqwest::get(&opt_out_url).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber = sqlx::query!("SELECT tracking_opt_out FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!subscriber.tracking_opt_out);
+    let response = reqwest::Client::new()
+        .post(&opt_out_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (_, html) = publish_and_deliver(&app, &["track_opens", "track_clicks"]).await;
+    assert_eq!(pixel_url(&html), None);
+    assert!(tracked_links(&app, &html).is_empty());
+}
+
+#[sqlx::test]
+async fn opting_out_of_tracking_with_an_unknown_token_is_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let url = format!(
+        "{}/subscriptions/tracking-opt-out?subscription_token=unknown",
+        app.address
+    );
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = reqwest::Client::new().post(&url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[sqlx::test]
+async fn forged_tracking_tokens_are_not_recorded(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = reqwest::get(format!("{}/t/b3Blbgp4CnktsignatureZZ", app.address))
+        .await
+        .unwrap();
+
+    // Still an image, so that the email renders fine
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/gif");
+    let n_events = sqlx::query!("SELECT COUNT(*) AS count FROM delivery_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_events, 0);
+}
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_see_the_status_of_an_issue(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app.get_issue_status("0H5N0000000A1").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn the_status_of_an_unknown_issue_is_not_found(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_issue_status("0H5N0000000A1").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
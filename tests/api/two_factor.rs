@@ -0,0 +1,257 @@
+use sqlx::SqlitePool;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// The code an authenticator app would show `steps` time steps from now.
+/// Every accepted code must come from a later step than the previous one.
+fn totp_code(secret: &str, steps: i64) -> String {
+    let totp = TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+        None,
+        String::new(),
+    );
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    totp.generate((now + steps * 30) as u64)
+}
+
+/// Enrol the logged in test user, returning the TOTP secret and the recovery codes.
+pub async fn enable_two_factor(app: &TestApp) -> (String, Vec<String>) {
+    let html_page = app.get_two_factor_html().await;
+    let secret = html_page
+        .split("enter this secret manually: <code>")
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .unwrap()
+        .to_string();
+
+    let response = app.post_two_factor("enable", &totp_code(&secret, -1)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let recovery_codes = html_page
+        .split("<li><code>")
+        .skip(1)
+        .map(|rest| rest.split("</code>").next().unwrap().to_string())
+        .collect();
+    (secret, recovery_codes)
+}
+
+/// Log out and go through the password step again.
+async fn log_in_again(app: &TestApp) {
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[sqlx::test]
+async fn enrolment_shows_a_qr_code_and_the_provisioning_uri(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("Two-factor authentication is disabled."));
+    assert!(html_page.contains("<svg"));
+    assert!(html_page.contains(&format!(
+        "otpauth://totp/zero2prod:{}?",
+        app.test_user.username
+    )));
+
+    // Reloading the page must not invalidate what was just scanned.
+    let secret = |html: &str| html.split("<code>").nth(1).unwrap().to_string();
+    assert_eq!(secret(&html_page), secret(&app.get_two_factor_html().await));
+}
+
+#[sqlx::test]
+async fn enabling_requires_a_valid_code(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    app.get_two_factor_html().await;
+
+    let response = app.post_two_factor("enable", "000000").await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("<p><i>The code is invalid.</i></p>"));
+    assert!(html_page.contains("Two-factor authentication is disabled."));
+}
+
+#[sqlx::test]
+async fn recovery_codes_are_shown_once_and_stored_hashed(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let hashes: Vec<String> = sqlx::query_scalar!(
+        "SELECT code_hash FROM recovery_codes WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(hashes.len(), 10);
+    assert!(hashes.iter().all(|h| h.starts_with("$argon2")));
+    assert!(!hashes.contains(&recovery_codes[0]));
+
+    let html_page = app.get_two_factor_html().await;
+    assert!(html_page.contains("Two-factor authentication is enabled."));
+    assert!(html_page.contains("You have 10 unused recovery codes."));
+    assert!(!html_page.contains(&recovery_codes[0]));
+}
+
+#[sqlx::test]
+async fn the_password_alone_does_not_log_in_once_enrolled(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    enable_two_factor(&app).await;
+
+    log_in_again(&app).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn a_valid_code_completes_the_login_and_cannot_be_replayed(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enable_two_factor(&app).await;
+
+    log_in_again(&app).await;
+    let code = totp_code(&secret, 0);
+    let response = app.post_login_two_factor(&code).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+
+    log_in_again(&app).await;
+    let response = app.post_login_two_factor(&code).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn an_invalid_code_is_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    enable_two_factor(&app).await;
+
+    log_in_again(&app).await;
+    let response = app.post_login_two_factor("123456").await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    let html_page = app
+        .api_client
+        .get(format!("{}/login/two-factor", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The code is invalid.</i></p>"));
+}
+
+#[sqlx::test]
+async fn repeated_invalid_codes_are_throttled(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enable_two_factor(&app).await;
+
+    log_in_again(&app).await;
+    for _ in 0..3 {
+        let response = app.post_login_two_factor("123456").await;
+        assert_is_redirect_to(&response, "/login/two-factor");
+    }
+
+    // Even the right code has to wait, and the password must be entered again.
+    let response = app.post_login_two_factor(&totp_code(&secret, 0)).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(
+        html_page.contains("<p><i>Too many failed login attempts, please try again later.</i></p>")
+    );
+    let response = app.post_login_two_factor(&totp_code(&secret, 0)).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let recorded = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM failed_login_attempts WHERE username = $1",
+        app.test_user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(recorded, 3);
+}
+
+#[sqlx::test]
+async fn a_recovery_code_logs_in_only_once(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+
+    log_in_again(&app).await;
+    let response = app
+        .post_login_two_factor(&recovery_codes[3].to_uppercase())
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    log_in_again(&app).await;
+    let response = app.post_login_two_factor(&recovery_codes[3]).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[sqlx::test]
+async fn the_second_step_requires_a_checked_password(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app.post_login_two_factor("123456").await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn disabling_requires_a_valid_code(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enable_two_factor(&app).await;
+
+    let response = app.post_two_factor("disable", "000000").await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    assert!(app
+        .get_two_factor_html()
+        .await
+        .contains("Two-factor authentication is enabled."));
+
+    let response = app.post_two_factor("disable", &totp_code(&secret, 0)).await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    assert!(app
+        .get_two_factor_html()
+        .await
+        .contains("Two-factor authentication is disabled."));
+
+    // Back to the password alone.
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
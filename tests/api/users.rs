@@ -0,0 +1,301 @@
+use sqlx::SqlitePool;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, post_form, spawn_app, TestApp, TestUser};
+
+/// Invite `email` as the logged in user and return the token of the invitation link.
+async fn invite(app: &TestApp, email: &str, role: &str) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app.post_invitation(email, role).await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = app.get_confirmation_links(&email_request);
+    assert_eq!(links.html.path(), "/invitations/accept");
+    links
+        .html
+        .query_pairs()
+        .find(|(key, _)| key == "invitation_token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+fn accept_form(token: &str, username: &str, password: &str) -> serde_json::Value {
+    serde_json::json!({
+        "invitation_token": token,
+        "username": username,
+        "password": password,
+        "password_check": password
+    })
+}
+
+#[sqlx::test]
+async fn you_must_be_logged_in_to_manage_users(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = app.post_invitation("colleague@example.com", "editor").await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.post_deactivate_user(&app.test_user.user_id).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn invited_users_can_create_their_account_and_log_in(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let token = invite(&app, "colleague@example.com", "editor").await;
+    let html_page = app.get_users_html().await;
+    assert!(
+        html_page.contains("<p><i>An invitation has been sent to colleague@example.com.</i></p>")
+    );
+    assert!(html_page.contains("<li>colleague@example.com (expires on "));
+
+    let response = reqwest::get(format!(
+        "{}/invitations/accept?invitation_token={}",
+        app.address, token
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let password = uuid::Uuid::new_v4().to_string();
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "colleague", &password))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "colleague",
+            "password": password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.test_user.login(&app).await;
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(
+        "<tr><td>colleague</td><td>colleague@example.com</td><td>editor</td><td>active</td>"
+    ));
+
+    // Invitations can only be used once
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "someone-else", &password))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[sqlx::test]
+async fn emails_of_users_and_pending_invitations_cannot_be_invited_again(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "colleague@example.com", "editor").await;
+
+    let response = app.post_invitation("colleague@example.com", "viewer").await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page
+        .contains("<p><i>There already is a pending invitation for this email address.</i></p>"));
+    let n_invitations = sqlx::query!("SELECT COUNT(*) AS count FROM invitations")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_invitations, 1);
+
+    let password = uuid::Uuid::new_v4().to_string();
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "colleague", &password))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.post_invitation("colleague@example.com", "viewer").await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>There already is a user with this email address.</i></p>"));
+}
+
+#[sqlx::test]
+async fn unknown_invitations_are_rejected(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+
+    let response = reqwest::get(format!(
+        "{}/invitations/accept?invitation_token=unknown",
+        app.address
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app
+        .post_accept_invitation(&accept_form(
+            "unknown",
+            "intruder",
+            "a-long-enough-password",
+        ))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+    let n_users = sqlx::query!("SELECT COUNT(*) AS count FROM users")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    // The seeded admin and the test user
+    assert_eq!(n_users, 2);
+}
+
+#[sqlx::test]
+async fn concurrent_accepts_of_an_invitation_create_a_single_account(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "colleague@example.com", "editor").await;
+    let new_client = || {
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .cookie_store(true)
+            .build()
+            .unwrap()
+    };
+    let (first_client, second_client) = (new_client(), new_client());
+    let password = uuid::Uuid::new_v4().to_string();
+    let first_form = accept_form(&token, "colleague", &password);
+    let second_form = accept_form(&token, "impostor", &password);
+
+    let (first, second) = tokio::join!(
+        post_form(
+            &first_client,
+            &app.address,
+            "/invitations/accept",
+            &first_form
+        ),
+        post_form(
+            &second_client,
+            &app.address,
+            "/invitations/accept",
+            &second_form
+        ),
+    );
+
+    let mut statuses = [first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+    assert_eq!(statuses, [303, 404]);
+    let n_users = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM users WHERE username IN ('colleague', 'impostor')"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_users, 1);
+}
+
+#[sqlx::test]
+async fn invited_users_must_choose_a_valid_password_and_a_free_username(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "colleague@example.com", "editor").await;
+    let form_url = format!("/invitations/accept?invitation_token={}", token);
+
+    let test_cases = [
+        (
+            accept_form(&token, "colleague", "short"),
+            "The password must be at least 12 characters long.",
+        ),
+        (
+            serde_json::json!({
+                "invitation_token": token,
+                "username": "colleague",
+                "password": "a-long-enough-password",
+                "password_check": "another-long-password"
+            }),
+            "You entered two different passwords - the field values must match.",
+        ),
+        (
+            accept_form(&token, &app.test_user.username, "a-long-enough-password"),
+            "This username is already taken.",
+        ),
+    ];
+    for (body, message) in test_cases {
+        let response = app.post_accept_invitation(&body).await;
+        assert_is_redirect_to(&response, &form_url);
+
+        let html_page = app
+            .api_client
+            .get(format!("{}{}", app.address, form_url))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(html_page.contains(&format!("<p><i>{message}</i></p>")));
+    }
+}
+
+#[sqlx::test]
+async fn deactivated_users_are_logged_out_and_cannot_log_in_again(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    let colleague = TestUser::generate();
+    colleague.store(&app.db_pool).await;
+    let colleague_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let credentials = colleague.credentials();
+    let log_in_colleague = || post_form(&colleague_client, &app.address, "/login", &credentials);
+    let get_dashboard = || {
+        colleague_client
+            .get(format!("{}/admin/dashboard", app.address))
+            .send()
+    };
+    let response = log_in_colleague().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(get_dashboard().await.unwrap().status().as_u16(), 200);
+
+    app.test_user.login(&app).await;
+    let response = app.post_deactivate_user(&colleague.user_id).await;
+    assert_is_redirect_to(&response, "/admin/users");
+    assert!(app
+        .get_users_html()
+        .await
+        .contains("<p><i>The user has been deactivated.</i></p>"));
+
+    // Their session is gone
+    let response = get_dashboard().await.unwrap();
+    assert_is_redirect_to(&response, "/login");
+    // And they cannot start a new one
+    let response = log_in_colleague().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn you_cannot_deactivate_yourself(pool: SqlitePool) {
+    let app = spawn_app(pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_deactivate_user(&app.test_user.user_id).await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    assert!(app
+        .get_users_html()
+        .await
+        .contains("<p><i>You cannot deactivate your own account.</i></p>"));
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}